@@ -0,0 +1,111 @@
+use crate::types::{FlacJob, FlacStatus};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Prefix of the warnings added by the album check, so they can be told apart
+/// from (and replaced independently of) the per-file findings
+pub const ALBUM_WARNING_PREFIX: &str = "album: ";
+
+/// One STREAMINFO property compared across the tracks of an album
+struct Property {
+    name: &'static str,
+    value: fn(&FlacJob) -> Option<u32>,
+    format: fn(u32) -> String,
+}
+
+const PROPERTIES: [Property; 3] = [
+    Property {
+        name: "sample rate",
+        value: |job| job.sample_rate,
+        format: crate::util::format_sample_rate,
+    },
+    Property {
+        name: "bit depth",
+        value: |job| job.bits_per_sample,
+        format: |bits| bits.to_string(),
+    },
+    Property {
+        name: "channel count",
+        value: |job| job.channels,
+        format: |channels| channels.to_string(),
+    },
+];
+
+/// Compare sample rate, bit depth and channel count of the verified files in each
+/// directory, and add a warning to the tracks that differ from the majority of their
+/// siblings (a stray hi-res track or a mono rip in a stereo album)
+/// Replaces the warnings of any earlier run and moves files between OK and WARNING
+/// to match; returns the number of files flagged
+pub fn check_album_consistency(jobs: &mut [FlacJob]) -> usize {
+    // Start from the per-file findings only
+    for job in jobs.iter_mut() {
+        job.warnings.retain(|w| !w.starts_with(ALBUM_WARNING_PREFIX));
+    }
+
+    // Group by the directory the file was found in, even if it has been quarantined
+    let mut albums: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for (idx, job) in jobs.iter().enumerate() {
+        if matches!(job.status, FlacStatus::Ok | FlacStatus::Warning)
+            && let Some(dir) = job.quarantined_from.as_ref().unwrap_or(&job.path).parent()
+        {
+            albums.entry(dir).or_default().push(idx);
+        }
+    }
+
+    let mut findings: Vec<(usize, String)> = Vec::new();
+    for tracks in albums.values() {
+        for property in &PROPERTIES {
+            let values: Vec<(usize, u32)> = tracks
+                .iter()
+                .filter_map(|&idx| (property.value)(&jobs[idx]).map(|value| (idx, value)))
+                .collect();
+
+            // Without a clear majority there's no telling which tracks are the odd ones
+            let mut counts: HashMap<u32, usize> = HashMap::new();
+            for (_, value) in &values {
+                *counts.entry(*value).or_default() += 1;
+            }
+            let Some((&majority, &count)) = counts.iter().max_by_key(|(_, count)| **count) else {
+                continue;
+            };
+            if count == values.len() || count * 2 <= values.len() {
+                continue;
+            }
+
+            for &(idx, value) in values.iter().filter(|(_, value)| *value != majority) {
+                findings.push((
+                    idx,
+                    format!(
+                        "{}{} {} differs from {} sibling tracks at {}",
+                        ALBUM_WARNING_PREFIX,
+                        property.name,
+                        (property.format)(value),
+                        count,
+                        (property.format)(majority)
+                    ),
+                ));
+            }
+        }
+    }
+    findings.sort();
+
+    let mut flagged = 0;
+    for (idx, warning) in findings {
+        let job = &mut jobs[idx];
+        if !job.warnings.iter().any(|w| w.starts_with(ALBUM_WARNING_PREFIX)) {
+            flagged += 1;
+        }
+        job.warnings.push(warning);
+    }
+
+    // The status follows whatever warnings are left
+    for job in jobs.iter_mut() {
+        match job.status {
+            FlacStatus::Ok if !job.warnings.is_empty() => job.status = FlacStatus::Warning,
+            FlacStatus::Warning if job.warnings.is_empty() => job.status = FlacStatus::Ok,
+            _ => {}
+        }
+    }
+
+    flagged
+}
@@ -1,179 +1,2243 @@
-use crate::types::{FlacStatus, JobFile, Statistics};
+use crate::album::check_album_consistency;
+use crate::lock::JobLock;
+use crate::metadata::{HideBlocks, MetadataChecks, check_metadata};
+use crate::priority::lower_priority;
+use crate::dump::{WavDump, dump_path};
+use crate::quarantine::quarantine_file;
+use crate::runlog::{RunLog, RunSummary};
+use crate::sidecar::{SidecarCache, sidecar_mismatch};
+use crate::tags::find_stream_bounds;
+use crate::terminate::{install_termination_handler, terminated};
+use crate::types::{
+    EXIT_INTERRUPTED, EXIT_MAX_ERRORS, EXIT_TERMINATED, FailurePosition, FlacJob, FlacStatus,
+    JobFile, Statistics, VerifyLevel, find_root, result_exit_code,
+};
+use crate::util::{clock_seed, format_audio_time, format_count, glob_match, shuffle, to_hex};
 use anyhow::{Context, Result};
+use chrono::Utc;
 use claxon::FlacReader;
+use claxon::frame::Block;
+use claxon::metadata::StreamInfo;
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{
+    BinaryBytes, DecimalBytes, HumanDuration, MultiProgress, ProgressBar, ProgressState,
+    ProgressStyle,
+};
 use md5::{Digest, Md5};
+use sha2::Sha256;
 use rayon::prelude::*;
-use std::fs;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, Once, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Size of the byte buffer used to feed decoded samples into the MD5 hasher
+const MD5_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Streams of at least this size are hashed on a separate thread while they decode
+const PIPELINE_MIN_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Whether a hashing thread can actually run next to the decoder
+static MULTI_CORE: LazyLock<bool> = LazyLock::new(|| num_cpus::get() > 1);
+
+/// Buffers of MD5_BUFFER_SIZE that may wait for the hashing thread, bounding its memory use
+const PIPELINE_DEPTH: usize = 16;
+
+/// Pause before retrying a transient I/O error, multiplied by the attempt number
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Reason given to files failed by --require-md5, distinct from a genuine MD5 mismatch
+const NO_MD5_REASON: &str = "no MD5 signature in STREAMINFO (--require-md5)";
+
+/// Reason given when every frame decodes but the audio doesn't match the header MD5
+const MD5_MISMATCH_REASON: &str = "FLAC verification failed: MD5 mismatch, although the whole \
+     stream decoded without errors, so the damage is subtle (e.g. a few flipped bits in the audio)";
+
+/// Set by the Ctrl-C handler; workers stop picking up new files and abort in-flight ones
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// A file queued for checking: job index, size in bytes, path, and its contents if a
+/// reader thread already loaded them
+/// With --max-memory, the item also carries the memory reserved for it
+type WorkItem<'a> = (
+    usize,
+    u64,
+    PathBuf,
+    Option<std::io::Result<Vec<u8>>>,
+    Option<MemoryReservation<'a>>,
+);
+
+/// Memory a file takes while it decodes, whatever its size: the frame buffer (at most
+/// 65535 samples of 8 channels as i32, 2 MiB), the MD5 buffer and the queue of the
+/// hashing thread (16 buffers of 64 KiB)
+const DECODE_MEMORY_ESTIMATE: u64 = 4 * 1024 * 1024;
+
+/// Error returned by verify_flac_file when the run was interrupted mid-file
+#[derive(Debug)]
+struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "check was interrupted")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Context of a claxon decoding error, so the job file can record where decoding failed
+/// (its Display is the error message)
+#[derive(Debug)]
+struct DecodeFailure {
+    message: String,
+    position: FailurePosition,
+}
+
+impl std::fmt::Display for DecodeFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Counts the bytes claxon has read, to tell roughly where in the file a frame is
+/// (claxon reads ahead, so the count can be up to its buffer size past the frame)
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+/// Install the Ctrl-C handler (only once per process) and reset the interrupted flag
+/// The first Ctrl-C requests a graceful stop; a second one exits immediately
+fn install_interrupt_handler() {
+    static INSTALL: Once = Once::new();
+
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    INSTALL.call_once(|| {
+        let result = ctrlc::set_handler(|| {
+            if INTERRUPTED.swap(true, Ordering::SeqCst) {
+                // Second Ctrl-C: the user really wants out
+                crate::lock::release_held_lock();
+                std::process::exit(EXIT_INTERRUPTED as i32);
+            }
+            eprintln!(
+                "\n{} Interrupted, finishing up and saving the job file (press Ctrl-C again to force quit)...",
+                "⚠".yellow().bold()
+            );
+        });
+
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to install Ctrl-C handler: {}", e);
+        }
+    });
+}
+
+/// Options controlling a check run
+pub struct CheckOptions {
+    /// Number of parallel threads (defaults to number of CPU cores)
+    pub threads: Option<usize>,
+    /// Number of threads reading files from disk (defaults to the thread count)
+    pub io_threads: Option<usize>,
+    /// Continue checking even if errors occur
+    pub continue_on_error: bool,
+    /// How often to persist the job file (None means after every status change)
+    pub save_interval: Option<SaveInterval>,
+    /// Give up on a single file after this long and mark it as Error
+    pub timeout: Option<Duration>,
+    /// Only check files with these statuses (empty means DEFAULT_STATUSES)
+    pub statuses: Vec<FlacStatus>,
+    /// Also re-verify files previously marked OK (bit-rot monitoring)
+    pub recheck_ok: bool,
+    /// Also retry files previously found to be missing
+    pub retry_missing: bool,
+    /// Stop dispatching new files after the first Bad or Error result
+    pub fail_fast: bool,
+    /// Abort the run once this many files have finished with Error
+    pub max_errors: Option<usize>,
+    /// Check at most this many files, leaving the rest for later runs
+    pub limit: Option<usize>,
+    /// Stop starting new files once the run has taken this long
+    pub max_duration: Option<Duration>,
+    /// Order in which the selected files are handed to the workers
+    pub order: CheckOrder,
+    /// Seed for --order random, to hand the files out in the same order again
+    pub seed: Option<u64>,
+    /// Glob patterns of files to leave out of this run (marked Skipped)
+    pub skip: Vec<String>,
+    /// List the files an earlier, interrupted run left in CHECKING
+    pub show_interrupted: bool,
+    /// Write the results to this new file instead of updating the job file
+    pub results: Option<PathBuf>,
+    /// Take over a stale lock file left behind by a process that no longer exists
+    pub force: bool,
+    /// Show one progress line per worker thread (on a terminal)
+    pub per_thread_progress: bool,
+    /// Lower CPU and I/O priority so the check runs in the background
+    pub low_priority: bool,
+    /// Move files found to be Bad into this directory
+    pub quarantine: Option<PathBuf>,
+    /// Write the decoded audio of Bad files to WAV files in this directory
+    pub dump_bad: Option<PathBuf>,
+    /// Total bytes of WAV the dumps of one run may write
+    pub dump_bad_max_size: u64,
+    /// Retry a file this many times when it fails with a transient I/O error
+    pub retries: u32,
+    /// Append a line per checked file (plus run start/end markers) to this file
+    pub log_file: Option<PathBuf>,
+    /// Compare files against checksums in .ffp/.md5 files in their directory
+    pub verify_sidecars: bool,
+    /// Treat warnings (e.g. ID3 tags around the stream) as Bad
+    pub strict: bool,
+    /// Also hash the whole file and compare it with the hash recorded by the last run
+    pub hash_file: bool,
+    /// Don't start files that would take the memory of running ones over this many bytes
+    pub max_memory: Option<u64>,
+    /// Mark files whose header has no MD5 signature as Bad
+    pub require_md5: bool,
+    /// Where the library is mounted, for a job file with relative paths
+    pub root: Option<PathBuf>,
+    /// Settings for verifying each individual file
+    pub verify: VerifySettings,
+}
+
+/// Settings that affect how a single file is verified
+/// Cloned into the watchdog thread when --timeout is used
+#[derive(Debug, Clone, Default)]
+pub struct VerifySettings {
+    /// External `flac` binary used to re-test files claxon cannot decode
+    pub fallback_flac: Option<PathBuf>,
+    /// Validate metadata blocks (Vorbis comments, pictures) and record problems as warnings
+    pub check_metadata: bool,
+    /// Check that SEEKTABLE entries point at real frames, recording problems as warnings
+    pub check_seektable: bool,
+    /// Hash on a separate thread for every file, not just those of PIPELINE_MIN_SIZE or more
+    pub pipeline: bool,
+    /// Only check the headers, metadata blocks and first frame, without decoding the audio
+    pub quick: bool,
+}
+
+/// Outcome of verifying a single file that could be fully examined
+#[derive(Debug)]
+pub struct Verification {
+    /// Whether the file passed or failed
+    pub verdict: Verdict,
+    /// Which decoder produced the verdict ("claxon" or "flac")
+    pub verifier: &'static str,
+    /// MD5 of the decoded audio as lowercase hex (None when the flac binary decided)
+    pub audio_md5: Option<String>,
+    /// MD5 stored in the STREAMINFO header as lowercase hex (None if the header has none)
+    pub header_md5: Option<String>,
+    /// Problems that don't make the file Bad unless --strict is given
+    pub warnings: Vec<String>,
+    /// Audio format from STREAMINFO (None when the flac binary decided)
+    pub format: Option<AudioFormat>,
+    /// Where the stream is damaged, for Bad files cut off or missing frames at a known spot
+    pub failed_at: Option<FailurePosition>,
+    /// Whether the whole stream was decoded or only its headers (--quick)
+    pub level: VerifyLevel,
+}
+
+/// The STREAMINFO properties compared across the tracks of an album
+#[derive(Debug)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub bits_per_sample: u32,
+    pub channels: u32,
+    /// Samples per channel, if the header says
+    pub total_samples: Option<u64>,
+}
+
+/// Result of decoding a whole stream with claxon
+struct StreamCheck {
+    verdict: Verdict,
+    /// MD5 computed from the decoded audio (None after a --quick check)
+    audio_md5: Option<[u8; 16]>,
+    /// MD5 stored in the STREAMINFO header, if any
+    header_md5: Option<[u8; 16]>,
+    /// Problems that don't make the file Bad on their own
+    warnings: Vec<String>,
+    /// The STREAMINFO header of the stream
+    streaminfo: StreamInfo,
+    /// Where decoding went wrong, when the verdict is about a specific spot
+    failed_at: Option<FailurePosition>,
+}
+
+/// Pass/fail verdict for a verified file
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    /// File is valid
+    Ok,
+    /// File is corrupted, with a description of what is wrong
+    Bad(String),
+}
+
+/// Statuses that are (re-)checked when no --status filter is given
+/// Files with status CHECKING are re-checked in case of a previous interruption
+pub const DEFAULT_STATUSES: [FlacStatus; 4] = [
+    FlacStatus::ToBeChecked,
+    FlacStatus::Checking,
+    FlacStatus::Error,
+    FlacStatus::Skipped,
+];
+
+/// How often the job file is written to disk during a check run
+#[derive(Debug, Clone, Copy)]
+pub enum SaveInterval {
+    /// Save after this many files have been checked
+    Files(usize),
+    /// Save when at least this much time has passed since the last save
+    Time(Duration),
+}
+
+impl FromStr for SaveInterval {
+    type Err = String;
+
+    /// Parse either a plain file count ("50") or a duration ("30s", "5m")
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(count) = s.trim().parse::<usize>() {
+            if count == 0 {
+                return Err("save interval must be at least 1 file".to_string());
+            }
+            return Ok(SaveInterval::Files(count));
+        }
+
+        crate::util::parse_duration(s).map(SaveInterval::Time)
+    }
+}
+
+/// Order in which a check run works through its files
+/// Only scheduling is affected; the jobs keep their order in the job file
+#[derive(Debug, Clone, Copy, Default)]
+pub enum CheckOrder {
+    /// By path, so the files of an album are read one after another
+    #[default]
+    Path,
+    /// Largest files first, so no big file is left running alone at the end
+    Size,
+    /// Shuffled, spreading the load across disks and directories
+    Random,
+    /// Interrupted and never-checked files first, then retries, then rechecks
+    StatusPriority,
+}
+
+impl FromStr for CheckOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "path" => Ok(CheckOrder::Path),
+            "size" => Ok(CheckOrder::Size),
+            "random" => Ok(CheckOrder::Random),
+            "status-priority" | "status" => Ok(CheckOrder::StatusPriority),
+            _ => Err(format!(
+                "unknown order '{}' (expected path, size, random or status-priority)",
+                s
+            )),
+        }
+    }
+}
+
+/// Decides when the job file should be persisted based on the save interval
+struct SaveThrottle {
+    interval: Option<SaveInterval>,
+    files_since_save: usize,
+    last_save: Instant,
+}
+
+impl SaveThrottle {
+    fn new(interval: Option<SaveInterval>) -> Self {
+        SaveThrottle {
+            interval,
+            files_since_save: 0,
+            last_save: Instant::now(),
+        }
+    }
+
+    /// Record a status change and return true if the job file should be saved now
+    /// `file_finished` is true when the change is a final result rather than CHECKING
+    fn should_save(&mut self, file_finished: bool) -> bool {
+        if file_finished {
+            self.files_since_save += 1;
+        }
+
+        let due = match self.interval {
+            None => true,
+            Some(SaveInterval::Files(count)) => self.files_since_save >= count,
+            Some(SaveInterval::Time(duration)) => self.last_save.elapsed() >= duration,
+        };
+
+        if due {
+            self.files_since_save = 0;
+            self.last_save = Instant::now();
+        }
+
+        due
+    }
+}
+
+/// A status change sent from a worker to the job file writer
+enum Update {
+    /// The file at this index is now being checked
+    Started(usize),
+    /// The file no longer exists on disk
+    Missing(usize),
+    /// The check was aborted by Ctrl-C, so the file goes back to its previous status
+    Aborted(usize),
+    /// The file was fully examined
+    Finished {
+        idx: usize,
+        // Boxed, as a Verification is much larger than the other updates
+        result: Box<Result<Verification>>,
+        duration_ms: u64,
+        metadata: Option<Box<fs::Metadata>>,
+        /// New location of the file if it was moved by --quarantine
+        quarantined: Option<PathBuf>,
+        /// SHA-256 of the whole file, with --hash-file
+        file_sha256: Option<String>,
+    },
+}
+
+/// What happened during a run, tallied by the writer as results come in
+#[derive(Default)]
+struct RunTally {
+    /// Files that finished with OK
+    ok: usize,
+    /// Files that finished with Warning
+    warnings: usize,
+    /// Files that turned out to be missing
+    missing: usize,
+    /// Files that were OK before this run and have now failed verification
+    regressed: usize,
+    /// Files that finished with Bad
+    bad: usize,
+    /// Bad files that were only failed by --require-md5
+    no_md5: usize,
+    /// Files that finished with Error
+    errors: usize,
+    /// The first file that failed, reported prominently with --fail-fast
+    first_failure: Option<(PathBuf, String)>,
+    /// Bad files moved by --quarantine
+    quarantined: usize,
+}
+
+/// Owns the job file during a check run. Workers send it updates over a channel, so
+/// they never wait on each other to record a result, and updates are applied (and
+/// saved) strictly in the order they arrive.
+struct JobWriter<'a> {
+    job_file: JobFile,
+    path: &'a Path,
+    throttle: SaveThrottle,
+    /// Status and message each in-flight file had before it was marked CHECKING
+    previous: HashMap<usize, (FlacStatus, Option<String>)>,
+    fail_fast: bool,
+    /// Per-file log written with --log-file
+    log: Option<RunLog>,
+    tally: RunTally,
+}
+
+impl<'a> JobWriter<'a> {
+    fn new(
+        job_file: JobFile,
+        path: &'a Path,
+        options: &CheckOptions,
+        log: Option<RunLog>,
+    ) -> Self {
+        JobWriter {
+            job_file,
+            path,
+            throttle: SaveThrottle::new(options.save_interval),
+            previous: HashMap::new(),
+            fail_fast: options.fail_fast,
+            log,
+            tally: RunTally::default(),
+        }
+    }
+
+    /// Apply updates until every worker has hung up, then hand back the job file
+    fn run(mut self, updates: mpsc::Receiver<Update>) -> (JobFile, RunTally, Option<RunLog>) {
+        while let Ok(update) = updates.recv() {
+            let mut save_due = self.apply(update);
+
+            // Apply whatever else queued up in the meantime before paying for a save
+            while let Ok(update) = updates.try_recv() {
+                save_due |= self.apply(update);
+            }
+
+            if save_due {
+                self.job_file.statistics = Statistics::from_jobs(&self.job_file.jobs);
+                if let Err(e) = self.job_file.save(self.path) {
+                    eprintln!("Warning: Failed to save job file: {}", e);
+                }
+            }
+        }
+
+        (self.job_file, self.tally, self.log)
+    }
+
+    /// Apply a single update and return true if the job file is due to be saved
+    fn apply(&mut self, update: Update) -> bool {
+        match update {
+            Update::Started(idx) => {
+                let job = &mut self.job_file.jobs[idx];
+                self.previous
+                    .insert(idx, (job.status.clone(), job.error_message.take()));
+                job.status = FlacStatus::Checking;
+
+                // Persist the CHECKING status (immediately unless --save-interval says otherwise)
+                self.throttle.should_save(false)
+            }
+            Update::Missing(idx) => {
+                self.previous.remove(&idx);
+                let job = &mut self.job_file.jobs[idx];
+                job.status = FlacStatus::Missing;
+                job.error_message = Some("File not found".to_string());
+                job.skip_pattern = None;
+                job.failed_at = None;
+                job.checked_at = Some(Utc::now());
+                self.tally.missing += 1;
+                if let Some(log) = &mut self.log {
+                    log.file(job, None);
+                }
+                self.throttle.should_save(true)
+            }
+            Update::Aborted(idx) => {
+                // An aborted check says nothing about the file, so put it back in the queue
+                if let Some((status, message)) = self.previous.remove(&idx) {
+                    let job = &mut self.job_file.jobs[idx];
+                    job.status = status;
+                    job.error_message = message;
+                }
+                false
+            }
+            Update::Finished {
+                idx,
+                result,
+                duration_ms,
+                metadata,
+                quarantined,
+                file_sha256,
+            } => {
+                let previous_status = self
+                    .previous
+                    .remove(&idx)
+                    .map(|(status, _)| status)
+                    .unwrap_or_default();
+                let job = &mut self.job_file.jobs[idx];
+                job.checked_at = Some(Utc::now());
+                job.check_duration_ms = Some(duration_ms);
+                job.skip_pattern = None;
+
+                // Compare against the size/mtime recorded at explore time (if any)
+                if let Some(changed) = metadata.and_then(|m| job.metadata_changed(&m)) {
+                    job.changed = changed;
+                }
+
+                // Follow the file to the quarantine directory, remembering where it was
+                if let Some(new_path) = quarantined {
+                    job.quarantined_from = Some(std::mem::replace(&mut job.path, new_path));
+                    self.tally.quarantined += 1;
+                }
+
+                match *result {
+                    Ok(verification) => {
+                        job.verifier = Some(verification.verifier.to_string());
+                        job.audio_md5 = verification.audio_md5;
+                        job.warnings = verification.warnings;
+                        job.failed_at = verification.failed_at;
+                        job.verified = Some(verification.level);
+                        if file_sha256.is_some() {
+                            job.file_sha256 = file_sha256;
+                        }
+                        // The flac binary doesn't say whether the header had an MD5
+                        job.has_md5 = (verification.verifier == "claxon")
+                            .then_some(verification.header_md5.is_some());
+                        if let Some(format) = verification.format {
+                            job.record_format(
+                                format.sample_rate,
+                                format.bits_per_sample,
+                                format.channels,
+                                format.total_samples,
+                            );
+                        }
+                        match verification.verdict {
+                            // Verified files with findings such as ID3 tags get their own status
+                            Verdict::Ok if !job.warnings.is_empty() => {
+                                job.status = FlacStatus::Warning;
+                                job.error_message = None;
+                                self.tally.warnings += 1;
+                            }
+                            Verdict::Ok => {
+                                job.status = FlacStatus::Ok;
+                                job.error_message = None;
+                                self.tally.ok += 1;
+                            }
+                            Verdict::Bad(reason) => {
+                                if reason == NO_MD5_REASON {
+                                    self.tally.no_md5 += 1;
+                                }
+                                job.status = FlacStatus::Bad;
+                                job.error_message = Some(reason);
+                                self.tally.bad += 1;
+                                if matches!(previous_status, FlacStatus::Ok | FlacStatus::Warning) {
+                                    self.tally.regressed += 1;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        job.status = FlacStatus::Error;
+                        job.error_message = Some(e.to_string());
+                        job.failed_at = e.downcast_ref::<DecodeFailure>().map(|f| f.position);
+                        job.verifier = None;
+                        job.audio_md5 = None;
+                        job.verified = None;
+                        job.warnings.clear();
+                        job.has_md5 = None;
+                        self.tally.errors += 1;
+                    }
+                }
+
+                // With --fail-fast, remember the failure that stopped the run
+                if self.fail_fast && matches!(job.status, FlacStatus::Bad | FlacStatus::Error) {
+                    self.tally.first_failure.get_or_insert_with(|| {
+                        (
+                            job.path.clone(),
+                            job.error_message.clone().unwrap_or_default(),
+                        )
+                    });
+                }
+
+                if let Some(log) = &mut self.log {
+                    log.file(job, Some(duration_ms));
+                }
+
+                // Save job file after each update by default (slower but safer in case of
+                // interruption), or only as often as --save-interval allows
+                self.throttle.should_save(true)
+            }
+        }
+    }
+}
+
+/// Memory that files being read or decoded may hold at once (--max-memory)
+/// Files wait for running ones to finish until theirs fits, except when nothing else is
+/// running: a file larger than the whole budget is then checked on its own.
+struct MemoryBudget {
+    limit: u64,
+    used: Mutex<u64>,
+    freed: std::sync::Condvar,
+}
+
+impl MemoryBudget {
+    fn new(limit: u64) -> Self {
+        MemoryBudget {
+            limit,
+            used: Mutex::new(0),
+            freed: std::sync::Condvar::new(),
+        }
+    }
+
+    /// Wait until `bytes` fit in the budget and hold them until the reservation is dropped
+    fn reserve(&self, bytes: u64) -> MemoryReservation<'_> {
+        let mut used = self.used.lock().unwrap();
+        while *used > 0 && *used + bytes > self.limit {
+            used = self.freed.wait(used).unwrap();
+        }
+        *used += bytes;
+        MemoryReservation {
+            budget: self,
+            bytes,
+        }
+    }
+}
+
+/// Memory held by one file, given back to the budget when dropped
+struct MemoryReservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl Drop for MemoryReservation<'_> {
+    fn drop(&mut self) {
+        *self.budget.used.lock().unwrap() -= self.bytes;
+        self.budget.freed.notify_all();
+    }
+}
+
+/// Files that are currently being checked, so a stalled run shows which file it is
+/// stuck on. They are listed on the line below the progress bar, or with
+/// --per-thread-progress on the line of the worker thread checking them.
+struct InFlight {
+    files: Mutex<Vec<(usize, String)>>,
+    /// Columns available for the list
+    width: usize,
+    /// One spinner per worker thread (empty unless --per-thread-progress is active)
+    worker_bars: Vec<ProgressBar>,
+}
+
+impl InFlight {
+    fn new(worker_bars: Vec<ProgressBar>) -> Self {
+        InFlight {
+            files: Mutex::new(Vec::new()),
+            width: (console::Term::stderr().size().1 as usize).saturating_sub(3),
+            worker_bars,
+        }
+    }
+
+    /// Show a file as being checked until the returned guard is dropped
+    fn track<'a>(&'a self, pb: &'a ProgressBar, idx: usize, path: &Path) -> InFlightGuard<'a> {
+        let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+            .into_owned();
+
+        // Each worker thread has its own line when per-thread bars are shown
+        let worker_bar = rayon::current_thread_index().and_then(|i| self.worker_bars.get(i));
+        if let Some(bar) = worker_bar {
+            bar.reset_elapsed();
+            bar.set_message(name);
+            return InFlightGuard {
+                in_flight: self,
+                pb,
+                idx,
+                worker_bar,
+            };
+        }
+
+        let mut files = self.files.lock().unwrap();
+        files.push((idx, name));
+        // Update the message while still holding the lock so updates can't land out of order
+        pb.set_message(self.message(&files));
+
+        InFlightGuard {
+            in_flight: self,
+            pb,
+            idx,
+            worker_bar: None,
+        }
+    }
+
+    /// Remove the per-thread bars once the run is over
+    fn finish(&self) {
+        for bar in &self.worker_bars {
+            bar.finish_and_clear();
+        }
+    }
+
+    /// Build e.g. "3 in flight: trackA.flac, trackB.flac, …" cut to the terminal width
+    fn message(&self, files: &[(usize, String)]) -> String {
+        if files.is_empty() {
+            return String::new();
+        }
+
+        let mut message = format!("{} in flight: ", files.len());
+        for (i, (_, name)) in files.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            let used = message.chars().count() + separator.len();
+
+            // Room for this name plus a trailing ", …" if more names follow
+            let reserve = if i + 1 < files.len() { 3 } else { 0 };
+            if used + name.chars().count() + reserve <= self.width {
+                message.push_str(separator);
+                message.push_str(name);
+                continue;
+            }
+
+            // The first name is shortened rather than dropped
+            if i == 0 {
+                let keep = self.width.saturating_sub(used + 1);
+                message.extend(name.chars().take(keep));
+                message.push('…');
+            } else {
+                message.push_str(", …");
+            }
+            break;
+        }
+
+        message
+    }
+}
+
+/// Removes a file from the in-flight list when its check is over (however it ended)
+struct InFlightGuard<'a> {
+    in_flight: &'a InFlight,
+    pb: &'a ProgressBar,
+    idx: usize,
+    worker_bar: Option<&'a ProgressBar>,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(bar) = self.worker_bar {
+            bar.set_message("idle");
+            return;
+        }
+
+        let mut files = self
+            .in_flight
+            .files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        files.retain(|(idx, _)| *idx != self.idx);
+        self.pb.set_message(self.in_flight.message(&files));
+    }
+}
 
 /// Check FLAC files from a job file using parallel processing
-pub fn check_flac_files(
-    job_file_path: PathBuf,
-    threads: Option<usize>,
-    continue_on_error: bool,
-) -> Result<()> {
+/// Returns the process exit code (0 on success, EXIT_BAD/EXIT_ERROR for failed files,
+/// EXIT_MAX_ERRORS, EXIT_INTERRUPTED or EXIT_TERMINATED when the run was cut short)
+pub fn check_flac_files(job_file_path: PathBuf, options: CheckOptions) -> Result<u8> {
+    // A job file read from stdin can't be written back
+    let from_stdin = job_file_path == Path::new("-");
+    if from_stdin && options.results.is_none() {
+        anyhow::bail!("A job file read from stdin (`-`) needs --results to say where the results go");
+    }
+    if options.seed.is_some() && !matches!(options.order, CheckOrder::Random) {
+        anyhow::bail!("--seed only applies to --order random");
+    }
+
+    // With --results, the job file is only read and this run writes its own file
+    let output_path = options.results.clone().unwrap_or_else(|| job_file_path.clone());
+
+    // Keep other check runs off the file being written until we return (or unwind)
+    let _lock = JobLock::acquire(&output_path, options.force)?;
+    if options.results.is_some() && output_path.exists() {
+        anyhow::bail!(
+            "Results file already exists: {} (to continue from it, pass it as the job file)",
+            output_path.display()
+        );
+    }
+
     println!("{} Loading job file...", "→".blue().bold());
 
-    // Read and parse the job file
-    let job_file_content = fs::read_to_string(&job_file_path)
-        .with_context(|| format!("Failed to read job file: {}", job_file_path.display()))?;
+    // Read and parse the job file
+    let mut job_file = JobFile::load_with_root(&job_file_path, options.root.as_deref())?;
+
+    // The results file starts as a copy of the job, remembering which plan it came from
+    // (a results file used as the input keeps pointing at the original plan)
+    if options.results.is_some() {
+        if !from_stdin {
+            job_file.results_of.get_or_insert_with(|| job_file_path.clone());
+        }
+        job_file.save(&output_path)?;
+        println!(
+            "{} Writing results to {} (the job file is left unchanged)",
+            "→".blue().bold(),
+            output_path.display()
+        );
+    }
+
+    // Files still CHECKING were in flight when an earlier run died. Put them back in the
+    // queue on disk right away, so the job stays truthful even if this run is cut short too.
+    let interrupted_files: Vec<usize> = job_file
+        .jobs
+        .iter()
+        .enumerate()
+        .filter(|(_, job)| job.status == FlacStatus::Checking)
+        .map(|(idx, _)| idx)
+        .collect();
+    if !interrupted_files.is_empty() {
+        println!(
+            "{} Found {} files left in CHECKING from an interrupted run, they will be re-verified",
+            "⚠".yellow().bold(),
+            interrupted_files.len()
+        );
+        for &idx in &interrupted_files {
+            if options.show_interrupted {
+                let path = job_file.relative_path(&job_file.jobs[idx].path);
+                println!("  {} {}", "◐".cyan(), path.display());
+            }
+            job_file.jobs[idx].status = FlacStatus::ToBeChecked;
+        }
+        job_file.statistics = Statistics::from_jobs(&job_file.jobs);
+        job_file.save(&output_path)?;
+    }
+
+    // Configure thread pool size
+    // The pool is local to this run so check can be called more than once per process
+    let thread_count = options.threads.unwrap_or_else(num_cpus::get);
+
+    // --low-priority: threads started from here on (readers, writer) inherit the setting,
+    // and the rayon workers apply it to themselves since Linux priorities are per thread
+    if options.low_priority {
+        let applied = lower_priority();
+        if !applied.is_empty() {
+            println!("{} Low priority: {}", "→".blue().bold(), applied.join(", "));
+        }
+    }
+    let low_priority = options.low_priority;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(thread_count)
+        .start_handler(move |_| {
+            if low_priority {
+                lower_priority();
+            }
+        })
+        .build()
+        .context("Failed to initialize thread pool")?;
+
+    println!(
+        "{} Using {} threads for parallel checking",
+        "→".blue().bold(),
+        thread_count
+    );
+    if options.verify.quick {
+        println!(
+            "{} Quick check: headers, metadata blocks and the first frame only, audio is not verified",
+            "→".blue().bold()
+        );
+    }
+
+    // With fewer reader threads than decode threads, files are read sequentially into
+    // memory by dedicated readers (avoids seek thrashing on spinning disks)
+    let io_threads = options
+        .io_threads
+        .unwrap_or(thread_count)
+        .clamp(1, thread_count);
+    // A quick check only reads the start of each file, so there is nothing to read ahead
+    let pipelined = io_threads < thread_count && !options.verify.quick;
+    if pipelined {
+        println!(
+            "{} Using {} reader threads feeding the decoders",
+            "→".blue().bold(),
+            io_threads
+        );
+    }
+
+    // Decide which statuses get checked: the --status filter, or the default set
+    let filtered = !options.statuses.is_empty();
+    let mut statuses: Vec<FlacStatus> = if filtered {
+        options.statuses.clone()
+    } else {
+        DEFAULT_STATUSES.to_vec()
+    };
+    if options.recheck_ok {
+        for status in [FlacStatus::Ok, FlacStatus::Warning] {
+            if !statuses.contains(&status) {
+                statuses.push(status);
+            }
+        }
+    }
+    if options.retry_missing && !statuses.contains(&FlacStatus::Missing) {
+        statuses.push(FlacStatus::Missing);
+    }
+
+    // Files that passed only a --quick check are due for their full verification
+    let upgrade_quick = !filtered && !options.verify.quick;
+
+    // Count how many files need to be checked
+    let mut files_to_check: Vec<usize> = job_file
+        .jobs
+        .iter()
+        .enumerate()
+        .filter(|(_, job)| statuses.contains(&job.status) || (upgrade_quick && job.quick_only()))
+        .map(|(idx, _)| idx)
+        .collect();
+    let upgrades = files_to_check
+        .iter()
+        .filter(|&&idx| !statuses.contains(&job_file.jobs[idx].status))
+        .count();
+    if upgrades > 0 {
+        println!(
+            "{} Including {} files that so far only had a quick check (--quick)",
+            "→".blue().bold(),
+            upgrades
+        );
+    }
+
+    // --skip: leave matching files out of this run, remembering which pattern matched
+    let mut skipped = 0;
+    if !options.skip.is_empty() {
+        let root_directories = job_file.root_directories.clone();
+        files_to_check.retain(|&idx| {
+            let job = &mut job_file.jobs[idx];
+            let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+            let Some(pattern) = skip_pattern(&options.skip, path, &root_directories) else {
+                return true;
+            };
+            job.status = FlacStatus::Skipped;
+            job.skip_pattern = Some(pattern.clone());
+            skipped += 1;
+            false
+        });
+        println!(
+            "{} Skipping {} files matching --skip",
+            "→".blue().bold(),
+            skipped
+        );
+        if skipped > 0 {
+            job_file.statistics = Statistics::from_jobs(&job_file.jobs);
+            job_file.save(&output_path)?;
+        }
+    }
+
+    if files_to_check.is_empty() {
+        if filtered {
+            println!(
+                "{} No files match status filter: {}",
+                "✓".green().bold(),
+                status_list(&statuses)
+            );
+        } else {
+            println!("{} No files to check!", "✓".green().bold());
+        }
+        return Ok(0);
+    }
+
+    // --limit: take files that were never checked first, then those checked longest ago,
+    // so repeated runs work through the whole job even when OK files are rechecked
+    // With --order random, files checked equally long ago (such as all the new ones) are
+    // picked at random too, so the runs cover the library evenly
+    let seed = options.seed.unwrap_or_else(clock_seed);
+    let mut deferred = 0;
+    if let Some(limit) = options.limit
+        && files_to_check.len() > limit
+    {
+        if matches!(options.order, CheckOrder::Random) {
+            shuffle(&mut files_to_check, seed);
+        }
+        files_to_check.sort_by_key(|&idx| job_file.jobs[idx].checked_at);
+        deferred = files_to_check.len() - limit;
+        files_to_check.truncate(limit);
+    }
+    sort_queue(&mut files_to_check, &job_file.jobs, options.order, seed);
+    let limit_note = if deferred > 0 {
+        format!(" ({} of them this run, --limit)", files_to_check.len())
+    } else {
+        String::new()
+    };
+
+    if filtered {
+        println!(
+            "{} Found {} files to check matching status filter: {}{}",
+            "→".blue().bold(),
+            files_to_check.len() + deferred,
+            status_list(&statuses),
+            limit_note
+        );
+    } else {
+        println!(
+            "{} Found {} files to check{}",
+            "→".blue().bold(),
+            files_to_check.len() + deferred,
+            limit_note
+        );
+    }
+    if matches!(options.order, CheckOrder::Random) {
+        println!(
+            "{} Random order (seed {}; --seed {} gives the same order again)",
+            "→".blue().bold(),
+            seed,
+            seed
+        );
+    }
+
+    // Look up file sizes so progress (and the ETA) is driven by bytes rather than
+    // file counts, using the sizes recorded at explore time where there are any
+    let sizes: Vec<Option<u64>> = files_to_check
+        .iter()
+        .map(|&idx| {
+            let job = &job_file.jobs[idx];
+            job.size.or_else(|| fs::metadata(&job.path).map(|m| m.len()).ok())
+        })
+        .collect();
+
+    // Files that can't be stat'ed count as an average file, which keeps the estimate sane
+    let known: Vec<u64> = sizes.iter().flatten().copied().collect();
+    let average_size = known.iter().sum::<u64>() / (known.len() as u64).max(1);
+    let files_to_check: Vec<(usize, u64, PathBuf)> = files_to_check
+        .into_iter()
+        .zip(sizes)
+        .map(|(idx, size)| (idx, size.unwrap_or(average_size), job_file.jobs[idx].path.clone()))
+        .collect();
+    let total_bytes: u64 = files_to_check.iter().map(|(_, size, _)| size).sum();
+    let total_count = files_to_check.len();
+
+    // Checksums from .ffp/.md5 files, read once per directory
+    let sidecars = SidecarCache::new();
+
+    // Bad files are quarantined relative to their library root
+    let root_directories = job_file.root_directories.clone();
+
+    // With --per-thread-progress, each worker gets a spinner line above the overall bar.
+    // That only works on a terminal; otherwise fall back to the single bar.
+    let per_thread = options.per_thread_progress && console::Term::stderr().is_term();
+    if options.per_thread_progress && !per_thread {
+        println!(
+            "{} Not a terminal, showing overall progress only",
+            "→".blue().bold()
+        );
+    }
+
+    let multi = MultiProgress::new();
+    let worker_bars: Vec<ProgressBar> = if per_thread {
+        (0..thread_count)
+            .map(|i| {
+                let bar = multi.add(ProgressBar::new_spinner());
+                bar.set_style(
+                    ProgressStyle::default_spinner()
+                        .template("{spinner:.green} thread {prefix}: [{elapsed_precise}] {wide_msg}")
+                        .unwrap(),
+                );
+                bar.set_prefix(format!("{:>2}", i + 1));
+                bar.set_message("idle");
+                bar.enable_steady_tick(Duration::from_millis(200));
+                bar
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Create progress bar
+    // Without per-thread bars, the line below it lists the files being checked
+    // The time left is extrapolated from the average speed of the run so far: bytes only
+    // advance when a file is done, which makes indicatif's own (recent speed) ETA jump
+    // around when short and long tracks are mixed
+    let template = if per_thread {
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {decimal_bytes}/{decimal_total_bytes} ({percent}%, {decimal_bytes_per_sec}, {time_left}) {prefix}"
+    } else {
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {decimal_bytes}/{decimal_total_bytes} ({percent}%, {decimal_bytes_per_sec}, {time_left}) {prefix}\n  {wide_msg}"
+    };
+    let pb = ProgressBar::new(total_bytes);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(template)
+            .unwrap()
+            .with_key("time_left", |state: &ProgressState, w: &mut dyn fmt::Write| {
+                let _ = match time_left(state.elapsed(), state.pos(), state.len()) {
+                    Some(left) => write!(w, "~{} left", HumanDuration(left)),
+                    None => write!(w, "estimating…"),
+                };
+            })
+            .progress_chars("#>-")
+    );
+    pb.set_prefix(format!("0/{} files", total_count));
+    let pb = if per_thread { multi.add(pb) } else { pb };
+    let in_flight = InFlight::new(worker_bars);
+    let started = Instant::now();
+
+    // Files and bytes that have been fully verified (not counting aborted files)
+    let files_done = AtomicUsize::new(0);
+    let bytes_done = AtomicU64::new(0);
+
+    // Move the progress bar forward once a file is finished with
+    let advance_progress = |size: u64| {
+        let done = files_done.fetch_add(1, Ordering::Relaxed) + 1;
+        pb.inc(size);
+        pb.set_prefix(format!("{}/{} files", done, total_count));
+    };
+
+    // Stop dispatching new work when Ctrl-C is pressed, or when a service manager
+    // terminates the run
+    install_interrupt_handler();
+    install_termination_handler(&INTERRUPTED, EXIT_TERMINATED);
+
+    // Set when the run should stop picking up new files but let in-flight ones finish
+    // (unlike Ctrl-C, which also aborts files that are being checked)
+    let stop_requested = AtomicBool::new(false);
+
+    // Number of files that finished with Error, for --max-errors
+    let error_count = AtomicUsize::new(0);
+
+    // With --max-memory, files wait for memory held by others to be given back
+    let memory_budget = options.max_memory.map(MemoryBudget::new);
+    if let Some(limit) = options.max_memory {
+        println!(
+            "{} Memory budget: {} ({} per file being decoded{})",
+            "→".blue().bold(),
+            BinaryBytes(limit),
+            BinaryBytes(DECODE_MEMORY_ESTIMATE),
+            if pipelined { ", plus its size while read into memory" } else { "" }
+        );
+    }
+
+    // With --hash-file, the hashes recorded by earlier runs to compare against
+    let previous_sha256: HashMap<usize, String> = if options.hash_file {
+        files_to_check
+            .iter()
+            .filter_map(|(idx, _, _)| Some((*idx, job_file.jobs[*idx].file_sha256.clone()?)))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let max_errors_hit = AtomicBool::new(false);
+
+    // With --dump-bad, the bytes all dumps of this run may still write, and what they did
+    let dump_budget = AtomicU64::new(options.dump_bad_max_size);
+    let dumped = AtomicUsize::new(0);
+    let dumps_truncated = AtomicUsize::new(0);
+
+    // With --max-duration, running out of time stops the run like --fail-fast does
+    let deadline = options.max_duration.map(|budget| started + budget);
+    let deadline_hit = AtomicBool::new(false);
+
+    // True once no new files should be started
+    let should_stop = || {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            deadline_hit.store(true, Ordering::SeqCst);
+            stop_requested.store(true, Ordering::SeqCst);
+        }
+        INTERRUPTED.load(Ordering::SeqCst) || stop_requested.load(Ordering::SeqCst)
+    };
+
+    // Check a single file and send the result to the job file writer
+    // `data` holds the file contents when a reader thread already loaded them
+    let check_one = |(idx, size, file_path, data, memory): WorkItem, updates: &mpsc::Sender<Update>| {
+        // Don't start new files once the run has been interrupted or stopped
+        if should_stop() {
+            return;
+        }
+
+        // With --max-memory, wait for room to decode (the reader already reserved it)
+        let _memory = memory.or_else(|| {
+            memory_budget
+                .as_ref()
+                .map(|budget| budget.reserve(DECODE_MEMORY_ESTIMATE))
+        });
+        if should_stop() {
+            return;
+        }
+
+        // The writer only goes away if it panicked, in which case nothing can be recorded
+        let send = |update| {
+            let _ = updates.send(update);
+        };
+
+        // Mark file as CHECKING before we start, and list it under the progress bar
+        send(Update::Started(idx));
+        let _in_flight = in_flight.track(&pb, idx, &file_path);
+
+        // A file that no longer exists gets its own status instead of a generic error
+        let metadata = match fs::metadata(&file_path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                send(Update::Missing(idx));
+                advance_progress(size);
+                return;
+            }
+            metadata => metadata.ok().map(Box::new),
+        };
+
+        // Perform the actual FLAC verification
+        // With --retries, transient I/O errors get a few more attempts (re-reading the
+        // file from disk each time)
+        let check_started = Instant::now();
+        let mut data = data;
+        let mut attempts = 1;
+        let result = loop {
+            let result = match (data.take(), options.timeout) {
+                (Some(Err(e)), _) => Err(anyhow::Error::new(e)
+                    .context(format!("Failed to read file: {}", file_path.display()))),
+                (data, Some(timeout)) => verify_with_timeout(
+                    &file_path,
+                    data.and_then(Result::ok),
+                    &options.verify,
+                    timeout,
+                ),
+                (data, None) => verify_file(
+                    &file_path,
+                    data.and_then(Result::ok),
+                    &options.verify,
+                    &INTERRUPTED,
+                ),
+            };
+
+            match &result {
+                Err(e)
+                    if attempts <= options.retries
+                        && is_transient_io_error(e)
+                        && !INTERRUPTED.load(Ordering::SeqCst) =>
+                {
+                    thread::sleep(RETRY_BACKOFF * attempts);
+                    attempts += 1;
+                }
+                _ => break result,
+            }
+        };
+        let mut result = result.map_err(|e| {
+            if attempts > 1 {
+                let message = format!("{} (after {} attempts)", e, attempts);
+                e.context(message)
+            } else {
+                e
+            }
+        });
+
+        // With --verify-sidecars, a file that passed must also match the checksum an
+        // .ffp/.md5 file next to it recorded
+        if options.verify_sidecars
+            && let Ok(verification) = &mut result
+            && verification.verdict == Verdict::Ok
+            && let Some(entry) = sidecars.lookup(&file_path, |warning| {
+                log_line(&pb, format!("{} {}", "⚠".yellow().bold(), warning))
+            })
+            && let Some(reason) = sidecar_mismatch(
+                &entry,
+                &file_path,
+                verification.header_md5.as_deref(),
+                verification.audio_md5.as_deref(),
+            )
+        {
+            verification.verdict = Verdict::Bad(reason);
+        }
+
+        // With --require-md5, decoding without errors isn't enough: the header must carry
+        // an MD5 the audio was compared against (unknown when the flac binary decided)
+        if options.require_md5
+            && let Ok(verification) = &mut result
+            && verification.verdict == Verdict::Ok
+            && verification.verifier == "claxon"
+            && verification.header_md5.is_none()
+        {
+            verification.verdict = Verdict::Bad(NO_MD5_REASON.to_string());
+        }
+
+        // With --hash-file, hash the file's bytes too: the audio MD5 doesn't notice damage
+        // to tags or other metadata, but a changed SHA-256 does
+        let mut file_sha256 = None;
+        if options.hash_file
+            && let Ok(verification) = &mut result
+        {
+            match sha256_file(&file_path) {
+                Ok(hash) => {
+                    if let Some(previous) = previous_sha256.get(&idx)
+                        && *previous != hash
+                        && verification.verdict == Verdict::Ok
+                    {
+                        verification.warnings.push(format!(
+                            "file bytes changed since last run (metadata edit or silent \
+                             corruption): SHA-256 was {}, now {}",
+                            previous, hash
+                        ));
+                    }
+                    file_sha256 = Some(hash);
+                }
+                Err(e) => result = Err(e),
+            }
+        }
+
+        // With --strict, warnings (such as ID3 tags around the stream) fail the file too
+        if options.strict
+            && let Ok(verification) = &mut result
+            && verification.verdict == Verdict::Ok
+            && !verification.warnings.is_empty()
+        {
+            verification.verdict = Verdict::Bad(verification.warnings.join("; "));
+        }
+
+        if let Err(e) = &result
+            && e.is::<Cancelled>()
+        {
+            send(Update::Aborted(idx));
+            return;
+        }
+
+        // The worker decides on stopping itself, so no further file is started after a
+        // failure while the writer catches up
+        if result.is_err() {
+            let errors = error_count.fetch_add(1, Ordering::SeqCst) + 1;
+
+            // With --max-errors, too many errors (a dying disk, a lost mount) stop the run
+            if let Some(max_errors) = options.max_errors
+                && errors >= max_errors
+            {
+                max_errors_hit.store(true, Ordering::SeqCst);
+                stop_requested.store(true, Ordering::SeqCst);
+            }
+        }
+
+        // With --fail-fast, the first failure stops the run
+        if options.fail_fast && !matches!(&result, Ok(v) if v.verdict == Verdict::Ok) {
+            stop_requested.store(true, Ordering::SeqCst);
+        }
+
+        // With --dump-bad, decode damaged files once more with a WAV file teed off the
+        // samples, so what did decode can be listened to
+        let damaged = match &result {
+            Ok(v) => matches!(v.verdict, Verdict::Bad(_)) && v.verifier == "claxon",
+            Err(e) => e.is::<DecodeFailure>(),
+        };
+        if let Some(dir) = &options.dump_bad
+            && damaged
+        {
+            let dump = WavDump::new(dump_path(&file_path, &root_directories, dir), &dump_budget);
+            match dump_decoded_audio(&file_path, dump, &options.verify) {
+                Ok(Some(dump)) => {
+                    dumped.fetch_add(1, Ordering::Relaxed);
+                    if dump.truncated {
+                        dumps_truncated.fetch_add(1, Ordering::Relaxed);
+                    }
+                    log_line(&pb, format!(
+                        "{} Dumped {} of decoded audio to {}{}",
+                        "→".blue().bold(),
+                        format_audio_time(dump.samples, dump.sample_rate),
+                        dump.path.display(),
+                        if dump.truncated { " (cut short by --dump-bad-max-size)" } else { "" }
+                    ));
+                }
+                // Nothing decoded, or the budget was already used up
+                Ok(None) => {}
+                Err(e) => log_line(&pb, format!(
+                    "{} Failed to dump the audio of {}: {:#}",
+                    "⚠".yellow().bold(),
+                    file_path.display(),
+                    e
+                )),
+            }
+        }
+
+        // With --quarantine, move Bad files out of the library (Error files stay put,
+        // they may well be fine). Files already in quarantine are left where they are.
+        let quarantined = match (&options.quarantine, &result) {
+            (Some(dir), Ok(v))
+                if matches!(v.verdict, Verdict::Bad(_)) && !file_path.starts_with(dir) =>
+            {
+                match quarantine_file(&file_path, &root_directories, dir) {
+                    Ok(new_path) => {
+                        log_line(&pb, format!(
+                            "{} Quarantined {} -> {}",
+                            "→".blue().bold(),
+                            file_path.display(),
+                            new_path.display()
+                        ));
+                        Some(new_path)
+                    }
+                    Err(e) => {
+                        log_line(&pb, format!(
+                            "{} Failed to quarantine {}: {:#}",
+                            "⚠".yellow().bold(),
+                            file_path.display(),
+                            e
+                        ));
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        send(Update::Finished {
+            idx,
+            result: Box::new(result),
+            duration_ms: check_started.elapsed().as_millis() as u64,
+            metadata,
+            quarantined,
+            file_sha256,
+        });
+
+        // Update progress bar
+        bytes_done.fetch_add(size, Ordering::Relaxed);
+        advance_progress(size);
+    };
+
+    // Open the run log before any work starts, so a bad path fails early
+    let mut log = options.log_file.as_deref().map(RunLog::open).transpose()?;
+    if let Some(log) = &mut log {
+        log.start(&job_file_path, total_count);
+    }
+
+    // A single writer thread owns the job file for the rest of the run
+    let (updates, update_rx) = mpsc::channel::<Update>();
+    let writer = JobWriter::new(job_file, &output_path, &options, log);
 
-    let job_file: JobFile = serde_json::from_str(&job_file_content)
-        .context("Failed to parse job file JSON")?;
+    // Process files in parallel
+    let (mut job_file, tally, log) = thread::scope(|scope| {
+        let writer = scope.spawn(move || writer.run(update_rx));
+        let updates = updates;
 
-    // Configure thread pool size
-    let thread_count = threads.unwrap_or_else(num_cpus::get);
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(thread_count)
-        .build_global()
-        .context("Failed to initialize thread pool")?;
+        if pipelined {
+            // Reader stage: a few threads read whole files in queue order and hand them to
+            // the decoders through a bounded channel, so memory use stays limited
+            let (tx, rx) = mpsc::sync_channel::<WorkItem>(thread_count);
+            let next_file = AtomicUsize::new(0);
 
-    println!(
-        "{} Using {} threads for parallel checking",
-        "→".blue().bold(),
-        thread_count
-    );
+            thread::scope(|readers| {
+                for _ in 0..io_threads {
+                    let tx = tx.clone();
+                    readers.spawn(|| {
+                        let tx = tx;
+                        while !should_stop() {
+                            let i = next_file.fetch_add(1, Ordering::SeqCst);
+                            let Some((idx, size, path)) = files_to_check.get(i) else {
+                                break;
+                            };
+                            // The whole file is held in memory until it has been decoded
+                            let memory = memory_budget
+                                .as_ref()
+                                .map(|budget| budget.reserve(size + DECODE_MEMORY_ESTIMATE));
+                            let data = fs::read(path);
+                            if tx.send((*idx, *size, path.clone(), Some(data), memory)).is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+                drop(tx);
 
-    // Count how many files need to be checked
-    // Files with status CHECKING will be re-checked (in case of previous interruption)
-    let files_to_check: Vec<usize> = job_file
-        .jobs
-        .iter()
-        .enumerate()
-        .filter(|(_, job)| {
-            matches!(
-                job.status,
-                FlacStatus::ToBeChecked | FlacStatus::Checking | FlacStatus::Error
-            )
-        })
-        .map(|(idx, _)| idx)
-        .collect();
+                // Decode stage: the rayon workers consume files as they arrive
+                pool.install(|| {
+                    rx.into_iter()
+                        .par_bridge()
+                        .for_each(|item| check_one(item, &updates))
+                });
+            });
+        } else {
+            // Every worker reads its own file directly
+            pool.install(|| {
+                files_to_check.par_iter().for_each(|(idx, size, path)| {
+                    check_one((*idx, *size, path.clone(), None, None), &updates)
+                })
+            });
+        }
 
-    if files_to_check.is_empty() {
-        println!("{} No files to check!", "✓".green().bold());
-        return Ok(());
+        // Hanging up lets the writer drain the remaining updates and finish
+        drop(updates);
+        writer.join().expect("job file writer panicked")
+    });
+
+    in_flight.finish();
+
+    let interrupted = INTERRUPTED.load(Ordering::SeqCst);
+    let stopped = stop_requested.load(Ordering::SeqCst);
+    if interrupted {
+        // Leave the terminal in a clean state with the bar where it stopped
+        pb.abandon_with_message("Interrupted!");
+    } else if stopped {
+        pb.abandon_with_message("Stopped!");
+    } else {
+        pb.finish_with_message("Done!");
+    }
+
+    // Final save and statistics update
+    // Nothing should be left in CHECKING, but make sure an interrupted run
+    // never leaves stale entries behind
+    for job in job_file.jobs.iter_mut() {
+        if job.status == FlacStatus::Checking {
+            job.status = FlacStatus::ToBeChecked;
+        }
+    }
+
+    // Tracks whose format differs from the rest of their album get a warning
+    let album_outliers = check_album_consistency(&mut job_file.jobs);
+
+    job_file.statistics = Statistics::from_jobs(&job_file.jobs);
+    job_file.save(&output_path)?;
+
+    if let Some(mut log) = log {
+        log.end(&RunSummary {
+            outcome: if terminated() {
+                "terminated"
+            } else if interrupted {
+                "interrupted"
+            } else if stopped {
+                "stopped"
+            } else {
+                "completed"
+            },
+            ok: tally.ok,
+            warnings: tally.warnings,
+            bad: tally.bad,
+            errors: tally.errors,
+            missing: tally.missing,
+            skipped,
+            unchecked: total_count - files_done.load(Ordering::Relaxed),
+        });
     }
 
+    // Print summary
+    print_check_summary(&job_file);
+
+    // Report how much data was read and how fast
+    let elapsed = started.elapsed();
+    let bytes_done = bytes_done.load(Ordering::Relaxed);
+    let speed = bytes_done as f64 / elapsed.as_secs_f64().max(0.001);
     println!(
-        "{} Found {} files to check",
-        "→".blue().bold(),
-        files_to_check.len()
+        "\n  Verified {} in {} ({}/s)",
+        DecimalBytes(bytes_done),
+        HumanDuration(elapsed),
+        DecimalBytes(speed as u64)
     );
 
-    // Create progress bar
-    let pb = ProgressBar::new(files_to_check.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) {msg}")
-            .unwrap()
-            .progress_chars("#>-")
-    );
+    // Files that used to verify and no longer do are the real bit-rot signal
+    let regressed = tally.regressed;
+    if regressed > 0 {
+        println!(
+            "\n{} {} files that were previously OK are now BAD",
+            "✗".red().bold(),
+            regressed.to_string().red().bold()
+        );
+    }
 
-    // Wrap the job file in Arc<Mutex<>> for thread-safe access
-    let job_file = Arc::new(Mutex::new(job_file));
+    if album_outliers > 0 {
+        println!(
+            "\n{} {} files don't match the format of the rest of their album (see stats)",
+            "⚠".yellow().bold(),
+            album_outliers
+        );
+    }
 
-    // Process files in parallel
-    let results: Vec<_> = files_to_check
-        .into_par_iter()
-        .map(|idx| {
-            // Mark file as CHECKING before we start
-            {
-                let mut jf = job_file.lock().unwrap();
-                jf.jobs[idx].status = FlacStatus::Checking;
-                jf.jobs[idx].error_message = None;
+    // --require-md5: tell policy failures apart from files that are actually damaged
+    if tally.no_md5 > 0 {
+        println!(
+            "\n{} {} bad files have no MD5 signature (--require-md5), {} failed verification",
+            "✗".red().bold(),
+            tally.no_md5,
+            tally.bad - tally.no_md5
+        );
+    }
 
-                // Save the job file immediately to persist the CHECKING status
-                if let Err(e) = save_job_file(&jf, &job_file_path) {
-                    eprintln!("Warning: Failed to save job file: {}", e);
-                }
-            }
+    if interrupted {
+        let unchecked = total_count - files_done.load(Ordering::Relaxed);
+        println!(
+            "\n{} Check {}, {} files left to check. Run the same command again to resume.",
+            "⚠".yellow().bold(),
+            if terminated() { "terminated" } else { "interrupted" },
+            unchecked
+        );
+        return Ok(if terminated() {
+            EXIT_TERMINATED
+        } else {
+            EXIT_INTERRUPTED
+        });
+    }
+
+    // --max-errors: explain why the run was cut short
+    if max_errors_hit.load(Ordering::SeqCst) {
+        let unchecked = total_count - files_done.load(Ordering::Relaxed);
+        println!(
+            "\n{} Aborted: reached the error threshold of {} files (--max-errors)",
+            "⚠".yellow().bold(),
+            options.max_errors.unwrap_or_default()
+        );
+        println!(
+            "  {} files were left unchecked because of the abort.",
+            unchecked
+        );
+        return Ok(EXIT_MAX_ERRORS);
+    }
+
+    if let Some(dir) = &options.dump_bad {
+        let dumped = dumped.load(Ordering::Relaxed);
+        let truncated = dumps_truncated.load(Ordering::Relaxed);
+        if dumped > 0 {
+            println!(
+                "\n{} Dumped the decoded audio of {} damaged files to {}",
+                "→".blue().bold(),
+                dumped,
+                dir.display()
+            );
+        }
+        if truncated > 0 || dump_budget.load(Ordering::Relaxed) == 0 {
+            println!(
+                "{} The --dump-bad-max-size budget of {} ran out, some dumps are incomplete or missing",
+                "⚠".yellow().bold(),
+                BinaryBytes(options.dump_bad_max_size)
+            );
+        }
+    }
+
+    if let Some(dir) = &options.quarantine
+        && tally.quarantined > 0
+    {
+        println!(
+            "\n{} Moved {} bad files to {}",
+            "→".blue().bold(),
+            tally.quarantined,
+            dir.display()
+        );
+    }
 
-            // Get the file path to check
-            let file_path = {
-                let jf = job_file.lock().unwrap();
-                jf.jobs[idx].path.clone()
+    // --fail-fast: point at the file that stopped the run
+    if let Some((path, reason)) = tally.first_failure {
+        let unchecked = total_count - files_done.load(Ordering::Relaxed);
+        println!(
+            "\n{} Stopped at first failure (--fail-fast): {}",
+            "✗".red().bold(),
+            path.display().to_string().red().bold()
+        );
+        if !reason.is_empty() {
+            println!("    {}: {}", "Reason".dimmed(), reason);
+        }
+        println!(
+            "  {} files left unchecked. Run the same command again to resume.",
+            unchecked
+        );
+        return Ok(result_exit_code(tally.bad, tally.errors));
+    }
+
+    // --max-duration: say how far this run got before the time ran out
+    if deadline_hit.load(Ordering::SeqCst) {
+        let checked = files_done.load(Ordering::Relaxed);
+        println!(
+            "\n{} Time budget of {} used up (--max-duration): checked {} of {} files, {} left. Run the same command again to continue.",
+            "→".blue().bold(),
+            HumanDuration(options.max_duration.unwrap_or_default()),
+            checked,
+            total_count,
+            total_count - checked
+        );
+    }
+
+    // --limit: say whether the job is finished or another run is needed
+    if let Some(limit) = options.limit {
+        if deferred > 0 {
+            println!(
+                "\n{} Stopped at the limit of {} files (--limit), {} files left to check. Run the same command again to continue.",
+                "→".blue().bold(),
+                limit,
+                deferred
+            );
+        } else if !deadline_hit.load(Ordering::SeqCst) {
+            println!("\n{} Nothing left to check after this run", "✓".green().bold());
+        }
+    }
+
+    // Bad or errored files fail the run (exit code 2 or 3) unless --continue-on-error
+    if !options.continue_on_error && (tally.errors > 0 || tally.bad > 0) {
+        eprintln!(
+            "Error: Check completed with {} errors and {} bad files",
+            tally.errors, tally.bad
+        );
+        return Ok(result_exit_code(tally.bad, tally.errors));
+    }
+
+    Ok(0)
+}
+
+/// Time the rest of a run should take at its average speed so far, if there is one yet
+fn time_left(elapsed: Duration, done: u64, total: Option<u64>) -> Option<Duration> {
+    if done == 0 {
+        return None;
+    }
+    let remaining = total.unwrap_or(done).saturating_sub(done);
+    Some(elapsed.mul_f64(remaining as f64 / done as f64))
+}
+
+/// Put the indices of the files to check in the order they should be handed out
+/// Ties (and the size of files that can't be stat'ed) fall back to path order
+fn sort_queue(queue: &mut [usize], jobs: &[FlacJob], order: CheckOrder, seed: u64) {
+    let path = |idx: usize| &jobs[idx].path;
+    match order {
+        CheckOrder::Path => queue.sort_by(|&a, &b| path(a).cmp(path(b))),
+        CheckOrder::Size => {
+            let sizes: HashMap<usize, u64> = queue
+                .iter()
+                .map(|&idx| {
+                    let job = &jobs[idx];
+                    let size = job.size.or_else(|| fs::metadata(&job.path).map(|m| m.len()).ok());
+                    (idx, size.unwrap_or(0))
+                })
+                .collect();
+            queue.sort_by(|&a, &b| sizes[&b].cmp(&sizes[&a]).then_with(|| path(a).cmp(path(b))));
+        }
+        CheckOrder::Random => shuffle(queue, seed),
+        CheckOrder::StatusPriority => {
+            let rank = |idx: usize| match jobs[idx].status {
+                FlacStatus::Checking => 0,
+                FlacStatus::ToBeChecked => 1,
+                FlacStatus::Error => 2,
+                FlacStatus::Missing => 3,
+                FlacStatus::Bad => 4,
+                FlacStatus::Skipped => 5,
+                FlacStatus::Warning => 6,
+                FlacStatus::Ok => 7,
+                FlacStatus::Duplicate => 8,
             };
+            queue.sort_by(|&a, &b| rank(a).cmp(&rank(b)).then_with(|| path(a).cmp(path(b))));
+        }
+    }
+}
 
-            // Perform the actual FLAC verification
-            let check_result = verify_flac_file(&file_path);
+/// The first --skip pattern matching a file, if any
+/// Relative patterns are matched against the path below the file's root directory (or,
+/// without a `/`, against the file name), absolute ones against the full path
+fn skip_pattern<'a>(
+    patterns: &'a [String],
+    path: &Path,
+    roots: &[PathBuf],
+) -> Option<&'a String> {
+    let full = path.to_string_lossy().replace('\\', "/");
+    let relative = find_root(roots, path)
+        .and_then(|root| path.strip_prefix(root).ok())
+        .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_else(|| full.clone());
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
 
-            // Update the job status based on the result
-            {
-                let mut jf = job_file.lock().unwrap();
-                match &check_result {
-                    Ok(true) => {
-                        jf.jobs[idx].status = FlacStatus::Ok;
-                        jf.jobs[idx].error_message = None;
-                    }
-                    Ok(false) => {
-                        jf.jobs[idx].status = FlacStatus::Bad;
-                        jf.jobs[idx].error_message = Some("FLAC verification failed".to_string());
-                    }
-                    Err(e) => {
-                        jf.jobs[idx].status = FlacStatus::Error;
-                        jf.jobs[idx].error_message = Some(e.to_string());
-                    }
-                }
+    patterns.iter().find(|pattern| {
+        if Path::new(pattern.as_str()).is_absolute() {
+            glob_match(pattern, &full)
+        } else if pattern.contains('/') {
+            glob_match(pattern, &relative)
+        } else {
+            glob_match(pattern, &name)
+        }
+    })
+}
 
-                // Save job file after each update (slower but safer in case of interruption)
-                if let Err(e) = save_job_file(&jf, &job_file_path) {
-                    eprintln!("Warning: Failed to save job file: {}", e);
-                }
+/// Print a line above the progress bar, or plainly when the bar is hidden (not a
+/// terminal) and would swallow it
+fn log_line(pb: &ProgressBar, line: String) {
+    if pb.is_hidden() {
+        println!("{}", line);
+    } else {
+        pb.println(line);
+    }
+}
+
+/// Run verify_flac_file on a watchdog-supervised thread so a file whose reads hang
+/// can be given up on. On timeout the decode thread is abandoned (it is told to stop
+/// and exits as soon as its blocked read returns) and an error is returned.
+fn verify_with_timeout(
+    path: &Path,
+    data: Option<Vec<u8>>,
+    settings: &VerifySettings,
+    timeout: Duration,
+) -> Result<Verification> {
+    let (tx, rx) = mpsc::channel();
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    {
+        let path = path.to_path_buf();
+        let settings = settings.clone();
+        let cancel = Arc::clone(&cancel);
+        thread::Builder::new()
+            .name("checkflac-verify".to_string())
+            .spawn(move || {
+                // The receiver may be gone if we already timed out, which is fine
+                let _ = tx.send(verify_file(&path, data, &settings, &cancel));
+            })
+            .context("Failed to spawn verification thread")?;
+    }
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        // Wake up regularly so Ctrl-C is noticed even while the file is stuck
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(remaining.min(Duration::from_millis(100))) {
+            Ok(result) => return result,
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                anyhow::bail!("Verification thread exited unexpectedly");
             }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
 
-            // Update progress bar
-            pb.inc(1);
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            cancel.store(true, Ordering::SeqCst);
+            return Err(Cancelled.into());
+        }
 
-            check_result
-        })
-        .collect();
+        if Instant::now() >= deadline {
+            cancel.store(true, Ordering::SeqCst);
+            anyhow::bail!("timed out after {}s", timeout.as_secs());
+        }
+    }
+}
 
-    pb.finish_with_message("Done!");
+/// Verify a single file with claxon, falling back to the external `flac` binary
+/// (when configured) if claxon itself cannot decode the stream
+/// This is the whole per-file check shared by the check and verify commands; the
+/// job-level policies (--strict, --require-md5, sidecars) are applied by check on top
+pub fn verify_file(
+    path: &Path,
+    data: Option<Vec<u8>>,
+    settings: &VerifySettings,
+    cancel: &AtomicBool,
+) -> Result<Verification> {
+    let claxon_result = verify_flac_file(path, data, settings, cancel).map(|check| Verification {
+        verdict: check.verdict,
+        verifier: "claxon",
+        audio_md5: check.audio_md5.map(|md5| to_hex(&md5)),
+        header_md5: check.header_md5.map(|md5| to_hex(&md5)),
+        warnings: check.warnings,
+        format: Some(AudioFormat {
+            sample_rate: check.streaminfo.sample_rate,
+            bits_per_sample: check.streaminfo.bits_per_sample,
+            channels: check.streaminfo.channels,
+            total_samples: check.streaminfo.samples,
+        }),
+        failed_at: check.failed_at,
+        level: if settings.quick {
+            VerifyLevel::Quick
+        } else {
+            VerifyLevel::Full
+        },
+    });
 
-    // Final save and statistics update
-    {
-        let mut jf = job_file.lock().unwrap();
-        jf.statistics = Statistics::from_jobs(&jf.jobs);
-        save_job_file(&jf, &job_file_path)?;
+    let Some(flac_binary) = &settings.fallback_flac else {
+        return claxon_result;
+    };
+
+    match claxon_result {
+        Err(e) if is_decoder_error(&e) => match verify_with_flac_binary(path, flac_binary) {
+            Some(verification) => Ok(verification),
+            // The binary couldn't be run, so report what claxon said
+            None => Err(e),
+        },
+        result => result,
     }
+}
 
-    // Print summary
-    let jf = job_file.lock().unwrap();
-    print_check_summary(&jf);
+/// Whether an error came from claxon failing to decode the stream
+/// (as opposed to I/O problems, which the flac binary would hit just the same)
+fn is_decoder_error(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<claxon::Error>(),
+        Some(claxon::Error::FormatError(_)) | Some(claxon::Error::Unsupported(_))
+    )
+}
 
-    // Check if we should fail on errors
-    if !continue_on_error {
-        let error_count = results.iter().filter(|r| r.is_err()).count();
-        let bad_count = results
-            .iter()
-            .filter(|r| matches!(r, Ok(false)))
-            .count();
-
-        if error_count > 0 || bad_count > 0 {
-            anyhow::bail!(
-                "Check completed with {} errors and {} bad files",
-                error_count,
-                bad_count
-            );
+/// Whether an error was caused by an I/O failure that may go away on its own (e.g. a
+/// hiccup on a network share) rather than by the contents of the file
+fn is_transient_io_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let io_error = match cause.downcast_ref::<claxon::Error>() {
+            Some(claxon::Error::IoError(e)) => Some(e),
+            _ => cause.downcast_ref::<std::io::Error>(),
+        };
+
+        io_error.is_some_and(|e| {
+            matches!(
+                e.kind(),
+                ErrorKind::Interrupted
+                    | ErrorKind::TimedOut
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::NotConnected
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::NetworkDown
+                    | ErrorKind::NetworkUnreachable
+                    | ErrorKind::HostUnreachable
+                    | ErrorKind::StaleNetworkFileHandle
+                    | ErrorKind::ResourceBusy
+            )
+        })
+    })
+}
+
+/// SHA-256 of a file's entire contents as lowercase hex, for --hash-file
+fn sha256_file(path: &Path) -> Result<String> {
+    let context = || format!("Failed to hash file: {}", path.display());
+    let mut file = File::open(path).with_context(context)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).with_context(context)?;
+        if read == 0 {
+            break;
         }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Test a file with `flac -t`, using its exit status as the verdict
+/// Returns None if the binary could not be run at all
+fn verify_with_flac_binary(path: &Path, flac_binary: &Path) -> Option<Verification> {
+    let mut command = Command::new(flac_binary);
+    command.arg("-t").arg("--silent");
+    // flac only guesses Ogg from a .oga/.ogg name
+    let ogg = File::open(path).is_ok_and(|mut file| is_ogg_stream(&mut file, 0).unwrap_or(false));
+    if ogg {
+        command.arg("--ogg");
     }
+    let output = command.arg(path).output().ok()?;
 
-    Ok(())
+    let verdict = if output.status.success() {
+        Verdict::Ok
+    } else {
+        // flac prints the reason as the last line on stderr
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or("no error output");
+        Verdict::Bad(format!("flac -t failed: {}", reason))
+    };
+
+    Some(Verification {
+        verdict,
+        verifier: "flac",
+        audio_md5: None,
+        header_md5: None,
+        warnings: Vec::new(),
+        format: None,
+        failed_at: None,
+        level: VerifyLevel::Full,
+    })
 }
 
 /// Verify a FLAC file by:
 /// 1. Decoding all frames
 /// 2. Computing MD5 hash of decoded audio
-/// 3. Comparing with MD5 stored in FLAC header
-/// Returns Ok(true) if file is valid, Ok(false) if corrupted, Err on other errors
-fn verify_flac_file(path: &PathBuf) -> Result<bool> {
-    // Open the FLAC file using claxon
-    let mut reader = FlacReader::open(path)
-        .with_context(|| format!("Failed to open FLAC file: {}", path.display()))?;
+/// 3. Comparing the decoded sample count with the total stored in the header
+/// 4. Comparing with MD5 stored in FLAC header
+///
+/// Returns Ok with Verdict::Ok if file is valid or Verdict::Bad if corrupted (together with
+/// the computed and stored MD5s), Err on other errors
+/// Decoding stops early with a `Cancelled` error as soon as `cancel` is set
+/// If `data` holds the file contents (already read by a reader thread) it is decoded
+/// from memory instead of opening the file again
+/// ID3/APE tags wrapped around the stream are skipped and reported as warnings, as are
+/// metadata problems with --check-metadata and --check-seektable
+fn verify_flac_file(
+    path: &Path,
+    data: Option<Vec<u8>>,
+    settings: &VerifySettings,
+    cancel: &AtomicBool,
+) -> Result<StreamCheck> {
+    let open_context = || format!("Failed to open FLAC file: {}", path.display());
+    match data {
+        Some(data) => verify_flac_input(Cursor::new(data), settings, cancel, open_context, None),
+        None => {
+            let file = File::open(path).with_context(open_context)?;
+            verify_flac_input(file, settings, cancel, open_context, None)
+        }
+    }
+}
+
+/// Decode a file again for --dump-bad, writing the samples to `dump` as far as decoding
+/// gets (the verdict is already known, so decoding errors only end the dump)
+/// Returns None if no audio decoded at all
+fn dump_decoded_audio(
+    path: &Path,
+    mut dump: WavDump,
+    settings: &VerifySettings,
+) -> Result<Option<crate::dump::DumpSummary>> {
+    let open_context = || format!("Failed to open FLAC file: {}", path.display());
+    let file = File::open(path).with_context(open_context)?;
+    let result = verify_flac_input(file, settings, &INTERRUPTED, open_context, Some(&mut dump));
+
+    if matches!(&result, Err(e) if e.is::<Cancelled>()) {
+        dump.abandon();
+        return Ok(None);
+    }
+    dump.finish()
+}
+
+/// Find the FLAC stream within a file or buffer, then open it with claxon and verify it
+fn verify_flac_input<R: Read + Seek>(
+    mut input: R,
+    settings: &VerifySettings,
+    cancel: &AtomicBool,
+    open_context: impl Fn() -> String,
+    dump: Option<&mut WavDump>,
+) -> Result<StreamCheck> {
+    let bounds = find_stream_bounds(&mut input).with_context(&open_context)?;
+    let mut warnings = bounds.warnings;
+
+    // .oga files (and the odd misnamed .flac) can hold FLAC in an Ogg container, which
+    // claxon doesn't read; as an unsupported stream it still goes to the flac binary
+    if is_ogg_stream(&mut input, bounds.start).with_context(&open_context)? {
+        return Err(claxon::Error::Unsupported("Ogg-encapsulated FLAC")).with_context(|| {
+            format!(
+                "{}: the stream is in an Ogg container (Ogg FLAC), which the built-in decoder can't read (--fallback-flac-binary can verify it)",
+                open_context()
+            )
+        });
+    }
+
+    // The Vorbis comments are validated here, so claxon doesn't need to see them (and
+    // can't reject a file with broken tags before its audio is verified)
+    let mut hidden_blocks = Vec::new();
+    if settings.check_metadata || settings.check_seektable {
+        let checks = MetadataChecks {
+            tags: settings.check_metadata,
+            seektable: settings.check_seektable,
+        };
+        let report = check_metadata(&mut input, bounds.start, bounds.end, checks)
+            .with_context(&open_context)?;
+        warnings.extend(report.warnings);
+        hidden_blocks = report.vorbis_comment_headers;
+    }
+
+    input
+        .seek(SeekFrom::Start(bounds.start))
+        .with_context(&open_context)?;
+
+    // Open the FLAC stream using claxon, hiding any trailing tags from it
+    let bytes_read = Rc::new(Cell::new(bounds.start));
+    let stream = CountingReader {
+        inner: HideBlocks::new(input.take(bounds.end - bounds.start), hidden_blocks),
+        count: Rc::clone(&bytes_read),
+    };
+    let reader = FlacReader::new(stream).with_context(&open_context)?;
+    if settings.quick {
+        let mut check = quick_check_stream(reader)?;
+        check.warnings = warnings;
+        return Ok(check);
+    }
+    // On a single core the hashing thread would only compete with the decoder
+    let pipelined = settings.pipeline
+        || (bounds.end - bounds.start >= PIPELINE_MIN_SIZE && *MULTI_CORE);
+    let mut check = verify_flac_stream(reader, pipelined, cancel, &bytes_read, dump)?;
+    check.warnings = warnings;
+    Ok(check)
+}
+
+/// Whether the stream starts with an Ogg page rather than the "fLaC" marker
+fn is_ogg_stream<R: Read + Seek>(input: &mut R, start: u64) -> std::io::Result<bool> {
+    input.seek(SeekFrom::Start(start))?;
+    let mut magic = [0; 4];
+    match input.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == b"OggS"),
+        // Too short to be anything; claxon reports that
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Check an opened FLAC stream for --quick: opening it already parsed the fLaC marker,
+/// STREAMINFO and every metadata block header, so all that is left is to see that a
+/// valid audio frame follows the metadata. Only the first frame is decoded.
+fn quick_check_stream<R: Read>(mut reader: FlacReader<R>) -> Result<StreamCheck> {
+    let streaminfo = reader.streaminfo();
+    let has_md5 = streaminfo.md5sum.iter().any(|&b| b != 0);
+    let check = |verdict| StreamCheck {
+        verdict,
+        audio_md5: None,
+        header_md5: has_md5.then_some(streaminfo.md5sum),
+        warnings: Vec::new(),
+        streaminfo,
+        failed_at: None,
+    };
+
+    match reader.blocks().read_next_or_eof(Vec::new()) {
+        Ok(Some(_)) => Ok(check(Verdict::Ok)),
+        // A stream that is announced as empty may well have no frames
+        Ok(None) if streaminfo.samples.unwrap_or(0) == 0 => Ok(check(Verdict::Ok)),
+        Ok(None) => Ok(check(Verdict::Bad(
+            "file is truncated: no audio frames after the metadata".to_string(),
+        ))),
+        Err(claxon::Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => Ok(check(
+            Verdict::Bad("file is truncated: ends in the middle of the first frame".to_string()),
+        )),
+        Err(e) => {
+            let message = format!("FLAC decoding error in the first frame: {}", e);
+            Err(anyhow::Error::new(e).context(message))
+        }
+    }
+}
+
+/// MD5 of the packed audio, computed either on the decoding thread or, for large files,
+/// on a helper thread so decoding the next frames and hashing the previous ones overlap
+enum AudioHasher {
+    Inline(Md5),
+    Pipelined {
+        /// Full buffers on their way to the hashing thread
+        chunks: mpsc::SyncSender<Vec<u8>>,
+        /// Hashed buffers coming back to be refilled
+        recycled: mpsc::Receiver<Vec<u8>>,
+        digest: thread::JoinHandle<[u8; 16]>,
+    },
+}
+
+impl AudioHasher {
+    fn pipelined() -> Self {
+        // Bounded, so a decoder that outruns the hasher blocks instead of buffering the file
+        let (chunks, incoming) = mpsc::sync_channel::<Vec<u8>>(PIPELINE_DEPTH);
+        let (give_back, recycled) = mpsc::channel();
+        let digest = thread::spawn(move || {
+            let mut md5 = Md5::new();
+            for mut chunk in incoming {
+                md5.update(&chunk);
+                chunk.clear();
+                // The decoder may already be done and gone
+                let _ = give_back.send(chunk);
+            }
+            md5.finalize().into()
+        });
+        AudioHasher::Pipelined {
+            chunks,
+            recycled,
+            digest,
+        }
+    }
+
+    /// Hash the contents of `buffer`, leaving it empty and ready to be refilled
+    fn update(&mut self, buffer: &mut Vec<u8>) {
+        match self {
+            AudioHasher::Inline(md5) => {
+                md5.update(&buffer);
+                buffer.clear();
+            }
+            AudioHasher::Pipelined {
+                chunks, recycled, ..
+            } => {
+                let empty = recycled
+                    .try_recv()
+                    .unwrap_or_else(|_| Vec::with_capacity(MD5_BUFFER_SIZE));
+                let full = std::mem::replace(buffer, empty);
+                // The hashing thread only goes away if it panicked, which finalize reports
+                let _ = chunks.send(full);
+            }
+        }
+    }
+
+    fn finalize(self) -> [u8; 16] {
+        match self {
+            AudioHasher::Inline(md5) => md5.finalize().into(),
+            AudioHasher::Pipelined { chunks, digest, .. } => {
+                // Hanging up lets the hashing thread finish the queue and return the digest
+                drop(chunks);
+                digest.join().expect("MD5 hashing thread panicked")
+            }
+        }
+    }
+}
+
+/// Append the samples of a frame to the MD5 buffer, interleaved and packed into the
+/// low WIDTH bytes of their little-endian two's complement value, which is the same as
+/// sign-extending them to ceil(bits / 8) bytes (12-bit uses 2 bytes, 20-bit uses 3)
+fn push_interleaved<const WIDTH: usize>(block: &Block, buffer: &mut Vec<u8>) {
+    let channels: Vec<&[i32]> = (0..block.channels()).map(|ch| block.channel(ch)).collect();
+    buffer.reserve(block.len() as usize * WIDTH);
+
+    // Stereo is by far the most common layout, so it gets a loop of its own
+    if let [left, right] = channels[..] {
+        for (l, r) in left.iter().zip(right) {
+            buffer.extend_from_slice(&l.to_le_bytes()[..WIDTH]);
+            buffer.extend_from_slice(&r.to_le_bytes()[..WIDTH]);
+        }
+        return;
+    }
 
+    for i in 0..block.duration() as usize {
+        for channel in &channels {
+            buffer.extend_from_slice(&channel[i].to_le_bytes()[..WIDTH]);
+        }
+    }
+}
+
+/// Decode and verify an opened FLAC stream (the body of verify_flac_file)
+/// `bytes_read` is the file offset claxon has read up to, used to locate damaged frames
+/// With a `dump`, every decoded frame is also written to it (--dump-bad)
+fn verify_flac_stream<R: Read>(
+    mut reader: FlacReader<R>,
+    pipelined: bool,
+    cancel: &AtomicBool,
+    bytes_read: &Cell<u64>,
+    mut dump: Option<&mut WavDump>,
+) -> Result<StreamCheck> {
     // Get stream info which contains the expected MD5
     let streaminfo = reader.streaminfo();
     let expected_md5 = streaminfo.md5sum;
@@ -182,98 +2246,269 @@ fn verify_flac_file(path: &PathBuf) -> Result<bool> {
     let has_md5 = expected_md5.iter().any(|&b| b != 0);
 
     // Prepare MD5 hasher for computed checksum
-    let mut hasher = Md5::new();
+    let mut hasher = if pipelined {
+        AudioHasher::pipelined()
+    } else {
+        AudioHasher::Inline(Md5::new())
+    };
 
     // Get sample information
     let bits_per_sample = streaminfo.bits_per_sample;
 
-    // Create a buffer to hold samples
-    let mut samples = Vec::new();
+    // Number of samples per channel the encoder said it wrote (None if unknown)
+    let expected_samples = streaminfo.samples;
 
-    // Decode all samples using the samples() iterator
-    for sample_result in reader.samples() {
-        match sample_result {
-            Ok(sample) => {
-                samples.push(sample);
-            }
-            Err(e) => {
-                // Any error means the file is corrupted or invalid
-                return Err(anyhow::anyhow!(
-                    "FLAC decoding error: {}",
-                    e
-                ));
-            }
-        }
+    // FLAC allows 4 to 32 bits per sample; anything else can't be hashed meaningfully
+    if !(4..=32).contains(&bits_per_sample) {
+        return Err(
+            anyhow::Error::new(claxon::Error::Unsupported("bits per sample"))
+                .context(format!("Unsupported bits per sample: {}", bits_per_sample)),
+        );
+    }
+
+    if let Some(dump) = dump.as_deref_mut() {
+        dump.begin(streaminfo.sample_rate, bits_per_sample, streaminfo.channels);
     }
 
-    // Now compute MD5 from the samples
+    // 32-bit streams (FLAC 1.4+) are not reliably handled by claxon, so failures on them
+    // are reported as a decoder limitation rather than as corruption
+    let is_32_bit = bits_per_sample == 32;
+
+    // The FLAC MD5 covers each sample packed into ceil(bits / 8) bytes,
+    // little-endian and sign-extended (so 12-bit uses 2 bytes, 20-bit uses 3)
+    let bytes_per_sample = bits_per_sample.div_ceil(8) as usize;
+
+    // Small reusable byte buffer so the hasher is fed in chunks rather than
+    // one sample at a time. Memory use stays constant regardless of track length.
+    let mut buffer: Vec<u8> = Vec::with_capacity(MD5_BUFFER_SIZE);
+
+    // Samples per channel decoded so far, to detect truncated streams and missing frames
+    let mut decoded_per_channel: u64 = 0;
+    let mut frames_decoded: u64 = 0;
+    // Read position after the last good frame, which is about where the next one starts
+    let mut frame_offset = bytes_read.get();
+
+    // Decode frame by frame (rather than sample by sample) so the position of each frame
+    // can be compared with what was decoded before it
     // MD5 is computed on the raw audio data in the file's native format
-    // We need to convert samples to bytes in the proper format
-    for &sample in &samples {
-        // Convert sample to bytes based on bits_per_sample
-        match bits_per_sample {
-            8 => {
-                // 8-bit samples are unsigned
-                let byte = (sample + 128) as u8;
-                hasher.update(&[byte]);
+    let mut frames = reader.blocks();
+    // One buffer of the largest frame size is reused for every frame
+    let mut block_buffer =
+        Vec::with_capacity(streaminfo.max_block_size as usize * streaminfo.channels as usize);
+    loop {
+        let position = FailurePosition {
+            sample: decoded_per_channel,
+            byte: Some(frame_offset),
+        };
+        let block = match frames.read_next_or_eof(block_buffer) {
+            Ok(Some(block)) => block,
+            Ok(None) => break,
+            // A stream that stops inside a frame has been cut off
+            Err(claxon::Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                let reason = truncation_reason(
+                    decoded_per_channel,
+                    expected_samples,
+                    streaminfo.sample_rate,
+                );
+                return Ok(StreamCheck {
+                    verdict: Verdict::Bad(reason),
+                    audio_md5: Some(hasher.finalize()),
+                    header_md5: has_md5.then_some(expected_md5),
+                    warnings: Vec::new(),
+                    streaminfo,
+                    failed_at: Some(position),
+                });
             }
-            16 => {
-                // 16-bit samples, little-endian
-                let bytes = (sample as i16).to_le_bytes();
-                hasher.update(&bytes);
-            }
-            24 => {
-                // 24-bit samples, stored in 3 bytes little-endian
-                let bytes = sample.to_le_bytes();
-                hasher.update(&bytes[0..3]);
-            }
-            32 => {
-                // 32-bit samples
-                let bytes = sample.to_le_bytes();
-                hasher.update(&bytes);
+            Err(e) => {
+                // Any other error means the file is corrupted or invalid
+                // (the claxon error is kept as the source so the fallback can inspect it)
+                let message = if is_32_bit {
+                    format!(
+                        "32-bit FLAC stream could not be decoded (unsupported by decoder): {}",
+                        e
+                    )
+                } else {
+                    format!(
+                        "FLAC decoding error: {}, {}",
+                        e,
+                        failure_position(&position, expected_samples, streaminfo.sample_rate)
+                    )
+                };
+                return Err(anyhow::Error::new(e).context(DecodeFailure { message, position }));
             }
-            _ => {
-                return Err(anyhow::anyhow!(
-                    "Unsupported bits per sample: {}",
-                    bits_per_sample
-                ));
+        };
+
+        // Each frame must start where the previous one ended. claxon derives the position
+        // of fixed-blocksize frames from the frame number times the frame's own size, so
+        // a short final frame is accepted when its frame number is the expected one
+        let duration = block.duration() as u64;
+        let starts_in_place = block.time() == decoded_per_channel
+            || (duration > 0 && block.time() == frames_decoded * duration);
+        if !starts_in_place {
+            let reason = format!(
+                "frames missing: audio jumps from {} to {} ({} missing)",
+                format_audio_time(decoded_per_channel, streaminfo.sample_rate),
+                format_audio_time(block.time(), streaminfo.sample_rate),
+                format_audio_time(
+                    block.time().saturating_sub(decoded_per_channel),
+                    streaminfo.sample_rate
+                )
+            );
+            return Ok(StreamCheck {
+                verdict: Verdict::Bad(reason),
+                audio_md5: Some(hasher.finalize()),
+                header_md5: has_md5.then_some(expected_md5),
+                warnings: Vec::new(),
+                streaminfo,
+                failed_at: Some(position),
+            });
+        }
+
+        if let Some(dump) = dump.as_deref_mut() {
+            dump.write_block(&block);
+        }
+
+        // Interleave the channels again, as the MD5 is computed over interleaved samples
+        // The sample width is dispatched once per frame rather than once per sample
+        match bytes_per_sample {
+            1 => push_interleaved::<1>(&block, &mut buffer),
+            2 => push_interleaved::<2>(&block, &mut buffer),
+            3 => push_interleaved::<3>(&block, &mut buffer),
+            _ => push_interleaved::<4>(&block, &mut buffer),
+        }
+
+        // Flush the buffer into the hasher once it is full
+        if buffer.len() >= MD5_BUFFER_SIZE {
+            hasher.update(&mut buffer);
+
+            // Give up on this file if the run is being interrupted
+            if cancel.load(Ordering::Relaxed) {
+                return Err(Cancelled.into());
             }
         }
+
+        decoded_per_channel += duration;
+        frames_decoded += 1;
+        frame_offset = bytes_read.get();
+        block_buffer = block.into_buffer();
     }
 
+    // Hash whatever is left in the buffer
+    hasher.update(&mut buffer);
+
     // Finalize MD5 hash
-    let computed_md5: [u8; 16] = hasher.finalize().into();
+    let computed_md5 = hasher.finalize();
+
+    let check = |verdict| StreamCheck {
+        verdict,
+        audio_md5: Some(computed_md5),
+        header_md5: has_md5.then_some(expected_md5),
+        warnings: Vec::new(),
+        streaminfo,
+        failed_at: None,
+    };
+
+    // A stream that decodes cleanly but is shorter (or longer) than announced has
+    // lost frames, whether or not the header carries an MD5
+    if let Some(expected) = expected_samples
+        && decoded_per_channel != expected
+    {
+        let mut reason = format!(
+            "expected {} samples, decoded {}",
+            format_count(expected),
+            format_count(decoded_per_channel)
+        );
+        if decoded_per_channel < expected {
+            reason.push_str(&format!(
+                " ({} of audio missing)",
+                format_audio_time(expected - decoded_per_channel, streaminfo.sample_rate)
+            ));
+        }
+        return Ok(check(Verdict::Bad(reason)));
+    }
 
     // Compare MD5 if available
     if has_md5 {
         if computed_md5 == expected_md5 {
-            Ok(true) // File is valid
+            Ok(check(Verdict::Ok)) // File is valid
+        } else if is_32_bit {
+            // Don't call a possibly healthy 32-bit file corrupted because of the decoder
+            Err(
+                anyhow::Error::new(claxon::Error::Unsupported("32-bit audio")).context(
+                    "32-bit FLAC stream: MD5 mismatch (unsupported by decoder, \
+                     claxon does not reliably decode 32-bit audio; use --fallback-flac-binary)",
+                ),
+            )
         } else {
-            // MD5 mismatch - file is corrupted
-            Ok(false)
+            // MD5 mismatch - file is corrupted, somewhere no frame check caught
+            Ok(check(Verdict::Bad(MD5_MISMATCH_REASON.to_string())))
         }
     } else {
-        // No MD5 in header, but file decoded successfully
-        // Consider it OK since we at least verified it decodes
-        Ok(true)
+        // No MD5 in header, but file decoded successfully (and the sample count matched
+        // if the header had one). Consider it OK since we at least verified it decodes
+        // A stream cut off exactly at a frame boundary can't be told apart from a
+        // complete one without either of them
+        Ok(check(Verdict::Ok))
+    }
+}
+
+/// Describe where decoding failed, e.g. "failed at ~3:41.20 (sample 9,782,400 of
+/// 15,876,000, near byte 31,234,567)"
+fn failure_position(position: &FailurePosition, expected: Option<u64>, sample_rate: u32) -> String {
+    let mut details = format!("sample {}", format_count(position.sample));
+    if let Some(expected) = expected {
+        details.push_str(&format!(" of {}", format_count(expected)));
+    }
+    if let Some(byte) = position.byte {
+        details.push_str(&format!(", near byte {}", format_count(byte)));
     }
+    format!(
+        "failed at ~{} ({})",
+        format_audio_time(position.sample, sample_rate),
+        details
+    )
 }
 
-/// Save the job file to disk
-fn save_job_file(job_file: &JobFile, path: &PathBuf) -> Result<()> {
-    let json = serde_json::to_string_pretty(job_file)
-        .context("Failed to serialize job file")?;
-    
-    fs::write(path, json)
-        .with_context(|| format!("Failed to write job file to {}", path.display()))?;
-    
-    Ok(())
+/// Describe a stream that ends in the middle of a frame, with how much audio is missing
+/// when the header says how long the stream should be
+fn truncation_reason(decoded: u64, expected: Option<u64>, sample_rate: u32) -> String {
+    let decoded_time = format_audio_time(decoded, sample_rate);
+    match expected {
+        Some(expected) if expected > decoded => format!(
+            "file is truncated: ends in the middle of a frame after {} of audio ({} of {} missing)",
+            decoded_time,
+            format_audio_time(expected - decoded, sample_rate),
+            format_audio_time(expected, sample_rate)
+        ),
+        _ => format!(
+            "file is truncated: ends in the middle of a frame after {} of audio \
+             (header has no length, so the amount missing is unknown)",
+            decoded_time
+        ),
+    }
+}
+
+/// Format a list of statuses for display (e.g. "bad, error")
+fn status_list(statuses: &[FlacStatus]) -> String {
+    statuses
+        .iter()
+        .map(|s| s.name())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 /// Print a summary of the check results
 fn print_check_summary(job_file: &JobFile) {
     println!("\n{}", "Check Summary:".bold().underline());
     println!("  Total files:    {}", job_file.total_files);
+    if let Some(note) = job_file.sample_note() {
+        println!(
+            "  {} {} {}",
+            "Sample:        ".yellow().bold(),
+            note.yellow().bold(),
+            "(a spot check, not the whole library)".yellow()
+        );
+    }
     println!("\n{}", "Status breakdown:".bold());
     println!(
         "  {} To be checked: {}",
@@ -290,6 +2525,11 @@ fn print_check_summary(job_file: &JobFile) {
         "✓".green().bold(),
         job_file.statistics.ok
     );
+    println!(
+        "  {} Warning:       {}",
+        "!".bright_yellow().bold(),
+        job_file.statistics.warning
+    );
     println!(
         "  {} Bad:           {}",
         "✗".red().bold(),
@@ -300,10 +2540,195 @@ fn print_check_summary(job_file: &JobFile) {
         "⚠".yellow().bold(),
         job_file.statistics.error
     );
+    println!(
+        "  {} Missing:       {}",
+        "?".blue(),
+        job_file.statistics.missing
+    );
+    println!(
+        "  {} Skipped:       {}",
+        "-".dimmed(),
+        job_file.statistics.skipped
+    );
+    if job_file.statistics.duplicate > 0 {
+        println!(
+            "  {} Duplicate:     {}",
+            "=".dimmed(),
+            job_file.statistics.duplicate
+        );
+    }
 
-    // Show percentage
-    if job_file.total_files > 0 {
-        let ok_percent = (job_file.statistics.ok as f64 / job_file.total_files as f64) * 100.0;
+    // Show percentage (files with warnings verified fine); duplicates are verified
+    // through the file they link to
+    let total = job_file.total_files - job_file.statistics.duplicate;
+    if total > 0 {
+        let passed = job_file.statistics.ok + job_file.statistics.warning;
+        let ok_percent = (passed as f64 / total as f64) * 100.0;
         println!("\n  Success rate: {:.1}%", ok_percent);
     }
-}
\ No newline at end of file
+
+    print_decode_only_count(job_file);
+}
+
+/// Print how many passed files had no MD5 in their header, so their audio could only be
+/// checked for decoding errors (shared with the stats summary)
+pub fn print_decode_only_count(job_file: &JobFile) {
+    let decode_only = job_file
+        .jobs
+        .iter()
+        .filter(|job| job.verified_without_md5())
+        .count();
+    if decode_only > 0 {
+        println!(
+            "  Verified by decode only (no MD5 in header): {}",
+            decode_only
+        );
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference files in tests/fixtures: short sine tones with verbatim subframes and
+    /// the MD5 of their audio in STREAMINFO
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    fn verify(path: &Path, data: Option<Vec<u8>>) -> Result<Verification> {
+        verify_file(
+            path,
+            data,
+            &VerifySettings::default(),
+            &AtomicBool::new(false),
+        )
+    }
+
+    /// Samples are hashed sign-extended to whole bytes, channels interleaved, the same for
+    /// every width and layout (stereo has a loop of its own)
+    #[test]
+    fn odd_bit_depths_match_the_header_md5() {
+        for name in [
+            "8bit-mono.flac",
+            "12bit-mono.flac",
+            "16bit-6ch.flac",
+            "20bit-stereo.flac",
+            "24bit-stereo.flac",
+        ] {
+            let verification = verify(&fixture(name), None).unwrap();
+            assert_eq!(verification.verdict, Verdict::Ok, "{}", name);
+            assert!(verification.header_md5.is_some(), "{}", name);
+            assert_eq!(verification.audio_md5, verification.header_md5, "{}", name);
+        }
+    }
+
+    /// claxon can't decode 32-bit audio, which must not be taken for corruption
+    #[test]
+    fn undecodable_32_bit_file_is_an_error_not_bad() {
+        let error = verify(&fixture("32bit-stereo.flac"), None).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("unsupported by decoder"),
+            "{:#}",
+            error
+        );
+        // So --fallback-flac-binary gets to test it
+        assert!(is_decoder_error(&error));
+    }
+
+    /// A file cut off inside its last frame is Bad, with how much of the audio is gone
+    #[test]
+    fn truncated_file_is_bad() {
+        let path = fixture("12bit-mono.flac");
+        let mut data = fs::read(&path).unwrap();
+        // The headers and the first frame take about 820 bytes, the second frame as many
+        data.truncate(1200);
+
+        let verification = verify(&path, Some(data)).unwrap();
+        let Verdict::Bad(reason) = &verification.verdict else {
+            panic!("a truncated file passed: {:?}", verification.verdict);
+        };
+        assert_eq!(
+            reason,
+            "file is truncated: ends in the middle of a frame after 0:00.01 of audio \
+             (0:00.04 of 0:00.06 missing)"
+        );
+        assert_eq!(verification.failed_at.map(|at| at.sample), Some(512));
+    }
+
+    fn check_options(threads: usize) -> CheckOptions {
+        CheckOptions {
+            threads: Some(threads),
+            io_threads: None,
+            continue_on_error: true,
+            save_interval: None,
+            timeout: None,
+            statuses: Vec::new(),
+            recheck_ok: false,
+            retry_missing: false,
+            fail_fast: false,
+            max_errors: None,
+            limit: None,
+            max_duration: None,
+            order: CheckOrder::Path,
+            seed: None,
+            skip: Vec::new(),
+            show_interrupted: false,
+            results: None,
+            force: false,
+            per_thread_progress: false,
+            low_priority: false,
+            quarantine: None,
+            dump_bad: None,
+            dump_bad_max_size: 0,
+            retries: 0,
+            log_file: None,
+            verify_sidecars: false,
+            strict: false,
+            hash_file: false,
+            max_memory: None,
+            require_md5: false,
+            root: None,
+            verify: VerifySettings::default(),
+        }
+    }
+
+    /// Each run builds its own thread pool, so a second run with another thread count
+    /// doesn't fail on the global pool the first one set up
+    #[test]
+    fn check_runs_twice_in_one_process() {
+        let dir = crate::util::test_dir("check-twice");
+        let names = ["12bit-mono.flac", "20bit-stereo.flac", "32bit-stereo.flac"];
+        let mut jobs = Vec::new();
+        for name in names {
+            let path = dir.join(name);
+            fs::copy(fixture(name), &path).unwrap();
+            jobs.push(FlacJob::new(path));
+        }
+        let job_file: JobFile = serde_json::from_value(serde_json::json!({
+            "root_directories": [&dir],
+            "total_files": jobs.len(),
+            "statistics": Statistics::from_jobs(&jobs),
+            "jobs": jobs,
+        }))
+        .unwrap();
+        let job_file_path = dir.join("job.json");
+        job_file.save(&job_file_path).unwrap();
+
+        check_flac_files(job_file_path.clone(), check_options(1)).unwrap();
+        let options = CheckOptions {
+            recheck_ok: true,
+            ..check_options(3)
+        };
+        check_flac_files(job_file_path.clone(), options).unwrap();
+
+        let job_file = JobFile::load(&job_file_path).unwrap();
+        let statuses: Vec<&FlacStatus> = job_file.jobs.iter().map(|job| &job.status).collect();
+        assert_eq!(
+            statuses,
+            [&FlacStatus::Ok, &FlacStatus::Ok, &FlacStatus::Error]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+use crate::types::{EXIT_BAD, FlacJob, FlacStatus, JobFile};
+use anyhow::{Context, Result};
+use colored::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Options controlling the diff command
+pub struct DiffOptions {
+    /// Colored text for people, or a JSON document for scripts
+    pub format: DiffFormat,
+    /// Show full paths instead of paths relative to the root directories
+    pub full_paths: bool,
+    /// Where the library is mounted, for job files with relative paths
+    pub root: Option<PathBuf>,
+}
+
+/// Output of the diff command
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DiffFormat {
+    #[default]
+    Text,
+    /// One JSON document on stdout (see `DiffReport`)
+    Json,
+}
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            _ => Err(format!("unknown format '{}' (expected text or json)", s)),
+        }
+    }
+}
+
+/// `checkflac diff --format json`
+#[derive(Serialize)]
+struct DiffReport<'a> {
+    /// 1 for this layout
+    schema_version: u32,
+    old_job_file: &'a Path,
+    new_job_file: &'a Path,
+    summary: DiffSummary,
+    /// Files only in the new job
+    new_files: Vec<DiffEntry>,
+    /// Files only in the old job
+    removed_files: Vec<DiffEntry>,
+    /// Files in both jobs whose status differs
+    changed_files: Vec<StatusChange>,
+}
+
+#[derive(Serialize)]
+struct DiffSummary {
+    old_files: usize,
+    new_files: usize,
+    added: usize,
+    removed: usize,
+    changed: usize,
+    unchanged: usize,
+    /// Files that verified in the old job and are Bad in the new one
+    ok_to_bad: usize,
+}
+
+#[derive(Serialize)]
+struct DiffEntry {
+    /// Relative to its root directory unless --full-paths is given
+    path: String,
+    /// The status names of check --status, e.g. "to_be_checked"
+    status: &'static str,
+}
+
+#[derive(Serialize)]
+struct StatusChange {
+    path: String,
+    old_status: &'static str,
+    new_status: &'static str,
+    /// The reason of the new status, for Bad and Error files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<String>,
+}
+
+/// Whether a file verified (OK, or OK with warnings)
+fn passed(status: &FlacStatus) -> bool {
+    matches!(status, FlacStatus::Ok | FlacStatus::Warning)
+}
+
+/// The entries of a job by the absolute path of the file (its original place, if it was
+/// quarantined), so jobs made from different working directories still line up
+fn entries_by_path(job_file: &JobFile) -> BTreeMap<PathBuf, &FlacJob> {
+    job_file
+        .jobs
+        .iter()
+        .map(|job| {
+            let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+            let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+            (path, job)
+        })
+        .collect()
+}
+
+/// Compare two job files of the same library (e.g. checks made months apart): files that
+/// are new, removed or whose status changed
+/// Returns EXIT_BAD when a file that verified in the old job is Bad in the new one
+pub fn diff_job_files(old_path: PathBuf, new_path: PathBuf, options: DiffOptions) -> Result<u8> {
+    let text = options.format == DiffFormat::Text;
+    if text {
+        println!(
+            "{} Comparing {} with {}...",
+            "→".blue().bold(),
+            old_path.display(),
+            new_path.display()
+        );
+    }
+    let old = JobFile::load_with_root(&old_path, options.root.as_deref())?;
+    let new = JobFile::load_with_root(&new_path, options.root.as_deref())?;
+    let old_entries = entries_by_path(&old);
+    let new_entries = entries_by_path(&new);
+
+    let display = |job_file: &JobFile, job: &FlacJob| {
+        let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+        if options.full_paths {
+            path.display().to_string()
+        } else {
+            job_file.relative_path(path).display().to_string()
+        }
+    };
+
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    let mut unchanged = 0;
+    for (path, new_job) in &new_entries {
+        match old_entries.get(path) {
+            None => added.push(DiffEntry {
+                path: display(&new, new_job),
+                status: new_job.status.name(),
+            }),
+            Some(old_job) if old_job.status == new_job.status => unchanged += 1,
+            Some(old_job) => changed.push((
+                passed(&old_job.status) && new_job.status == FlacStatus::Bad,
+                StatusChange {
+                    path: display(&new, new_job),
+                    old_status: old_job.status.name(),
+                    new_status: new_job.status.name(),
+                    error_message: new_job
+                        .error_message
+                        .clone()
+                        .filter(|_| matches!(new_job.status, FlacStatus::Bad | FlacStatus::Error)),
+                },
+            )),
+        }
+    }
+    let removed: Vec<DiffEntry> = old_entries
+        .iter()
+        .filter(|(path, _)| !new_entries.contains_key(*path))
+        .map(|(_, old_job)| DiffEntry {
+            path: display(&old, old_job),
+            status: old_job.status.name(),
+        })
+        .collect();
+
+    let ok_to_bad = changed.iter().filter(|(scary, _)| *scary).count();
+    let summary = DiffSummary {
+        old_files: old_entries.len(),
+        new_files: new_entries.len(),
+        added: added.len(),
+        removed: removed.len(),
+        changed: changed.len(),
+        unchanged,
+        ok_to_bad,
+    };
+
+    if text {
+        print_diff(&summary, &added, &removed, &changed);
+    } else {
+        let report = DiffReport {
+            schema_version: 1,
+            old_job_file: &old_path,
+            new_job_file: &new_path,
+            summary,
+            new_files: added,
+            removed_files: removed,
+            changed_files: changed.into_iter().map(|(_, change)| change).collect(),
+        };
+        let mut out = io::stdout().lock();
+        serde_json::to_writer_pretty(&mut out, &report)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(out))
+            .and_then(|()| out.flush())
+            .context("Failed to write the report to stdout")?;
+    }
+
+    // Lets a cron job alert on files that went bad since the last check
+    Ok(if ok_to_bad > 0 { EXIT_BAD } else { 0 })
+}
+
+/// Print the text output of the diff command
+fn print_diff(
+    summary: &DiffSummary,
+    added: &[DiffEntry],
+    removed: &[DiffEntry],
+    changed: &[(bool, StatusChange)],
+) {
+    println!("\n{}", "Summary:".bold().underline());
+    println!("  Files in old:   {}", summary.old_files);
+    println!("  Files in new:   {}", summary.new_files);
+    println!("  New files:      {}", summary.added);
+    println!("  Removed files:  {}", summary.removed);
+    println!("  Status changed: {}", summary.changed);
+    println!("  Unchanged:      {}", summary.unchanged);
+
+    if !changed.is_empty() {
+        println!("\n{}", "Status Changes (old → new):".bold());
+        for (scary, change) in changed {
+            let transition = format!("{} → {}", change.old_status, change.new_status);
+            let line = format!("{} [{}]", change.path, transition);
+            if *scary {
+                println!("  {} {}", "✗".red().bold(), line.red().bold());
+            } else if matches!(change.new_status, "bad" | "error" | "missing") {
+                println!("  {} {}", "⚠".yellow(), line.yellow());
+            } else if matches!(change.new_status, "ok" | "warning") {
+                println!("  {} {}", "✓".green(), line);
+            } else {
+                println!("  {} {}", "≠".magenta(), line);
+            }
+            if let Some(message) = &change.error_message {
+                println!("    {}: {}", "Reason".dimmed(), message.dimmed());
+            }
+        }
+    }
+
+    if !added.is_empty() {
+        println!("\n{}", "New Files (only in the new job):".cyan().bold());
+        for entry in added {
+            println!("  {} {} [{}]", "+".cyan(), entry.path, entry.status);
+        }
+    }
+
+    if !removed.is_empty() {
+        println!(
+            "\n{}",
+            "Removed Files (only in the old job):".dimmed().bold()
+        );
+        for entry in removed {
+            println!("  {} {} [{}]", "-".dimmed(), entry.path, entry.status);
+        }
+    }
+
+    println!();
+    if summary.ok_to_bad > 0 {
+        println!(
+            "{} {} files that were OK are now Bad.",
+            "✗".red().bold(),
+            summary.ok_to_bad
+        );
+    } else {
+        println!("{} No file went from OK to Bad.", "✓".green().bold());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::types::find_root;
+use anyhow::{Context, Result};
+use claxon::Block;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// WAV sizes are 32-bit, so a dump stops short of 4 GiB whatever the budget says
+const MAX_WAV_DATA: u64 = u32::MAX as u64 - 1024;
+
+/// What a finished dump contains
+pub struct DumpSummary {
+    pub path: PathBuf,
+    /// Samples per channel written
+    pub samples: u64,
+    pub sample_rate: u32,
+    /// Stopped early because the --dump-bad-max-size budget ran out
+    pub truncated: bool,
+}
+
+/// Format of the audio being dumped, known once STREAMINFO has been read
+struct WavFormat {
+    sample_rate: u32,
+    bits_per_sample: u32,
+    channels: u32,
+    /// Bytes per sample in the file (12-bit audio is stored in 2 bytes)
+    container_bytes: usize,
+}
+
+impl WavFormat {
+    /// Plain PCM only describes 8 and 16-bit mono or stereo unambiguously; anything else
+    /// gets WAVE_FORMAT_EXTENSIBLE with the valid bits and the speaker layout
+    fn extensible(&self) -> bool {
+        self.channels > 2 || !matches!(self.bits_per_sample, 8 | 16)
+    }
+
+    fn header(&self, data_bytes: u64) -> Vec<u8> {
+        let block_align = self.container_bytes as u32 * self.channels;
+        let fmt_size: u32 = if self.extensible() { 40 } else { 16 };
+        let data_bytes = data_bytes as u32;
+
+        let mut header = Vec::with_capacity(68);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(4 + 8 + fmt_size + 8 + data_bytes).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&fmt_size.to_le_bytes());
+        let format_tag: u16 = if self.extensible() { 0xFFFE } else { 1 };
+        header.extend_from_slice(&format_tag.to_le_bytes());
+        header.extend_from_slice(&(self.channels as u16).to_le_bytes());
+        header.extend_from_slice(&self.sample_rate.to_le_bytes());
+        header.extend_from_slice(&(self.sample_rate * block_align).to_le_bytes());
+        header.extend_from_slice(&(block_align as u16).to_le_bytes());
+        header.extend_from_slice(&(self.container_bytes as u16 * 8).to_le_bytes());
+        if self.extensible() {
+            header.extend_from_slice(&22u16.to_le_bytes());
+            header.extend_from_slice(&(self.bits_per_sample as u16).to_le_bytes());
+            header.extend_from_slice(&channel_mask(self.channels).to_le_bytes());
+            // KSDATAFORMAT_SUBTYPE_PCM
+            header.extend_from_slice(&[
+                0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38,
+                0x9B, 0x71,
+            ]);
+        }
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&data_bytes.to_le_bytes());
+        header
+    }
+}
+
+/// Speaker positions of FLAC's default channel assignments (0 = unspecified)
+fn channel_mask(channels: u32) -> u32 {
+    match channels {
+        1 => 0x4,
+        2 => 0x3,
+        3 => 0x7,
+        4 => 0x33,
+        5 => 0x37,
+        6 => 0x3F,
+        7 => 0x70F,
+        8 => 0x63F,
+        _ => 0,
+    }
+}
+
+/// WAV file receiving a copy of the decoded samples, for --dump-bad
+/// The file is only created once there are samples to write, and writing stops
+/// (leaving a valid, shorter WAV) when the shared byte budget runs out. Write errors
+/// are kept for `finish` so they never affect the verdict of the file being decoded.
+pub struct WavDump<'a> {
+    path: PathBuf,
+    budget: &'a AtomicU64,
+    format: Option<WavFormat>,
+    writer: Option<BufWriter<File>>,
+    buffer: Vec<u8>,
+    data_bytes: u64,
+    truncated: bool,
+    error: Option<anyhow::Error>,
+}
+
+impl<'a> WavDump<'a> {
+    pub fn new(path: PathBuf, budget: &'a AtomicU64) -> Self {
+        WavDump {
+            path,
+            budget,
+            format: None,
+            writer: None,
+            buffer: Vec::new(),
+            data_bytes: 0,
+            truncated: false,
+            error: None,
+        }
+    }
+
+    /// Set the format from STREAMINFO, before the first frame
+    pub fn begin(&mut self, sample_rate: u32, bits_per_sample: u32, channels: u32) {
+        self.format = Some(WavFormat {
+            sample_rate,
+            bits_per_sample,
+            channels,
+            container_bytes: bits_per_sample.div_ceil(8) as usize,
+        });
+    }
+
+    /// Append the samples of a decoded frame, interleaved and left-justified in their
+    /// container as WAV expects (8-bit WAV is unsigned)
+    pub fn write_block(&mut self, block: &Block) {
+        let Some(format) = &self.format else {
+            return;
+        };
+        if self.truncated || self.error.is_some() {
+            return;
+        }
+
+        let width = format.container_bytes;
+        let shift = width as u32 * 8 - format.bits_per_sample;
+        self.buffer.clear();
+        for i in 0..block.duration() {
+            for channel in 0..block.channels() {
+                let sample = block.sample(channel, i) << shift;
+                if width == 1 {
+                    self.buffer.push((sample + 128) as u8);
+                } else {
+                    self.buffer
+                        .extend_from_slice(&sample.to_le_bytes()[..width]);
+                }
+            }
+        }
+
+        let len = self.buffer.len() as u64;
+        let reserved = self.data_bytes + len <= MAX_WAV_DATA
+            && self
+                .budget
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                    left.checked_sub(len)
+                })
+                .is_ok();
+        if !reserved {
+            self.truncated = true;
+            return;
+        }
+
+        if let Err(e) = self.write_buffer() {
+            self.error = Some(e);
+        }
+    }
+
+    fn write_buffer(&mut self) -> Result<()> {
+        if self.writer.is_none() {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            let file = File::create(&self.path)
+                .with_context(|| format!("Failed to create {}", self.path.display()))?;
+            let mut writer = BufWriter::new(file);
+            // Sizes are filled in by finish
+            if let Some(format) = &self.format {
+                writer.write_all(&format.header(0))?;
+            }
+            self.writer = Some(writer);
+        }
+
+        if let Some(writer) = &mut self.writer {
+            writer
+                .write_all(&self.buffer)
+                .with_context(|| format!("Failed to write {}", self.path.display()))?;
+            self.data_bytes += self.buffer.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Write the final sizes into the header
+    /// Returns None if nothing was decoded, so there was nothing to dump
+    pub fn finish(mut self) -> Result<Option<DumpSummary>> {
+        if let Some(e) = self.error.take() {
+            self.abandon();
+            return Err(e);
+        }
+        let (Some(mut writer), Some(format)) = (self.writer.take(), self.format.take()) else {
+            return Ok(None);
+        };
+
+        let result = (|| -> Result<()> {
+            writer.seek(SeekFrom::Start(0))?;
+            writer.write_all(&format.header(self.data_bytes))?;
+            writer.flush()?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            let _ = fs::remove_file(&self.path);
+            return Err(e.context(format!("Failed to write {}", self.path.display())));
+        }
+
+        Ok(Some(DumpSummary {
+            samples: self.data_bytes / (format.container_bytes as u64 * u64::from(format.channels)),
+            sample_rate: format.sample_rate,
+            path: self.path,
+            truncated: self.truncated,
+        }))
+    }
+
+    /// Remove whatever was written, e.g. when the run is interrupted mid-file
+    pub fn abandon(mut self) {
+        if self.writer.take().is_some() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Where the dump of a file goes: its path below its library root (or just its name for
+/// files outside every root) inside the dump directory, with a .wav extension
+pub fn dump_path(path: &Path, roots: &[PathBuf], dump_dir: &Path) -> PathBuf {
+    let relative = match find_root(roots, path) {
+        Some(root) => path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+        None => PathBuf::from(path.file_name().unwrap_or_default()),
+    };
+    dump_dir.join(relative).with_extension("wav")
+}
@@ -1,37 +1,342 @@
-use crate::types::{FlacJob, FlacStatus, JobFile, Statistics};
+use crate::ignore::{DirectoryRules, IGNORE_FILE_NAME, IgnoreRules};
+use crate::lock::JobLock;
+use crate::tags::{find_stream_bounds, probe_stream_start};
+use crate::types::{
+    EXIT_NOTHING_FOUND, FlacJob, FlacStatus, IgnoreFile, JobFile, Sample, ScanError, Statistics,
+    find_root,
+};
+use crate::util::{
+    clock_seed, format_count, format_play_time, glob_match, outln, send_status_to_stderr, shuffle,
+};
 use anyhow::{Context, Result};
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
+use claxon::metadata::StreamInfo;
+use claxon::{FlacReader, FlacReaderOptions};
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{BinaryBytes, DecimalBytes, ProgressBar, ProgressStyle};
 use rayon::prelude::*;
-use std::fs;
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use walkdir::WalkDir;
 
-/// Explore a directory and create a job file with all FLAC files found
-pub fn explore_directory(directory: PathBuf, output: Option<PathBuf>) -> Result<()> {
-    println!(
-        "{} Exploring directory: {}",
-        "→".blue().bold(),
-        directory.display()
-    );
+/// Options controlling the explore command
+pub struct ExploreOptions {
+    /// Output job file path (defaults to auto-generated based on the directory names)
+    pub output: Option<PathBuf>,
+    /// Glob patterns of files and directories to leave out of the job
+    pub exclude: Vec<String>,
+    /// Job files whose files are left out of the job
+    pub exclude_jobs: Vec<PathBuf>,
+    /// Follow symbolic links to files and directories
+    pub follow_symlinks: bool,
+    /// How many levels to descend below each directory (1 = only the files directly in it)
+    pub max_depth: Option<usize>,
+    /// Leave out files smaller than this many bytes
+    pub min_size: Option<u64>,
+    /// Leave out files larger than this many bytes
+    pub max_size: Option<u64>,
+    /// Leave out files last modified at or before this time
+    pub modified_since: Option<DateTime<Utc>>,
+    /// Leave out files not modified since this file was
+    pub modified_since_file: Option<PathBuf>,
+    /// List the files left out by --min-size and --max-size
+    pub show_skipped: bool,
+    /// File extensions to pick up, without the dot (matched case-insensitively)
+    pub extensions: Vec<String>,
+    /// File listing the FLAC files to take instead of scanning directories
+    pub from_file: Option<PathBuf>,
+    /// Root directories of a file list (defaults to the directory the files share)
+    pub roots: Vec<PathBuf>,
+    /// Store job paths relative to the root directory
+    pub relative: bool,
+    /// Fail instead of writing a job file when part of a directory can't be read
+    pub strict_scan: bool,
+    /// Keep the jobs in the order they were found instead of sorting them by path
+    pub no_sort: bool,
+    /// Also scan hidden files and directories
+    pub include_hidden: bool,
+    /// Don't read the start of each file to mark non-FLAC files Bad right away
+    pub no_probe: bool,
+    /// Give every hardlink of a file its own job instead of marking them Duplicate
+    pub no_dedupe_hardlinks: bool,
+    /// Walk each directory tree with this many threads (None or 1: a single walker)
+    pub scan_threads: Option<usize>,
+    /// Show what the job would contain without writing it
+    pub dry_run: bool,
+    /// With dry_run, list every file that would be included
+    pub list: bool,
+    /// Put this many randomly picked files in the job instead of all of them
+    pub sample: Option<usize>,
+    /// Pick this percentage of the files at random instead
+    pub sample_percent: Option<f64>,
+    /// Put the jobs in random order instead of sorting them by path
+    pub shuffle: bool,
+    /// Seed for the random pick and order, to make them again (defaults to one from the
+    /// clock)
+    pub seed: Option<u64>,
+    /// Read the STREAMINFO header of every file into its job
+    pub read_metadata: bool,
+    /// Fail instead of writing a job file when paths differ only in letter case
+    pub fail_on_case_collision: bool,
+    /// Replace an existing job file
+    pub force: bool,
+    /// Add the newly found files to an existing job file, keeping its entries
+    pub append: bool,
+}
 
-    // Check if the directory exists
-    if !directory.exists() {
-        anyhow::bail!("Directory does not exist: {}", directory.display());
+impl Default for ExploreOptions {
+    fn default() -> Self {
+        ExploreOptions {
+            output: None,
+            exclude: Vec::new(),
+            exclude_jobs: Vec::new(),
+            follow_symlinks: false,
+            max_depth: None,
+            min_size: None,
+            max_size: None,
+            modified_since: None,
+            modified_since_file: None,
+            show_skipped: false,
+            extensions: vec!["flac".to_string()],
+            from_file: None,
+            roots: Vec::new(),
+            relative: false,
+            strict_scan: false,
+            no_sort: false,
+            include_hidden: false,
+            no_probe: false,
+            no_dedupe_hardlinks: false,
+            scan_threads: None,
+            dry_run: false,
+            list: false,
+            sample: None,
+            sample_percent: None,
+            shuffle: false,
+            seed: None,
+            read_metadata: false,
+            fail_on_case_collision: false,
+            force: false,
+            append: false,
+        }
     }
+}
 
-    if !directory.is_dir() {
-        anyhow::bail!("Path is not a directory: {}", directory.display());
+impl ExploreOptions {
+    /// The scan settings a job file was created with, to walk its roots again
+    pub fn from_job_file(job_file: &JobFile) -> Self {
+        let defaults = ExploreOptions::default();
+        ExploreOptions {
+            exclude: job_file.exclude.clone(),
+            exclude_jobs: job_file.exclude_jobs.clone(),
+            follow_symlinks: job_file.follow_symlinks,
+            max_depth: job_file.max_depth,
+            min_size: job_file.min_size,
+            max_size: job_file.max_size,
+            modified_since: job_file.modified_since,
+            extensions: if job_file.extensions.is_empty() {
+                defaults.extensions.clone()
+            } else {
+                job_file.extensions.clone()
+            },
+            include_hidden: job_file.include_hidden,
+            read_metadata: job_file.read_metadata,
+            ..defaults
+        }
     }
 
-    // Generate output filename if not provided
-    let output = match output {
-        Some(path) => path,
-        None => generate_job_filename(&directory),
+    /// Whether --modified-since leaves a file out (files without a readable time stay)
+    fn not_modified_since(&self, metadata: &fs::Metadata) -> bool {
+        self.modified_since.is_some_and(|since| {
+            metadata
+                .modified()
+                .is_ok_and(|modified| DateTime::<Utc>::from(modified) <= since)
+        })
+    }
+}
+
+/// FLAC files found by a directory scan, and what --exclude left out
+#[derive(Default)]
+pub struct Scan {
+    pub files: Vec<PathBuf>,
+    /// FLAC files matching an --exclude pattern
+    pub excluded_files: usize,
+    /// Directories matching an --exclude pattern, which were not walked
+    pub excluded_dirs: usize,
+    /// Symbolic links whose target doesn't exist, with that target
+    pub broken_symlinks: Vec<(PathBuf, PathBuf)>,
+    /// Links to a directory above them, which were not walked again
+    pub symlink_loops: usize,
+    /// Working symbolic links left alone because links aren't followed
+    pub skipped_symlinks: usize,
+    /// Directories at the --max-depth limit, which were not walked
+    pub too_deep_dirs: usize,
+    /// FLAC files outside the --min-size/--max-size range, with their size
+    pub outside_size: Vec<(PathBuf, u64)>,
+    /// FLAC files not modified since --modified-since
+    pub not_modified: usize,
+    /// Paths that could not be read, so nothing below them was found
+    pub errors: Vec<ScanError>,
+    /// Hidden FLAC files left out
+    pub hidden_files: usize,
+    /// Hidden directories left out, which were not walked
+    pub hidden_dirs: usize,
+    /// FLAC files excluded by .checkflacignore rules
+    pub ignored_files: usize,
+    /// Directories excluded by .checkflacignore rules, which were not walked
+    pub ignored_dirs: usize,
+    /// The .checkflacignore files that were read
+    pub ignore_files: Vec<IgnoreFile>,
+    /// Malformed patterns and unreadable ignore files
+    pub ignore_warnings: Vec<String>,
+}
+
+impl Scan {
+    /// Add what another scan found after what this one found
+    fn append(&mut self, other: Scan) {
+        self.files.extend(other.files);
+        self.excluded_files += other.excluded_files;
+        self.excluded_dirs += other.excluded_dirs;
+        self.broken_symlinks.extend(other.broken_symlinks);
+        self.symlink_loops += other.symlink_loops;
+        self.skipped_symlinks += other.skipped_symlinks;
+        self.too_deep_dirs += other.too_deep_dirs;
+        self.outside_size.extend(other.outside_size);
+        self.not_modified += other.not_modified;
+        self.errors.extend(other.errors);
+        self.hidden_files += other.hidden_files;
+        self.hidden_dirs += other.hidden_dirs;
+        self.ignored_files += other.ignored_files;
+        self.ignored_dirs += other.ignored_dirs;
+        self.ignore_files.extend(other.ignore_files);
+        self.ignore_warnings.extend(other.ignore_warnings);
+    }
+}
+
+/// Explore one or more directories and create a single job file with all FLAC files found
+/// With a file list (`-` for stdin, or --from-file), the listed files are taken instead
+/// and no directory is walked
+/// Returns the process exit code: 0, or with --dry-run EXIT_NOTHING_FOUND when no files
+/// would be included
+pub fn explore_directories(mut directories: Vec<PathBuf>, options: ExploreOptions) -> Result<u8> {
+    // With `-o -` stdout carries the job file, so everything else goes to stderr
+    if options.output.as_deref() == Some(Path::new("-")) {
+        send_status_to_stderr();
+    }
+
+    if options.max_depth == Some(0) {
+        anyhow::bail!("--max-depth must be at least 1 (1 scans only the files directly in DIR)");
+    }
+    if options.sample == Some(0) {
+        anyhow::bail!("--sample must be at least 1");
+    }
+    if options
+        .sample_percent
+        .is_some_and(|percent| !(percent > 0.0 && percent <= 100.0))
+    {
+        anyhow::bail!("--sample-percent must be more than 0 and at most 100");
+    }
+    if options.seed.is_some()
+        && options.sample.is_none()
+        && options.sample_percent.is_none()
+        && !options.shuffle
+    {
+        anyhow::bail!("--seed only applies to --sample, --sample-percent and --shuffle");
+    }
+
+    if options.append && options.output.as_deref() == Some(Path::new("-")) {
+        anyhow::bail!("--append adds to a job file, it can't be used with `-o -`");
+    }
+    // Checked before the scan too, so a mistyped -o fails right away
+    if let Some(output) = &options.output {
+        refuse_overwrite(output, &options)?;
+    }
+
+    if let (Some(min), Some(max)) = (options.min_size, options.max_size)
+        && min > max
+    {
+        anyhow::bail!(
+            "--min-size ({}) is larger than --max-size ({})",
+            BinaryBytes(min),
+            BinaryBytes(max)
+        );
+    }
+
+    let mut options = options;
+    if let Some(reference) = &options.modified_since_file {
+        let modified = fs::metadata(reference)
+            .and_then(|metadata| metadata.modified())
+            .with_context(|| {
+                format!("Failed to read the modification time of {}", reference.display())
+            })?;
+        options.modified_since = Some(modified.into());
+    }
+    options.extensions = normalize_extensions(&options.extensions);
+    if options.extensions.is_empty() {
+        anyhow::bail!("--extensions needs at least one extension");
+    }
+
+    let stdin = Path::new("-");
+    let list = if directories.len() == 1 && directories[0] == stdin {
+        directories.clear();
+        Some(stdin.to_path_buf())
+    } else {
+        options.from_file.clone()
     };
+    if directories.iter().any(|directory| directory == stdin) {
+        anyhow::bail!("`-` (read the file list from stdin) can't be combined with directories");
+    }
+    if list.is_some() {
+        if !options.exclude.is_empty()
+            || options.follow_symlinks
+            || options.max_depth.is_some()
+            || options.scan_threads.is_some()
+        {
+            anyhow::bail!(
+                "--exclude, --follow-symlinks, --max-depth and --scan-threads only apply to directory scans, not to a file list"
+            );
+        }
+    } else if !options.roots.is_empty() {
+        anyhow::bail!("--root only applies to a file list (the directories given are the roots)");
+    }
+
+    // The same directory given twice is only scanned once
+    let mut seen = HashSet::new();
+    directories.retain(|directory| seen.insert(directory.clone()));
+
+    match &list {
+        Some(list) => outln!(
+            "{} Reading file list: {}",
+            "→".blue().bold(),
+            if list == stdin {
+                "stdin".to_string()
+            } else {
+                list.display().to_string()
+            }
+        ),
+        None => {
+            for directory in &directories {
+                outln!(
+                    "{} Exploring directory: {}",
+                    "→".blue().bold(),
+                    directory.display()
+                );
+
+                // Check if the directory exists
+                if !directory.exists() {
+                    anyhow::bail!("Directory does not exist: {}", directory.display());
+                }
+
+                if !directory.is_dir() {
+                    anyhow::bail!("Path is not a directory: {}", directory.display());
+                }
+            }
+        }
+    }
 
     // Create a spinner for the directory scanning phase
     let spinner = ProgressBar::new_spinner();
@@ -42,21 +347,378 @@ pub fn explore_directory(directory: PathBuf, output: Option<PathBuf>) -> Result<
     );
     spinner.set_message("Scanning directory tree...");
 
-    // Find all FLAC files in the directory tree
-    let flac_files = find_flac_files(&directory, &spinner)?;
+    // Read the other job files before the scan, so a wrong path fails right away
+    let covered = load_covered_files(&options.exclude_jobs)?;
+
+    // Find all FLAC files in the directory trees, or take them from the list
+    let mut unusable = Vec::new();
+    let mut scan = match &list {
+        Some(list) => {
+            let (scan, rejected) = read_file_list(list, &options, &spinner)?;
+            unusable = rejected;
+            scan
+        }
+        None => scan_directories(&directories, &options, &spinner)?,
+    };
+    // Jobs are sorted by path (component by component, comparing bytes, so the order
+    // doesn't depend on the locale), which keeps explores of the same tree identical.
+    // A directory given twice, or inside another one, must not list its files twice.
+    if options.no_sort {
+        let mut seen = HashSet::new();
+        scan.files.retain(|path| seen.insert(path.clone()));
+    } else {
+        scan.files.sort();
+        scan.files.dedup();
+    }
+
+    // Links can reach the same file by several paths; it is only checked once
+    let mut duplicates = 0;
+    if options.follow_symlinks {
+        let before = scan.files.len();
+        scan.files = dedup_by_real_path(scan.files);
+        duplicates = before - scan.files.len();
+    }
+    let covered_counts = exclude_covered_files(&mut scan.files, &covered);
     
     spinner.finish_and_clear();
 
+    // Listed files go below --root, or else below the directory they all share
+    let roots = match list {
+        Some(_) if !options.roots.is_empty() => options.roots.clone(),
+        Some(_) => match common_directory(&scan.files) {
+            Some(root) => vec![root],
+            None if scan.files.is_empty() => Vec::new(),
+            None => anyhow::bail!(
+                "The listed files share no common directory, use --root to give the library root"
+            ),
+        },
+        None => directories,
+    };
+
+    if options.relative && roots.len() > 1 {
+        anyhow::bail!(
+            "--relative needs a single root directory, as each path is stored relative to it"
+        );
+    }
+
+    // Generate output filename if not provided
+    let output = match options.output.clone() {
+        Some(path) => path,
+        None => generate_job_filename(&roots),
+    };
+    refuse_overwrite(&output, &options)?;
+
+    // --append: the files the job file already has keep their entries (and statuses)
+    let mut appending = None;
+    let mut already_present = 0;
+    if options.append && output.exists() {
+        let lock = JobLock::acquire(&output, false)?;
+        let existing = JobFile::load(&output)?;
+        if existing.sample.is_some() {
+            anyhow::bail!(
+                "{} holds a random sample (explore --sample), files can't be appended to it",
+                output.display()
+            );
+        }
+        if existing.relative_paths
+            && roots
+                .iter()
+                .any(|root| find_root(&existing.root_directories, root).is_none())
+        {
+            anyhow::bail!(
+                "{} stores paths relative to {}, so only files below it can be appended",
+                output.display(),
+                existing
+                    .root_directories
+                    .first()
+                    .map_or_else(String::new, |root| root.display().to_string())
+            );
+        }
+        let known: HashSet<&Path> = existing.jobs.iter().map(|job| job.path.as_path()).collect();
+        let before = scan.files.len();
+        scan.files.retain(|path| !known.contains(path.as_path()));
+        already_present = before - scan.files.len();
+        appending = Some((existing, lock));
+    }
+
+    if !unusable.is_empty() {
+        outln!(
+            "{} Ignored {} listed paths:",
+            "⚠".yellow().bold(),
+            unusable.len()
+        );
+        for (path, reason) in &unusable {
+            outln!("  {} {} {}", "✗".red(), path, format!("({})", reason).dimmed());
+        }
+    }
+
+    if let Some(depth) = options.max_depth {
+        outln!(
+            "{} Scanned to a depth of {} (--max-depth), leaving out {} deeper directories",
+            "→".blue().bold(),
+            depth,
+            scan.too_deep_dirs
+        );
+    }
+
+    if let Some(since) = options.modified_since {
+        outln!(
+            "{} Left out {} FLAC files not modified since {} (--modified-since)",
+            "→".blue().bold(),
+            scan.not_modified,
+            since.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+
+    if options.min_size.is_some() || options.max_size.is_some() {
+        scan.outside_size.sort();
+        scan.outside_size.dedup();
+        outln!(
+            "{} Left out {} FLAC files outside the size range (--min-size/--max-size){}",
+            "→".blue().bold(),
+            scan.outside_size.len(),
+            if scan.outside_size.is_empty() || options.show_skipped {
+                ""
+            } else {
+                ", list them with --show-skipped"
+            }
+        );
+        if options.show_skipped {
+            for (path, size) in &scan.outside_size {
+                outln!(
+                    "  {} {} {}",
+                    "-".yellow(),
+                    path.display(),
+                    format!("({})", BinaryBytes(*size)).dimmed()
+                );
+            }
+        }
+    }
+
+    if scan.skipped_symlinks > 0 {
+        outln!(
+            "{} Skipped {} symbolic links (use --follow-symlinks to scan what they point to)",
+            "→".blue().bold(),
+            scan.skipped_symlinks
+        );
+    }
+
+    if scan.symlink_loops > 0 {
+        outln!(
+            "{} Skipped {} symbolic links pointing back up the tree (not walked again)",
+            "→".blue().bold(),
+            scan.symlink_loops
+        );
+    }
+
+    if duplicates > 0 {
+        outln!(
+            "{} Listed {} FLAC files reached through more than one path only once",
+            "→".blue().bold(),
+            duplicates
+        );
+    }
+
+    if !scan.broken_symlinks.is_empty() {
+        outln!(
+            "{} Found {} broken symbolic links:",
+            "⚠".yellow().bold(),
+            scan.broken_symlinks.len()
+        );
+        for (link, target) in &scan.broken_symlinks {
+            outln!("  {} {} -> {}", "✗".red(), link.display(), target.display());
+        }
+    }
+
+    if !scan.ignore_files.is_empty() {
+        outln!(
+            "{} Ignored {} FLAC files and {} directories (not scanned) by the rules of {} {} files",
+            "→".blue().bold(),
+            scan.ignored_files,
+            scan.ignored_dirs,
+            scan.ignore_files.len(),
+            IGNORE_FILE_NAME
+        );
+    }
+
+    for warning in &scan.ignore_warnings {
+        outln!("{} {}", "⚠".yellow().bold(), warning);
+    }
+
+    if scan.hidden_files + scan.hidden_dirs > 0 {
+        outln!(
+            "{} Skipped {} hidden FLAC files and {} hidden directories (not scanned), use --include-hidden to scan them",
+            "→".blue().bold(),
+            scan.hidden_files,
+            scan.hidden_dirs
+        );
+    }
+
+    if !options.exclude.is_empty() {
+        outln!(
+            "{} Excluded {} FLAC files and {} directories (not scanned) matching --exclude",
+            "→".blue().bold(),
+            scan.excluded_files,
+            scan.excluded_dirs
+        );
+    }
+
+    print_covered_counts(&covered, &covered_counts);
+
+    // Listed last, so they aren't scrolled away by the rest of the summary; the paths
+    // are kept in the job file for stats
+    let scan_errors = scan.errors;
+    let report_scan_errors = || {
+        if scan_errors.is_empty() {
+            return;
+        }
+        outln!(
+            "\n{} Could not read {} paths, files below them are not in the job:",
+            "⚠".yellow().bold(),
+            scan_errors.len()
+        );
+        for scan_error in &scan_errors {
+            outln!(
+                "  {} {}: {}",
+                "✗".red(),
+                scan_error.path.display(),
+                scan_error.error
+            );
+        }
+    };
+    if options.strict_scan && !scan_errors.is_empty() {
+        report_scan_errors();
+        anyhow::bail!(
+            "{} paths could not be read (--strict-scan), no job file written",
+            scan_errors.len()
+        );
+    }
+
+    // Paths that would land on top of each other on a case-insensitive filesystem
+    let case_collisions = find_case_collisions(&scan.files, &roots);
+    let report_case_collisions = || print_case_collisions(&case_collisions);
+    if options.fail_on_case_collision && !case_collisions.is_empty() {
+        report_case_collisions();
+        anyhow::bail!(
+            "{} sets of paths differ only in letter case (--fail-on-case-collision), no job file written",
+            case_collisions.len()
+        );
+    }
+
+    let mut flac_files = scan.files;
+
+    // One seed serves both the sample and the shuffle, so --seed repeats the whole job
+    let seed = options.seed.unwrap_or_else(clock_seed);
+
+    // --sample: a random selection of what was found, kept in the order of the job
+    let mut sample = None;
+    let wanted = match (options.sample, options.sample_percent) {
+        (Some(files), _) => Some(files),
+        (None, Some(percent)) => {
+            let files = (flac_files.len() as f64 * percent / 100.0).ceil() as usize;
+            Some(files.max(1))
+        }
+        (None, None) => None,
+    };
+    if let Some(wanted) = wanted
+        && !flac_files.is_empty()
+    {
+        let population = flac_files.len();
+        let mut picked: Vec<usize> = (0..population).collect();
+        shuffle(&mut picked, seed);
+        picked.truncate(wanted);
+        picked.sort_unstable();
+        let mut picked = picked.into_iter().peekable();
+        let mut idx = 0;
+        flac_files.retain(|_| {
+            let keep = picked.next_if_eq(&idx).is_some();
+            idx += 1;
+            keep
+        });
+        outln!(
+            "{} Picked a random sample of {} of the {} FLAC files found (seed {}; --seed {} picks the same files again)",
+            "→".blue().bold(),
+            flac_files.len(),
+            population,
+            seed,
+            seed
+        );
+        sample = Some(Sample {
+            files: flac_files.len(),
+            population,
+            seed,
+        });
+    }
+
+    // --shuffle: a random order, so runs that only get through part of the job (check
+    // --limit, --max-duration) cover the library evenly rather than always the same albums
+    let mut shuffle_seed = None;
+    if options.shuffle && !flac_files.is_empty() {
+        shuffle(&mut flac_files, seed);
+        outln!(
+            "{} Shuffled the order of the jobs (seed {}; --seed {} gives the same order again)",
+            "→".blue().bold(),
+            seed,
+            seed
+        );
+        shuffle_seed = Some(seed);
+    }
+
+    // Hardlinks of a file already in the list are only checked through that path
+    let hardlinks: HashMap<usize, PathBuf> = if options.no_dedupe_hardlinks {
+        HashMap::new()
+    } else {
+        find_hardlinks(&flac_files)
+            .into_iter()
+            .map(|(idx, original)| (idx, flac_files[original].clone()))
+            .collect()
+    };
+    if !hardlinks.is_empty() {
+        outln!(
+            "{} Found {} hardlinks to files already in the job, marked Duplicate (not checked)",
+            "→".blue().bold(),
+            hardlinks.len()
+        );
+    }
+
+    if flac_files.is_empty() && appending.is_some() {
+        outln!(
+            "{} No new FLAC files, all {} found are already in {}",
+            "✓".green().bold(),
+            already_present,
+            output.display()
+        );
+        report_scan_errors();
+        return Ok(if options.dry_run { EXIT_NOTHING_FOUND } else { 0 });
+    }
     if flac_files.is_empty() {
-        println!("{} No FLAC files found", "✗".red().bold());
-        return Ok(());
+        outln!("{} No FLAC files found", "✗".red().bold());
+        report_scan_errors();
+        return Ok(if options.dry_run { EXIT_NOTHING_FOUND } else { 0 });
     }
 
-    println!(
-        "{} Found {} FLAC files",
-        "✓".green().bold(),
-        flac_files.len()
-    );
+    if appending.is_some() {
+        outln!(
+            "{} Found {} new FLAC files ({} already in {})",
+            "✓".green().bold(),
+            flac_files.len(),
+            already_present,
+            output.display()
+        );
+    } else {
+        outln!(
+            "{} Found {} FLAC files",
+            "✓".green().bold(),
+            flac_files.len()
+        );
+    }
+
+    if options.dry_run {
+        print_dry_run(&flac_files, &hardlinks, &roots, options.list);
+        report_case_collisions();
+        report_scan_errors();
+        return Ok(0);
+    }
 
     // Create a progress bar for processing the files
     let pb = ProgressBar::new(flac_files.len() as u64);
@@ -72,13 +734,14 @@ pub fn explore_directory(directory: PathBuf, output: Option<PathBuf>) -> Result<
     let counter = Arc::new(AtomicUsize::new(0));
 
     // Create jobs for all FLAC files (all start as ToBeChecked)
-    let jobs: Vec<FlacJob> = flac_files
+    // Collecting the parallel iterator keeps the order of the files
+    let mut jobs: Vec<FlacJob> = flac_files
         .into_par_iter() // Use parallel iterator for performance
-        .map(|path| {
-            let job = FlacJob {
-                path,
-                status: FlacStatus::ToBeChecked,
-                error_message: None,
+        .enumerate()
+        .map(|(idx, path)| {
+            let job = match hardlinks.get(&idx) {
+                Some(original) => duplicate_job(path, original),
+                None => new_job(path, !options.no_probe),
             };
             
             // Update progress bar (thread-safe)
@@ -91,51 +754,725 @@ pub fn explore_directory(directory: PathBuf, output: Option<PathBuf>) -> Result<
 
     pb.finish_with_message("Done!");
 
+    print_impostors(&jobs);
+    if options.read_metadata {
+        read_stream_info(&mut jobs);
+    }
+
     // Calculate statistics
     let statistics = Statistics::from_jobs(&jobs);
 
     // Create the job file structure
-    let job_file = JobFile {
-        root_directory: directory.clone(),
-        total_files: jobs.len(),
-        statistics,
-        jobs,
+    // The lock is held until the merged file is saved
+    let (appending, lock) = appending.unzip();
+    let added = jobs.len();
+    let job_file = match appending {
+        Some(existing) => {
+            append_jobs(existing, jobs, roots, scan_errors.clone(), scan.ignore_files)
+        }
+        None => JobFile {
+            root_directories: roots,
+            total_files: jobs.len(),
+            statistics,
+            jobs,
+            results_of: None,
+            exclude: options.exclude,
+            // Absolute, so update finds them from any working directory
+            exclude_jobs: options
+                .exclude_jobs
+                .iter()
+                .map(|path| std::path::absolute(path).unwrap_or_else(|_| path.clone()))
+                .collect(),
+            max_depth: options.max_depth,
+            min_size: options.min_size,
+            max_size: options.max_size,
+            modified_since: options.modified_since,
+            extensions: if options.extensions == ["flac"] {
+                Vec::new()
+            } else {
+                options.extensions
+            },
+            follow_symlinks: options.follow_symlinks,
+            include_hidden: options.include_hidden,
+            read_metadata: options.read_metadata,
+            relative_paths: options.relative,
+            stored_root: None,
+            scan_errors: scan_errors.clone(),
+            ignore_files: scan.ignore_files,
+            sample,
+            shuffle_seed,
+        },
     };
 
-    // Serialize to JSON with pretty printing for human readability
-    println!("{} Serializing job file...", "→".blue().bold());
-    let json = serde_json::to_string_pretty(&job_file)
-        .context("Failed to serialize job file to JSON")?;
+    // Serialize to JSON and write it to the output file atomically, or to stdout
+    outln!("{} Serializing job file...", "→".blue().bold());
+    if output == Path::new("-") {
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{}", job_file.to_json()?)
+            .and_then(|()| stdout.flush())
+            .context("Failed to write job file to stdout")?;
+        outln!("{} Job file written to stdout", "✓".green().bold());
+    } else {
+        job_file.save(&output)?;
+        if lock.is_some() {
+            outln!(
+                "{} Job file updated: {} ({} entries added, {} already present)",
+                "✓".green().bold(),
+                output.display(),
+                added,
+                already_present
+            );
+        } else {
+            outln!(
+                "{} Job file created: {}",
+                "✓".green().bold(),
+                output.display()
+            );
+        }
+    }
+
+    // Print summary statistics
+    print_summary(&job_file);
+    report_case_collisions();
+    report_scan_errors();
+
+    Ok(0)
+}
+
+/// Fold the letter case of a name: every character is lowercased on its own (str::to_lowercase
+/// would turn a final Σ into ς) and ς becomes σ, as case-insensitive filesystems treat both as Σ
+fn fold_case(name: &str) -> impl Iterator<Item = char> + '_ {
+    name.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == 'ς' { 'σ' } else { c })
+}
+
+/// Find the paths that are the same when letter case is ignored, which a case-insensitive
+/// filesystem (exFAT, FAT32, and NTFS or APFS as usually set up) can't hold side by side.
+/// Directories are compared as well as files, so two album folders that differ only in
+/// case are one collision, not one per track. Returns the groups of colliding paths, each
+/// sorted, in path order. Names are compared after `fold_case`, per character, so a final
+/// Σ matches σ.
+pub fn find_case_collisions(files: &[PathBuf], roots: &[PathBuf]) -> Vec<Vec<PathBuf>> {
+    // Every directory and file below its root, by root and lowercased relative path
+    let mut entries: BTreeMap<(Option<&Path>, String), BTreeSet<PathBuf>> = BTreeMap::new();
+    for path in files {
+        let root = find_root(roots, path);
+        let relative = root
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        let mut entry = root.map(Path::to_path_buf).unwrap_or_default();
+        let mut folded = String::new();
+        for component in relative.components() {
+            entry.push(component);
+            folded.push('/');
+            folded.extend(fold_case(&component.as_os_str().to_string_lossy()));
+            entries
+                .entry((root, folded.clone()))
+                .or_default()
+                .insert(entry.clone());
+        }
+    }
 
-    // Write to the output file
-    fs::write(&output, json)
-        .with_context(|| format!("Failed to write job file to {}", output.display()))?;
+    // Below two colliding directories everything collides again; only the top counts
+    let colliding: HashSet<&(Option<&Path>, String)> = entries
+        .iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(key, _)| key)
+        .collect();
+    colliding
+        .iter()
+        .filter(|(root, folded)| {
+            let parent = folded.rsplit_once('/').map_or("", |(parent, _)| parent);
+            !colliding.contains(&(*root, parent.to_string()))
+        })
+        .map(|key| entries[*key].iter().cloned().collect::<Vec<_>>())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
 
-    println!(
-        "{} Job file created: {}",
-        "✓".green().bold(),
-        output.display()
+/// List the paths that differ only in letter case, one group per collision
+fn print_case_collisions(collisions: &[Vec<PathBuf>]) {
+    if collisions.is_empty() {
+        return;
+    }
+    outln!(
+        "\n{} Found {} sets of paths that differ only in letter case (they collide on case-insensitive filesystems such as exFAT):",
+        "⚠".yellow().bold(),
+        collisions.len()
     );
+    for group in collisions {
+        for (idx, path) in group.iter().enumerate() {
+            let marker = if idx == 0 { "✗".red() } else { " ".normal() };
+            outln!("  {} {}", marker, path.display());
+        }
+    }
+}
 
-    // Print summary statistics
-    print_summary(&job_file);
+/// The files of another job file, for --exclude-job
+pub struct CoveredFiles {
+    pub job_file: PathBuf,
+    /// Real paths of its jobs (as stored, for files that no longer exist)
+    paths: HashSet<PathBuf>,
+}
 
+/// Load the job files given to --exclude-job. Relative job paths are resolved against
+/// the root of the job file they come from, and every path is canonicalized so a file
+/// is recognized whichever way either job reaches it.
+pub fn load_covered_files(job_files: &[PathBuf]) -> Result<Vec<CoveredFiles>> {
+    job_files
+        .iter()
+        .map(|path| {
+            let job_file = JobFile::load(path)?;
+            let paths = job_file
+                .jobs
+                .par_iter()
+                .map(|job| fs::canonicalize(&job.path).unwrap_or_else(|_| job.path.clone()))
+                .collect();
+            Ok(CoveredFiles {
+                job_file: path.clone(),
+                paths,
+            })
+        })
+        .collect()
+}
+
+/// Drop the files already in one of the other job files
+/// Returns how many were dropped for each job file (counted for the first that has them)
+pub fn exclude_covered_files(files: &mut Vec<PathBuf>, covered: &[CoveredFiles]) -> Vec<usize> {
+    let mut counts = vec![0; covered.len()];
+    if covered.is_empty() {
+        return counts;
+    }
+    let owners: Vec<Option<usize>> = files
+        .par_iter()
+        .map(|path| {
+            let real = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            covered.iter().position(|other| other.paths.contains(&real))
+        })
+        .collect();
+    let mut owners = owners.into_iter();
+    files.retain(|_| match owners.next().flatten() {
+        Some(owner) => {
+            counts[owner] += 1;
+            false
+        }
+        None => true,
+    });
+    counts
+}
+
+/// Say how many files each --exclude-job file kept out of the job
+pub fn print_covered_counts(covered: &[CoveredFiles], counts: &[usize]) {
+    for (other, count) in covered.iter().zip(counts) {
+        outln!(
+            "{} Excluded {} FLAC files already in {} (--exclude-job)",
+            "→".blue().bold(),
+            count,
+            other.job_file.display()
+        );
+    }
+}
+
+/// Refuse to replace an existing job file, which may hold days of check results, unless
+/// --force or --append says what to do with it (a dry run writes nothing)
+fn refuse_overwrite(output: &Path, options: &ExploreOptions) -> Result<()> {
+    if options.dry_run {
+        return Ok(());
+    }
+    if output != Path::new("-") && output.exists() && !options.force && !options.append {
+        anyhow::bail!(
+            "Job file already exists: {} (use --force to replace it, or --append to add the new files to it)",
+            output.display()
+        );
+    }
     Ok(())
 }
 
+/// Merge the jobs of newly found files into an existing job file for --append; its own
+/// entries are kept as they are, and a sorted job file stays sorted
+fn append_jobs(
+    mut job_file: JobFile,
+    jobs: Vec<FlacJob>,
+    roots: Vec<PathBuf>,
+    scan_errors: Vec<ScanError>,
+    ignore_files: Vec<IgnoreFile>,
+) -> JobFile {
+    let was_sorted = job_file
+        .jobs
+        .windows(2)
+        .all(|pair| pair[0].path <= pair[1].path);
+    job_file.jobs.extend(jobs);
+    if was_sorted {
+        job_file.jobs.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    // A new root above an old one takes its place
+    for root in roots {
+        if find_root(&job_file.root_directories, &root).is_none() {
+            job_file.root_directories.retain(|old| !old.starts_with(&root));
+            job_file.root_directories.push(root);
+        }
+    }
+    job_file.total_files = job_file.jobs.len();
+    job_file.statistics = Statistics::from_jobs(&job_file.jobs);
+    job_file.scan_errors.extend(scan_errors);
+    for ignore_file in ignore_files {
+        if !job_file.ignore_files.iter().any(|known| known.path == ignore_file.path) {
+            job_file.ignore_files.push(ignore_file);
+        }
+    }
+    job_file
+}
+
+/// Create the job entry for a newly found file
+/// Size and modification time are recorded so check can detect changed files, and with
+/// `probe`, a file that isn't FLAC at all (e.g. a renamed MP3) is marked Bad right away
+/// rather than waiting for a check
+pub fn new_job(path: PathBuf, probe: bool) -> FlacJob {
+    let mut job = FlacJob::new(path);
+
+    if let Ok(metadata) = fs::metadata(&job.path) {
+        job.record_metadata(&metadata);
+    }
+
+    if probe
+        && let Ok(mut file) = File::open(&job.path)
+        && let Ok(Some(kind)) = probe_stream_start(&mut file)
+    {
+        job.status = FlacStatus::Bad;
+        job.error_message = Some(format!("not a FLAC stream ({})", kind));
+        job.verifier = Some("probe".to_string());
+        job.checked_at = Some(Utc::now());
+    }
+    job
+}
+
+/// Read the STREAMINFO header of each job's file for --read-metadata, so the format and
+/// length of the audio are known before anything is checked. Only the metadata blocks
+/// are read; files the probe already marked Bad and duplicates are left alone. Files
+/// that can't be read keep empty fields and are listed, but stay in the job.
+pub fn read_stream_info(jobs: &mut [FlacJob]) {
+    let pending: Vec<&mut FlacJob> = jobs
+        .iter_mut()
+        .filter(|job| job.status == FlacStatus::ToBeChecked)
+        .collect();
+    if pending.is_empty() {
+        return;
+    }
+
+    let pb = ProgressBar::new(pending.len() as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) {msg}")
+            .unwrap()
+            .progress_chars("#>-")
+    );
+    pb.set_message("Reading stream headers...");
+
+    let mut failures: Vec<(PathBuf, String)> = pending
+        .into_par_iter()
+        .filter_map(|job| {
+            let failure = match stream_info(&job.path) {
+                Ok(info) => {
+                    job.record_format(
+                        info.sample_rate,
+                        info.bits_per_sample,
+                        info.channels,
+                        info.samples,
+                    );
+                    None
+                }
+                Err(e) => Some((job.path.clone(), format!("{:#}", e))),
+            };
+            pb.inc(1);
+            failure
+        })
+        .collect();
+    pb.finish_with_message("Done!");
+
+    if failures.is_empty() {
+        return;
+    }
+    failures.sort();
+    outln!(
+        "{} Could not read the stream header of {} files (they are still in the job):",
+        "⚠".yellow().bold(),
+        failures.len()
+    );
+    for (path, error) in &failures {
+        outln!("  {} {}", path.display(), format!("({})", error).dimmed());
+    }
+}
+
+/// Read just the STREAMINFO header of a file, skipping any ID3v2 tags before the stream
+fn stream_info(path: &Path) -> Result<StreamInfo> {
+    let mut file = File::open(path).context("Failed to open the file")?;
+    let bounds = find_stream_bounds(&mut file)?;
+    file.seek(SeekFrom::Start(bounds.start))?;
+    let options = FlacReaderOptions {
+        metadata_only: true,
+        read_vorbis_comment: false,
+    };
+    let reader = FlacReader::new_ext(file.take(bounds.end - bounds.start), options)?;
+    Ok(reader.streaminfo())
+}
+
+/// Summary of an explore --dry-run: how many files the job would have, how much data a
+/// full check would read, and how the files are spread over the directories
+fn print_dry_run(
+    files: &[PathBuf],
+    hardlinks: &HashMap<usize, PathBuf>,
+    roots: &[PathBuf],
+    list: bool,
+) {
+    // Hardlinked duplicates would not be checked, so they count for neither
+    let sizes: Vec<Option<u64>> = files
+        .par_iter()
+        .enumerate()
+        .map(|(idx, path)| {
+            if hardlinks.contains_key(&idx) {
+                return None;
+            }
+            fs::metadata(path).ok().map(|metadata| metadata.len())
+        })
+        .collect();
+
+    let mut directories: BTreeMap<&Path, (usize, u64)> = BTreeMap::new();
+    for (idx, path) in files.iter().enumerate() {
+        if hardlinks.contains_key(&idx) {
+            continue;
+        }
+        let directory = path.parent().unwrap_or(path);
+        let entry = directories.entry(directory).or_default();
+        entry.0 += 1;
+        entry.1 += sizes[idx].unwrap_or(0);
+    }
+    let relative = |path: &Path| -> String {
+        let shown = find_root(roots, path)
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        if shown.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            shown.display().to_string()
+        }
+    };
+
+    outln!("\n{}", "Summary (dry run, no job file written):".bold());
+    for root in roots {
+        outln!("  Root directory: {}", root.display());
+    }
+    outln!("  Files:          {}", format_count(files.len() as u64));
+    if !hardlinks.is_empty() {
+        outln!(
+            "  Duplicates:     {} (hardlinks, not checked)",
+            format_count(hardlinks.len() as u64)
+        );
+    }
+    let known: Vec<u64> = sizes.iter().flatten().copied().collect();
+    if !known.is_empty() {
+        let total: u64 = known.iter().sum();
+        outln!(
+            "  Total size:     {} across {} files",
+            DecimalBytes(total),
+            format_count(known.len() as u64)
+        );
+        outln!(
+            "  Average size:   {}",
+            DecimalBytes(total / known.len() as u64)
+        );
+    }
+
+    outln!("\n{}", "Per directory:".bold());
+    for (directory, (count, bytes)) in &directories {
+        outln!(
+            "  {:>6}  {} {}",
+            count,
+            relative(directory),
+            format!("({})", DecimalBytes(*bytes)).dimmed()
+        );
+    }
+
+    if list {
+        outln!("\n{}", "Files:".bold());
+        for (idx, path) in files.iter().enumerate() {
+            match hardlinks.get(&idx) {
+                Some(original) => outln!(
+                    "  {} {}",
+                    relative(path),
+                    format!("(same file as {})", relative(original)).dimmed()
+                ),
+                None => outln!("  {}", relative(path)),
+            }
+        }
+    }
+}
+
+/// Create the job entry for a hardlink to the file of another job
+fn duplicate_job(path: PathBuf, original: &Path) -> FlacJob {
+    let mut job = FlacJob::new(path);
+    if let Ok(metadata) = fs::metadata(&job.path) {
+        job.record_metadata(&metadata);
+    }
+    job.status = FlacStatus::Duplicate;
+    job.duplicate_of = Some(original.to_path_buf());
+    job
+}
+
+/// Files that are hardlinks to a file earlier in the list, with the index of that file
+/// Only Unix has the device and inode numbers to tell; elsewhere nothing is found
+fn find_hardlinks(files: &[PathBuf]) -> HashMap<usize, usize> {
+    let mut hardlinks = HashMap::new();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let mut first: HashMap<(u64, u64), usize> = HashMap::new();
+        for (idx, path) in files.iter().enumerate() {
+            let Ok(metadata) = fs::metadata(path) else {
+                continue;
+            };
+            if metadata.nlink() < 2 {
+                continue;
+            }
+            match first.get(&(metadata.dev(), metadata.ino())) {
+                Some(&original) => {
+                    hardlinks.insert(idx, original);
+                }
+                None => {
+                    first.insert((metadata.dev(), metadata.ino()), idx);
+                }
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = files;
+
+    hardlinks
+}
+
+/// List the files the probe found not to be FLAC streams
+pub fn print_impostors(jobs: &[FlacJob]) {
+    let impostors: Vec<&FlacJob> = jobs
+        .iter()
+        .filter(|job| job.verifier.as_deref() == Some("probe"))
+        .collect();
+    if impostors.is_empty() {
+        return;
+    }
+    outln!(
+        "{} {} files are not FLAC streams and were marked Bad:",
+        "⚠".yellow().bold(),
+        impostors.len()
+    );
+    for job in &impostors {
+        outln!(
+            "  {} {} {}",
+            "✗".red(),
+            job.path.display(),
+            job.error_message.as_deref().unwrap_or_default().dimmed()
+        );
+    }
+}
+
+/// Scan several directory trees into one Scan
+pub fn scan_directories(
+    directories: &[PathBuf],
+    options: &ExploreOptions,
+    spinner: &ProgressBar,
+) -> Result<Scan> {
+    let mut all = Scan::default();
+    let mut seen_links = HashSet::new();
+    for directory in directories {
+        let mut scan = match options.scan_threads {
+            Some(threads) if threads > 1 => {
+                scan_directory_parallel(directory, options, spinner, threads)?
+            }
+            _ => scan_directory(directory, options, spinner)?,
+        };
+        // Followed links can lead to the same broken link more than once
+        scan.broken_symlinks.retain(|(link, _)| {
+            let real = link
+                .parent()
+                .and_then(|parent| fs::canonicalize(parent).ok())
+                .map(|parent| parent.join(link.file_name().unwrap_or_default()));
+            real.is_none_or(|real| seen_links.insert(real))
+        });
+        all.append(scan);
+    }
+    Ok(all)
+}
+
+/// Read a list of FLAC files, one path per line (`-` reads stdin); blank lines and
+/// lines starting with `#` are skipped
+/// Returns the usable files (the size filters apply as for a scan) and the listed paths
+/// that can't be used, with the reason
+fn read_file_list(
+    source: &Path,
+    options: &ExploreOptions,
+    spinner: &ProgressBar,
+) -> Result<(Scan, Vec<(String, &'static str)>)> {
+    let contents = if source == Path::new("-") {
+        let mut contents = String::new();
+        io::stdin()
+            .read_to_string(&mut contents)
+            .context("Failed to read the file list from stdin")?;
+        contents
+    } else {
+        fs::read_to_string(source)
+            .with_context(|| format!("Failed to read file list: {}", source.display()))?
+    };
+
+    let mut scan = Scan::default();
+    let mut unusable = Vec::new();
+    let lines = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    for (count, line) in lines.enumerate() {
+        if (count + 1) % 100 == 0 {
+            spinner.set_message(format!("Reading list... (checked {} paths)", count + 1));
+            spinner.tick();
+        }
+
+        let path = PathBuf::from(line);
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                unusable.push((line.to_string(), "does not exist"));
+                continue;
+            }
+        };
+        if !metadata.is_file() {
+            unusable.push((line.to_string(), "not a file"));
+        } else if !has_extension(&path, &options.extensions) {
+            unusable.push((line.to_string(), "not a FLAC extension, see --extensions"));
+        } else if options.min_size.is_some_and(|min| metadata.len() < min)
+            || options.max_size.is_some_and(|max| metadata.len() > max)
+        {
+            scan.outside_size.push((path, metadata.len()));
+        } else if options.not_modified_since(&metadata) {
+            scan.not_modified += 1;
+        } else {
+            scan.files.push(path);
+        }
+    }
+
+    Ok((scan, unusable))
+}
+
+/// The deepest directory containing all of `files`, if they share one
+fn common_directory(files: &[PathBuf]) -> Option<PathBuf> {
+    let (first, rest) = files.split_first()?;
+    let mut prefix: Vec<Component> = first.parent()?.components().collect();
+    for file in rest {
+        let parent = file.parent().unwrap_or(Path::new(""));
+        let shared = prefix
+            .iter()
+            .zip(parent.components())
+            .take_while(|(a, b)| *a == b)
+            .count();
+        prefix.truncate(shared);
+    }
+    (!prefix.is_empty()).then(|| prefix.iter().collect())
+}
+
 /// Find all FLAC files in a directory tree
 /// Returns a vector of paths to FLAC files
-fn find_flac_files(directory: &Path, spinner: &ProgressBar) -> Result<Vec<PathBuf>> {
+pub fn find_flac_files(directory: &Path, spinner: &ProgressBar) -> Result<Vec<PathBuf>> {
+    Ok(scan_directory(directory, &ExploreOptions::default(), spinner)?.files)
+}
+
+/// Find all FLAC files in a directory tree, leaving out those matching `exclude`
+/// Excluded directories are pruned, so nothing below them is read
+/// With `follow_symlinks`, WalkDir refuses to enter a link to one of the directories above
+/// it; either way broken links are recorded rather than dropped
+pub fn scan_directory(directory: &Path, options: &ExploreOptions, spinner: &ProgressBar) -> Result<Scan> {
+    let exclude = &options.exclude;
+    let follow_symlinks = options.follow_symlinks;
     let mut flac_files = Vec::new();
     let mut file_count = 0;
+    let mut excluded_files = 0;
+    let mut excluded_dirs = 0;
+    let mut broken_symlinks = Vec::new();
+    let mut symlink_loops = 0;
+    let mut skipped_symlinks = 0;
+    let mut too_deep_dirs = 0;
+    let mut outside_size = Vec::new();
+    let mut not_modified = 0;
+    let mut errors = Vec::new();
+    let mut hidden_files = 0;
+    let mut hidden_dirs = 0;
+    let mut ignore = IgnoreRules::new(directory);
+    let mut ignored_files = 0;
+    let mut ignored_dirs = 0;
 
     // WalkDir recursively walks through the directory tree
     // It's efficient and handles symlinks properly
     for entry in WalkDir::new(directory)
-        .follow_links(false) // Don't follow symbolic links to avoid loops
+        .follow_links(follow_symlinks)
+        .max_depth(options.max_depth.unwrap_or(usize::MAX))
         .into_iter()
-        .filter_map(|e| e.ok()) // Skip entries that cause errors (permissions, etc.)
+        .filter_entry(|entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+            if !options.include_hidden
+                && is_hidden(entry.file_name(), || entry.metadata().map_err(io::Error::from))
+            {
+                if entry.file_type().is_dir() {
+                    hidden_dirs += 1;
+                } else if has_extension(entry.path(), &options.extensions) {
+                    hidden_files += 1;
+                }
+                return false;
+            }
+            // The .checkflacignore rules come first, then --exclude on what they keep
+            if ignore.is_ignored(entry.path(), entry.file_type().is_dir()) {
+                if entry.file_type().is_dir() {
+                    ignored_dirs += 1;
+                } else if has_extension(entry.path(), &options.extensions) {
+                    ignored_files += 1;
+                }
+                return false;
+            }
+            if !is_excluded(exclude, directory, entry.path(), entry.file_type().is_dir()) {
+                return true;
+            }
+            if entry.file_type().is_dir() {
+                excluded_dirs += 1;
+            } else if has_extension(entry.path(), &options.extensions) {
+                excluded_files += 1;
+            }
+            false
+        })
     {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                // Following a broken link fails with the link's path
+                if e.loop_ancestor().is_some() {
+                    symlink_loops += 1;
+                } else if let Some(path) = e.path()
+                    && let Some(target) = broken_symlink_target(path)
+                {
+                    broken_symlinks.push((path.to_path_buf(), target));
+                } else {
+                    // Permissions, unreadable mounts, ...: report rather than lose a
+                    // whole folder without a trace
+                    errors.push(ScanError {
+                        path: e.path().unwrap_or(directory).to_path_buf(),
+                        error: match e.io_error() {
+                            Some(io_error) => io_error.to_string(),
+                            None => e.to_string(),
+                        },
+                    });
+                }
+                continue;
+            }
+        };
+
         // Update spinner every 100 entries for performance
         file_count += 1;
         if file_count % 100 == 0 {
@@ -143,41 +1480,510 @@ fn find_flac_files(directory: &Path, spinner: &ProgressBar) -> Result<Vec<PathBu
             spinner.tick();
         }
 
+        // Without following, links show up as links
+        if entry.path_is_symlink() && !follow_symlinks {
+            match broken_symlink_target(entry.path()) {
+                Some(target) => broken_symlinks.push((entry.path().to_path_buf(), target)),
+                None => skipped_symlinks += 1,
+            }
+            continue;
+        }
+
+        // WalkDir lists the directories at the limit without entering them
+        if entry.file_type().is_dir() && options.max_depth == Some(entry.depth()) {
+            too_deep_dirs += 1;
+        }
+
         // Check if this is a file (not a directory)
         if entry.file_type().is_file() {
             // Get the file path
             let path = entry.path();
 
-            // Check if the extension is .flac (case-insensitive)
-            if let Some(ext) = path.extension() {
-                if ext.eq_ignore_ascii_case("flac") {
-                    flac_files.push(path.to_path_buf());
-                    spinner.set_message(format!("Found {} FLAC files...", flac_files.len()));
+            if has_extension(path, &options.extensions) {
+                // Unreadable sizes are left for the job entry to report
+                if let Ok(metadata) = entry.metadata() {
+                    let size = metadata.len();
+                    if options.min_size.is_some_and(|min| size < min)
+                        || options.max_size.is_some_and(|max| size > max)
+                    {
+                        outside_size.push((path.to_path_buf(), size));
+                        continue;
+                    }
+                    if options.not_modified_since(&metadata) {
+                        not_modified += 1;
+                        continue;
+                    }
                 }
+                flac_files.push(path.to_path_buf());
+                spinner.set_message(format!("Found {} FLAC files...", flac_files.len()));
             }
         }
     }
 
-    Ok(flac_files)
+    Ok(Scan {
+        files: flac_files,
+        excluded_files,
+        excluded_dirs,
+        broken_symlinks,
+        symlink_loops,
+        skipped_symlinks,
+        too_deep_dirs,
+        outside_size,
+        not_modified,
+        errors,
+        hidden_files,
+        hidden_dirs,
+        ignored_files,
+        ignored_dirs,
+        ignore_files: ignore.files,
+        ignore_warnings: ignore.warnings,
+    })
+}
+
+/// Scan a directory tree like scan_directory, with several threads reading directories
+/// at once (a network filesystem spends most of a scan waiting on round trips)
+/// Each directory's findings are put together in the order a single walker finds them,
+/// so the result is the same as scan_directory's.
+fn scan_directory_parallel(
+    directory: &Path,
+    options: &ExploreOptions,
+    spinner: &ProgressBar,
+    threads: usize,
+) -> Result<Scan> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .context("Failed to initialize thread pool")?;
+    let walk = ParallelWalk {
+        root: directory,
+        options,
+        spinner,
+        ignore: Mutex::new(IgnoreRules::new(directory)),
+        items: AtomicUsize::new(1),
+        found: AtomicUsize::new(0),
+    };
+
+    // Links are only followed into directories that aren't above them
+    let mut ancestors = Vec::new();
+    if options.follow_symlinks {
+        match fs::canonicalize(directory) {
+            Ok(real) => ancestors.push(real),
+            Err(e) => {
+                return Ok(Scan {
+                    errors: vec![ScanError {
+                        path: directory.to_path_buf(),
+                        error: e.to_string(),
+                    }],
+                    ..Scan::default()
+                });
+            }
+        }
+    }
+
+    let mut scan = pool.install(|| walk.directory(directory, 0, &ancestors));
+    let ignore = walk.ignore.into_inner().unwrap();
+    scan.ignore_files.extend(ignore.files);
+    scan.ignore_warnings.extend(ignore.warnings);
+    Ok(scan)
+}
+
+/// What the threads of a parallel scan share
+struct ParallelWalk<'a> {
+    root: &'a Path,
+    options: &'a ExploreOptions,
+    spinner: &'a ProgressBar,
+    ignore: Mutex<IgnoreRules>,
+    /// Entries looked at and FLAC files found, for the spinner
+    items: AtomicUsize,
+    found: AtomicUsize,
 }
 
-/// Generate a job filename based on the directory path
-/// Sanitizes the path to only include alphanumeric characters, dashes, and underscores
+impl ParallelWalk<'_> {
+    /// Scan a directory at `depth` below the root, and the directories in it in parallel
+    /// `ancestors` are the real paths of the directories from the root down to this one
+    /// (only with --follow-symlinks)
+    fn directory(&self, dir: &Path, depth: usize, ancestors: &[PathBuf]) -> Scan {
+        let options = self.options;
+        let follow_symlinks = options.follow_symlinks;
+
+        let entries: Vec<io::Result<fs::DirEntry>> = match fs::read_dir(dir) {
+            Ok(read) => read.collect(),
+            Err(e) => {
+                return Scan {
+                    errors: vec![ScanError {
+                        path: dir.to_path_buf(),
+                        error: e.to_string(),
+                    }],
+                    ..Scan::default()
+                };
+            }
+        };
+        let has_ignore_file = entries.iter().any(|entry| {
+            entry
+                .as_ref()
+                .is_ok_and(|entry| entry.file_name() == IGNORE_FILE_NAME)
+        });
+        let mut rules_loaded = false;
+
+        // What was found between one subdirectory and the next; the subdirectories'
+        // own scans go in between
+        let mut segments = vec![Scan::default()];
+        let mut subdirs: Vec<(PathBuf, PathBuf)> = Vec::new();
+        for entry in entries {
+            let scan = segments.last_mut().unwrap();
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    scan.errors.push(ScanError {
+                        path: dir.to_path_buf(),
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(e) => {
+                    scan.errors.push(ScanError {
+                        path,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            // Followed links take the type of what they point to
+            let is_link = file_type.is_symlink();
+            let followed = follow_symlinks && is_link;
+            let file_type = if followed {
+                match fs::metadata(&path) {
+                    Ok(metadata) => metadata.file_type(),
+                    Err(e) => {
+                        match broken_symlink_target(&path) {
+                            Some(target) => scan.broken_symlinks.push((path, target)),
+                            None => scan.errors.push(ScanError {
+                                path,
+                                error: e.to_string(),
+                            }),
+                        }
+                        continue;
+                    }
+                }
+            } else {
+                file_type
+            };
+            let is_dir = file_type.is_dir();
+
+            let mut real = None;
+            if follow_symlinks && is_dir {
+                if followed {
+                    match fs::canonicalize(&path) {
+                        Ok(target) if ancestors.contains(&target) => {
+                            scan.symlink_loops += 1;
+                            continue;
+                        }
+                        Ok(target) => real = Some(target),
+                        Err(e) => {
+                            scan.errors.push(ScanError {
+                                path,
+                                error: e.to_string(),
+                            });
+                            continue;
+                        }
+                    }
+                } else {
+                    real = ancestors.last().map(|parent| parent.join(entry.file_name()));
+                }
+            }
+
+            // The same filters as scan_directory, in the same order
+            let metadata = || {
+                if followed {
+                    fs::metadata(&path)
+                } else {
+                    entry.metadata()
+                }
+            };
+            if !options.include_hidden && is_hidden(&entry.file_name(), metadata) {
+                if is_dir {
+                    scan.hidden_dirs += 1;
+                } else if has_extension(&path, &options.extensions) {
+                    scan.hidden_files += 1;
+                }
+                continue;
+            }
+            if !rules_loaded {
+                // Only a directory listing the file needs a read
+                let mut rules = if has_ignore_file {
+                    DirectoryRules::read(dir)
+                } else {
+                    DirectoryRules::none()
+                };
+                scan.ignore_files.extend(rules.file.take());
+                scan.ignore_warnings.append(&mut rules.warnings);
+                let mut ignore = self.ignore.lock().unwrap();
+                if !ignore.contains(dir) {
+                    ignore.insert(dir.to_path_buf(), rules);
+                }
+                rules_loaded = true;
+            }
+            if self.ignore.lock().unwrap().is_ignored(&path, is_dir) {
+                if is_dir {
+                    scan.ignored_dirs += 1;
+                } else if has_extension(&path, &options.extensions) {
+                    scan.ignored_files += 1;
+                }
+                continue;
+            }
+            if is_excluded(&options.exclude, self.root, &path, is_dir) {
+                if is_dir {
+                    scan.excluded_dirs += 1;
+                } else if has_extension(&path, &options.extensions) {
+                    scan.excluded_files += 1;
+                }
+                continue;
+            }
+
+            let items = self.items.fetch_add(1, Ordering::Relaxed) + 1;
+            if items.is_multiple_of(100) {
+                self.spinner
+                    .set_message(format!("Scanning... (checked {} items)", items));
+                self.spinner.tick();
+            }
+
+            if is_link && !follow_symlinks {
+                match broken_symlink_target(&path) {
+                    Some(target) => scan.broken_symlinks.push((path, target)),
+                    None => scan.skipped_symlinks += 1,
+                }
+                continue;
+            }
+
+            if is_dir {
+                if options.max_depth == Some(depth + 1) {
+                    scan.too_deep_dirs += 1;
+                } else {
+                    subdirs.push((path, real.unwrap_or_default()));
+                    segments.push(Scan::default());
+                }
+                continue;
+            }
+
+            if file_type.is_file() && has_extension(&path, &options.extensions) {
+                // Unreadable sizes are left for the job entry to report
+                if let Ok(metadata) = metadata() {
+                    let size = metadata.len();
+                    if options.min_size.is_some_and(|min| size < min)
+                        || options.max_size.is_some_and(|max| size > max)
+                    {
+                        scan.outside_size.push((path, size));
+                        continue;
+                    }
+                    if options.not_modified_since(&metadata) {
+                        scan.not_modified += 1;
+                        continue;
+                    }
+                }
+                scan.files.push(path);
+                let found = self.found.fetch_add(1, Ordering::Relaxed) + 1;
+                self.spinner
+                    .set_message(format!("Found {} FLAC files...", found));
+            }
+        }
+
+        let subscans: Vec<Scan> = subdirs
+            .par_iter()
+            .map(|(subdir, real)| {
+                let mut ancestors = ancestors.to_vec();
+                if follow_symlinks {
+                    ancestors.push(real.clone());
+                }
+                self.directory(subdir, depth + 1, &ancestors)
+            })
+            .collect();
+
+        let mut segments = segments.into_iter();
+        let mut scan = segments.next().unwrap_or_default();
+        for (subscan, segment) in subscans.into_iter().zip(segments) {
+            scan.append(subscan);
+            scan.append(segment);
+        }
+        scan
+    }
+}
+
+/// The target of a symbolic link that points nowhere, or None for anything else
+fn broken_symlink_target(path: &Path) -> Option<PathBuf> {
+    let is_link = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    if !is_link || fs::metadata(path).is_ok() {
+        return None;
+    }
+    fs::read_link(path).ok()
+}
+
+/// Keep one path per real file, preferring a path that isn't itself a link, then the
+/// shortest one. Files whose real path can't be resolved are kept as they are
+pub fn dedup_by_real_path(files: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut chosen: HashMap<PathBuf, usize> = HashMap::new();
+    let mut kept: Vec<PathBuf> = Vec::with_capacity(files.len());
+    for path in files {
+        let Ok(real) = fs::canonicalize(&path) else {
+            kept.push(path);
+            continue;
+        };
+        match chosen.get(&real) {
+            Some(&idx) => {
+                let current = &kept[idx];
+                let better = match (current.is_symlink(), path.is_symlink()) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => path.components().count() < current.components().count(),
+                };
+                if better {
+                    kept[idx] = path;
+                }
+            }
+            None => {
+                chosen.insert(real, kept.len());
+                kept.push(path);
+            }
+        }
+    }
+    kept
+}
+
+/// Whether the extension is one of `extensions` (case-insensitive)
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .is_some_and(|ext| extensions.iter().any(|wanted| ext.eq_ignore_ascii_case(wanted)))
+}
+
+/// Lowercase the --extensions, drop leading dots, empty entries and repeats
+fn normalize_extensions(extensions: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for extension in extensions {
+        let extension = extension.trim().trim_start_matches('.').to_lowercase();
+        if !extension.is_empty() && !normalized.contains(&extension) {
+            normalized.push(extension);
+        }
+    }
+    normalized
+}
+
+/// Whether an entry is hidden: its name starts with a dot (e.g. macOS `._` AppleDouble
+/// files, Syncthing's `.stversions`), or on Windows it has the hidden attribute
+/// The metadata is only looked at on Windows
+fn is_hidden(name: &OsStr, metadata: impl FnOnce() -> io::Result<fs::Metadata>) -> bool {
+    if name.to_string_lossy().starts_with('.') {
+        return true;
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if metadata()
+            .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+        {
+            return true;
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = metadata;
+
+    false
+}
+
+/// Print how much data a full check reads, from the sizes recorded by explore
+/// Duplicates are read through the file they link to, so they don't count
+pub fn print_total_size(job_file: &JobFile) {
+    let sizes: Vec<u64> = job_file
+        .jobs
+        .iter()
+        .filter(|job| job.status != FlacStatus::Duplicate)
+        .filter_map(|job| job.size)
+        .collect();
+    if sizes.is_empty() {
+        return;
+    }
+    let total: u64 = sizes.iter().sum();
+    outln!(
+        "  Total size:     {} across {} files",
+        DecimalBytes(total),
+        format_count(sizes.len() as u64)
+    );
+    outln!(
+        "  Average size:   {}",
+        DecimalBytes(total / sizes.len() as u64)
+    );
+
+    // Only known for files whose STREAMINFO was read (explore --read-metadata or check)
+    let durations: Vec<f64> = job_file
+        .jobs
+        .iter()
+        .filter(|job| job.status != FlacStatus::Duplicate)
+        .filter_map(|job| job.duration_seconds)
+        .collect();
+    if !durations.is_empty() {
+        outln!(
+            "  Total duration: {} across {} files",
+            format_play_time(durations.iter().sum()),
+            format_count(durations.len() as u64)
+        );
+    }
+}
+
+/// Whether an --exclude pattern matches a directory entry: patterns with a `/` are
+/// matched against the path below the scanned root, others against the name alone
+/// A directory also matches a pattern for everything below it ("**/__incoming/**")
+fn is_excluded(patterns: &[String], root: &Path, path: &Path, is_dir: bool) -> bool {
+    let relative = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/");
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    patterns.iter().any(|pattern| {
+        if !pattern.contains('/') {
+            return glob_match(pattern, &name);
+        }
+        glob_match(pattern, &relative)
+            || (is_dir
+                && pattern
+                    .strip_suffix("/**")
+                    .is_some_and(|parent| glob_match(parent, &relative)))
+    })
+}
+
+/// Generate a job filename based on the directory paths
+/// Sanitizes the names to only include alphanumeric characters, dashes, and underscores
 /// Includes timestamp with second accuracy
-fn generate_job_filename(directory: &Path) -> PathBuf {
-    // Get the directory name (last component of the path)
-    let dir_name = directory
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("checkflac");
+fn generate_job_filename(directories: &[PathBuf]) -> PathBuf {
+    // Get the directory names (last component of each path); past two directories the
+    // name would get unwieldy, so the rest are only counted
+    let name = |directory: &PathBuf| {
+        directory
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("checkflac")
+            .to_string()
+    };
+    let dir_name = match directories {
+        [] => "checkflac".to_string(),
+        [one] => name(one),
+        [first, second] => format!("{}_{}", name(first), name(second)),
+        [first, second, rest @ ..] => {
+            format!("{}_{}_and_{}_more", name(first), name(second), rest.len())
+        }
+    };
 
     // Sanitize the directory name - keep only alphanumeric, dashes, and underscores
     let sanitized: String = dir_name
         .chars()
         .map(|c| {
-            if c.is_alphanumeric() {
-                c
-            } else if c == '-' || c == '_' {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
                 c
             } else {
                 '_' // Replace any other character with underscore
@@ -202,25 +2008,216 @@ fn generate_job_filename(directory: &Path) -> PathBuf {
 
 /// Print a summary of the job file statistics
 fn print_summary(job_file: &JobFile) {
-    println!("\n{}", "Summary:".bold().underline());
-    println!("  Root directory: {}", job_file.root_directory.display());
-    println!("  Total files:    {}", job_file.total_files);
-    println!("\n{}", "Status breakdown:".bold());
-    println!(
+    outln!("\n{}", "Summary:".bold().underline());
+    for root in &job_file.root_directories {
+        outln!("  Root directory: {}", root.display());
+    }
+    if let Some(note) = job_file.sample_note() {
+        outln!(
+            "  {} {} {}",
+            "Sample:        ".yellow().bold(),
+            note.yellow().bold(),
+            "(a spot check, not the whole library)".yellow()
+        );
+    }
+    if let Some(depth) = job_file.max_depth {
+        outln!("  Max depth:      {}", depth);
+    }
+    if let Some(range) = job_file.size_range() {
+        outln!("  Size range:     {}", range);
+    }
+    if let Some(since) = job_file.modified_since {
+        outln!(
+            "  Modified since: {}",
+            since.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    if job_file.relative_paths {
+        outln!("  Paths:          relative to the root directory");
+    }
+    if let Some(seed) = job_file.shuffle_seed {
+        outln!("  Order:          shuffled (seed {})", seed);
+    }
+    if !job_file.scan_errors.is_empty() {
+        outln!("  Scan errors:    {}", job_file.scan_errors.len());
+    }
+    for ignore_file in &job_file.ignore_files {
+        outln!(
+            "  Ignore file:    {} ({} patterns)",
+            ignore_file.path.display(),
+            ignore_file.patterns.len()
+        );
+    }
+    if !job_file.extensions.is_empty() {
+        outln!("  Extensions:     {}", job_file.extensions.join(", "));
+    }
+    outln!("  Total files:    {}", job_file.total_files);
+    print_total_size(job_file);
+    outln!("\n{}", "Status breakdown:".bold());
+    outln!(
         "  {} To be checked: {}",
         "○".yellow(),
         job_file.statistics.to_be_checked
     );
-    println!(
+    outln!(
         "  {} Checking:      {}",
         "◐".cyan(),
         job_file.statistics.checking
     );
-    println!("  {} OK:            {}", "✓".green(), job_file.statistics.ok);
-    println!("  {} Bad:           {}", "✗".red(), job_file.statistics.bad);
-    println!(
+    outln!("  {} OK:            {}", "✓".green(), job_file.statistics.ok);
+    outln!(
+        "  {} Warning:       {}",
+        "!".bright_yellow(),
+        job_file.statistics.warning
+    );
+    outln!("  {} Bad:           {}", "✗".red(), job_file.statistics.bad);
+    outln!(
         "  {} Error:         {}",
         "⚠".yellow(),
         job_file.statistics.error
     );
-}
\ No newline at end of file
+    outln!(
+        "  {} Missing:       {}",
+        "?".blue(),
+        job_file.statistics.missing
+    );
+    outln!(
+        "  {} Skipped:       {}",
+        "-".dimmed(),
+        job_file.statistics.skipped
+    );
+    if job_file.statistics.duplicate > 0 {
+        outln!(
+            "  {} Duplicate:     {}",
+            "=".dimmed(),
+            job_file.statistics.duplicate
+        );
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_dir;
+
+    /// Everything a scan reports, in a form two scans can be compared by
+    fn findings(scan: &Scan) -> String {
+        let ignore_files: Vec<_> = scan
+            .ignore_files
+            .iter()
+            .map(|file| (&file.path, &file.patterns))
+            .collect();
+        let errors: Vec<_> = scan
+            .errors
+            .iter()
+            .map(|error| (&error.path, &error.error))
+            .collect();
+        format!(
+            "files: {:#?}\nexcluded: {} files, {} dirs\nbroken links: {:?}\n\
+             loops: {}, skipped links: {}, too deep: {}\noutside size: {:?}\n\
+             not modified: {}\nerrors: {:?}\nhidden: {} files, {} dirs\n\
+             ignored: {} files, {} dirs\nignore files: {:?}\nignore warnings: {:?}",
+            scan.files,
+            scan.excluded_files,
+            scan.excluded_dirs,
+            scan.broken_symlinks,
+            scan.symlink_loops,
+            scan.skipped_symlinks,
+            scan.too_deep_dirs,
+            scan.outside_size,
+            scan.not_modified,
+            errors,
+            scan.hidden_files,
+            scan.hidden_dirs,
+            scan.ignored_files,
+            scan.ignored_dirs,
+            ignore_files,
+            scan.ignore_warnings
+        )
+    }
+
+    #[test]
+    fn case_collisions_fold_each_character() {
+        let root = PathBuf::from("/music");
+        let files: Vec<PathBuf> = [
+            "ΟΔΥΣΣΕΥΣ/01.flac",
+            "Οδυσσευς/01.flac",
+            "Other/Track.flac",
+            "other/track.FLAC",
+            "Other/Trakc.flac",
+        ]
+        .iter()
+        .map(|path| root.join(path))
+        .collect();
+
+        let collisions = find_case_collisions(&files, std::slice::from_ref(&root));
+        let expected: Vec<Vec<PathBuf>> = vec![
+            vec![root.join("Other"), root.join("other")],
+            vec![root.join("ΟΔΥΣΣΕΥΣ"), root.join("Οδυσσευς")],
+        ];
+        assert_eq!(collisions, expected);
+    }
+
+    /// The --scan-threads walker finds what the single walker finds, in the same order
+    #[cfg(unix)]
+    #[test]
+    fn parallel_scan_matches_serial_scan() {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+
+        let root = test_dir("parallel-scan");
+        for dir in ["A/B/C", "A/skip", "A/locked", ".hidden", "Z"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "top.flac",
+            ".secret.flac",
+            "notes.txt",
+            "A/one.flac",
+            "A/two.FLAC",
+            "A/B/three.flac",
+            "A/B/C/deep.flac",
+            "A/skip/left-out.flac",
+            "A/live.tmp.flac",
+            "A/locked/hidden-away.flac",
+            ".hidden/x.flac",
+            "Z/last.flac",
+        ] {
+            fs::write(root.join(file), "fLaC").unwrap();
+        }
+        fs::write(root.join(IGNORE_FILE_NAME), "skip/\n*.tmp.flac\n***\n").unwrap();
+        let nested = root.join("A/B").join(IGNORE_FILE_NAME);
+        fs::write(nested, "!three.flac\ndeep.flac\n").unwrap();
+        symlink(&root, root.join("A/B/loop")).unwrap();
+        symlink(root.join("missing.flac"), root.join("Z/broken.flac")).unwrap();
+        symlink(root.join("top.flac"), root.join("Z/link.flac")).unwrap();
+        symlink("self", root.join("Z/self")).unwrap();
+        // An unreadable directory is a scan error (except for root, who can read it)
+        let locked = root.join("A/locked");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let spinner = ProgressBar::hidden();
+        for options in [
+            ExploreOptions::default(),
+            ExploreOptions {
+                include_hidden: true,
+                ..ExploreOptions::default()
+            },
+            ExploreOptions {
+                max_depth: Some(2),
+                ..ExploreOptions::default()
+            },
+            ExploreOptions {
+                follow_symlinks: true,
+                exclude: vec!["Z/last.flac".to_string()],
+                ..ExploreOptions::default()
+            },
+        ] {
+            let serial = scan_directory(&root, &options, &spinner).unwrap();
+            let parallel = scan_directory_parallel(&root, &options, &spinner, 4).unwrap();
+            assert!(!serial.files.is_empty());
+            assert_eq!(findings(&parallel), findings(&serial));
+        }
+
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use crate::types::{FlacStatus, JobFile, write_file_atomic};
+use anyhow::Result;
+use colored::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Options controlling the export-ffp command
+pub struct ExportFfpOptions {
+    /// Write one combined file here instead of one file per album directory
+    pub single: Option<PathBuf>,
+    /// Overwrite existing ffp files
+    pub force: bool,
+}
+
+/// Write FLAC fingerprint (.ffp) files from the audio MD5s stored in a job file
+/// Each line is "relative/path.flac:md5hex", relative to the directory of the ffp file
+pub fn export_ffp(job_file_path: PathBuf, options: ExportFfpOptions) -> Result<()> {
+    println!("{} Loading job file...", "→".blue().bold());
+    let job_file = JobFile::load(&job_file_path)?;
+
+    // Only files that verified (OK, or OK with warnings) have a fingerprint worth sharing
+    let mut fingerprinted = Vec::new();
+    let mut skipped = Vec::new();
+    for job in &job_file.jobs {
+        match (&job.status, &job.audio_md5) {
+            (FlacStatus::Ok | FlacStatus::Warning, Some(md5)) => {
+                fingerprinted.push((&job.path, md5))
+            }
+            (FlacStatus::Ok | FlacStatus::Warning, None) => {
+                skipped.push((&job.path, "no stored MD5".to_string()))
+            }
+            (FlacStatus::ToBeChecked | FlacStatus::Checking, _) => {
+                skipped.push((&job.path, "not checked yet".to_string()))
+            }
+            (status, _) => skipped.push((&job.path, format!("status is {}", status))),
+        }
+    }
+
+    // Group the lines by the ffp file they go into
+    let mut outputs: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for (path, md5) in &fingerprinted {
+        let (ffp_path, base) = match &options.single {
+            Some(single) => (single.clone(), None),
+            None => {
+                let dir = path.parent().unwrap_or(Path::new(""));
+                (album_ffp_path(dir), Some(dir))
+            }
+        };
+        let relative = match base {
+            Some(dir) => path.strip_prefix(dir).unwrap_or(path),
+            None => job_file.relative_path(path),
+        };
+        outputs
+            .entry(ffp_path)
+            .or_default()
+            .push(format!("{}:{}", relative.display(), md5));
+    }
+
+    // Refuse before writing anything, so a run never leaves a mix of old and new files
+    if !options.force {
+        let existing: Vec<&PathBuf> = outputs.keys().filter(|path| path.exists()).collect();
+        if !existing.is_empty() {
+            for path in &existing {
+                eprintln!("  {} {}", "✗".red(), path.display());
+            }
+            anyhow::bail!(
+                "{} ffp files already exist (use --force to overwrite them)",
+                existing.len()
+            );
+        }
+    }
+
+    for (ffp_path, lines) in &mut outputs {
+        lines.sort();
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        write_file_atomic(ffp_path, contents.as_bytes())?;
+        println!(
+            "  {} {} ({} files)",
+            "✓".green(),
+            ffp_path.display(),
+            lines.len()
+        );
+    }
+
+    if !skipped.is_empty() {
+        println!("\n{}", "Skipped files:".yellow().bold());
+        for (path, reason) in &skipped {
+            let path = job_file.relative_path(path);
+            println!(
+                "  {} {} {}",
+                "-".yellow(),
+                path.display(),
+                format!("({})", reason).dimmed()
+            );
+        }
+    }
+
+    println!(
+        "\n{} Wrote {} ffp files covering {} files, skipped {}",
+        "✓".green().bold(),
+        outputs.len(),
+        fingerprinted.len(),
+        skipped.len()
+    );
+
+    Ok(())
+}
+
+/// Per-album fingerprint file, named after its directory (e.g. Album/Album.ffp)
+fn album_ffp_path(dir: &Path) -> PathBuf {
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "fingerprints".to_string());
+    dir.join(format!("{}.ffp", name))
+}
@@ -0,0 +1,291 @@
+use crate::types::IgnoreFile;
+use crate::util::glob_match;
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Name of the per-directory ignore file read by explore
+pub const IGNORE_FILE_NAME: &str = ".checkflacignore";
+
+/// One pattern of an ignore file
+struct Rule {
+    pattern: String,
+    /// `!pattern`: include what earlier rules excluded
+    negated: bool,
+    /// `pattern/`: only matches directories
+    dir_only: bool,
+    /// Patterns with a `/` match the path below the ignore file's directory, others the
+    /// name at any depth
+    anchored: bool,
+}
+
+impl Rule {
+    fn matches(&self, relative: &str, name: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            glob_match(&self.pattern, relative)
+        } else {
+            glob_match(&self.pattern, name)
+        }
+    }
+}
+
+/// The .checkflacignore rules met during a walk, gitignore-style: a file applies to its
+/// directory and everything below it, rules of deeper files come after those of the
+/// files above, and the last matching rule decides. Each directory's file is read once.
+pub struct IgnoreRules {
+    root: PathBuf,
+    rules: HashMap<PathBuf, Vec<Rule>>,
+    /// The ignore files found, with their valid patterns
+    pub files: Vec<IgnoreFile>,
+    /// Problems with the ignore files (malformed patterns, unreadable files)
+    pub warnings: Vec<String>,
+}
+
+impl IgnoreRules {
+    pub fn new(root: &Path) -> Self {
+        IgnoreRules {
+            root: root.to_path_buf(),
+            rules: HashMap::new(),
+            files: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Whether the ignore files in the directories from the root down to the entry's
+    /// parent exclude the entry
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let Some(parent) = path.parent() else {
+            return false;
+        };
+        let Ok(below_root) = parent.strip_prefix(&self.root) else {
+            return false;
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        let mut dir = self.root.clone();
+        let mut ignored = false;
+        for component in std::iter::once(None).chain(below_root.components().map(Some)) {
+            if let Some(component) = component {
+                dir.push(component);
+            }
+            if !self.rules.contains_key(&dir) {
+                let rules = self.read(&dir);
+                self.rules.insert(dir.clone(), rules);
+            }
+
+            let relative = path
+                .strip_prefix(&dir)
+                .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_default();
+            for rule in &self.rules[&dir] {
+                if rule.matches(&relative, &name, is_dir) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        ignored
+    }
+
+    /// Whether the rules of a directory are known yet
+    pub fn contains(&self, dir: &Path) -> bool {
+        self.rules.contains_key(dir)
+    }
+
+    /// Use the rules of a directory that were read beforehand; recording its file and
+    /// warnings is left to the caller
+    pub fn insert(&mut self, dir: PathBuf, rules: DirectoryRules) {
+        self.rules.insert(dir, rules.rules);
+    }
+
+    /// Read the ignore file of a directory, if it has one
+    fn read(&mut self, dir: &Path) -> Vec<Rule> {
+        let mut rules = DirectoryRules::read(dir);
+        self.files.extend(rules.file.take());
+        self.warnings.append(&mut rules.warnings);
+        rules.rules
+    }
+}
+
+/// The ignore file of one directory, read without the shared IgnoreRules (a parallel
+/// scan reads the files of several directories at once)
+pub struct DirectoryRules {
+    rules: Vec<Rule>,
+    /// The ignore file with its valid patterns, if the directory has one
+    pub file: Option<IgnoreFile>,
+    /// Problems with the file
+    pub warnings: Vec<String>,
+}
+
+impl DirectoryRules {
+    /// The rules of a directory known to have no ignore file
+    pub fn none() -> Self {
+        DirectoryRules {
+            rules: Vec::new(),
+            file: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Read the ignore file of a directory, if it has one
+    pub fn read(dir: &Path) -> Self {
+        let mut read = DirectoryRules::none();
+        let path = dir.join(IGNORE_FILE_NAME);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return read,
+            Err(e) => {
+                read.warnings
+                    .push(format!("{}: could not be read: {}", path.display(), e));
+                return read;
+            }
+        };
+
+        let mut patterns = Vec::new();
+        for (idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match parse_rule(line) {
+                Ok(rule) => {
+                    read.rules.push(rule);
+                    patterns.push(line.to_string());
+                }
+                Err(reason) => read.warnings.push(format!(
+                    "{}:{}: ignoring `{}`: {}",
+                    path.display(),
+                    idx + 1,
+                    line,
+                    reason
+                )),
+            }
+        }
+        read.file = Some(IgnoreFile { path, patterns });
+        read
+    }
+}
+
+/// Parse one line of an ignore file (not blank, not a comment)
+/// A leading `\` escapes a `#` or `!` that is part of the pattern
+fn parse_rule(line: &str) -> Result<Rule, &'static str> {
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let pattern = pattern.strip_prefix('\\').unwrap_or(pattern);
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+    if pattern.is_empty() {
+        return Err("empty pattern");
+    }
+    if pattern.contains("//") {
+        return Err("empty path component");
+    }
+    if pattern.contains("***") {
+        return Err("`***` is not a valid wildcard (use `*` or `**`)");
+    }
+    if pattern.contains('[') || pattern.contains(']') {
+        return Err("character classes ([...]) are not supported");
+    }
+
+    Ok(Rule {
+        pattern: pattern.to_string(),
+        negated,
+        dir_only,
+        anchored,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_dir;
+
+    #[test]
+    fn rules_follow_gitignore_precedence() {
+        let root = test_dir("ignore");
+        fs::create_dir_all(root.join("A/live")).unwrap();
+        fs::write(
+            root.join(IGNORE_FILE_NAME),
+            "# scratch files\n\
+             *.tmp.flac\n\
+             !keep.tmp.flac\n\
+             skip/\n\
+             /live/*.flac\n\
+             \\#hash.flac\n\
+             \\!bang.flac\n\
+             ***\n\
+             [ab].flac\n\
+             a//b\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("A").join(IGNORE_FILE_NAME),
+            "!*.tmp.flac\ndeep.flac\n",
+        )
+        .unwrap();
+
+        let mut rules = IgnoreRules::new(&root);
+        let mut ignored = |path: &str, is_dir: bool| rules.is_ignored(&root.join(path), is_dir);
+
+        // Names match at any depth, and a deeper file's negation wins
+        assert!(ignored("x.tmp.flac", false));
+        assert!(ignored("B/x.tmp.flac", false));
+        assert!(!ignored("A/x.tmp.flac", false));
+        // The last matching rule of a file decides
+        assert!(!ignored("keep.tmp.flac", false));
+        // `dir/` only matches directories
+        assert!(ignored("skip", true));
+        assert!(ignored("A/skip", true));
+        assert!(!ignored("skip", false));
+        // Patterns with a `/` are relative to their file's directory
+        assert!(ignored("live/one.flac", false));
+        assert!(!ignored("A/live/one.flac", false));
+        assert!(!ignored("live/sub/one.flac", false));
+        // Escaped `#` and `!` are part of the pattern
+        assert!(ignored("#hash.flac", false));
+        assert!(ignored("!bang.flac", false));
+        assert!(!ignored("bang.flac", false));
+        // Rules only apply below their file
+        assert!(ignored("A/deep.flac", false));
+        assert!(!ignored("deep.flac", false));
+
+        let root_file = root.join(IGNORE_FILE_NAME);
+        assert_eq!(
+            rules.warnings,
+            [
+                format!(
+                    "{}:8: ignoring `***`: `***` is not a valid wildcard (use `*` or `**`)",
+                    root_file.display()
+                ),
+                format!(
+                    "{}:9: ignoring `[ab].flac`: character classes ([...]) are not supported",
+                    root_file.display()
+                ),
+                format!(
+                    "{}:10: ignoring `a//b`: empty path component",
+                    root_file.display()
+                ),
+            ]
+        );
+        let read: Vec<(PathBuf, usize)> = rules
+            .files
+            .iter()
+            .map(|file| (file.path.clone(), file.patterns.len()))
+            .collect();
+        assert_eq!(
+            read,
+            [(root_file, 6), (root.join("A").join(IGNORE_FILE_NAME), 2)]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Lock file held by this process, so a forced exit (second Ctrl-C) can still remove it
+static HELD_LOCK: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Advisory lock that keeps two check runs from working on the same job file
+/// The lock file (e.g. job.json -> job.json.lock) contains the PID of its owner and
+/// is removed when the guard is dropped, including when unwinding from a panic
+pub struct JobLock {
+    path: PathBuf,
+}
+
+impl JobLock {
+    /// Take the lock for a job file, refusing if another live process holds it
+    /// With `force`, a lock left behind by a process that no longer exists is taken over
+    pub fn acquire(job_file: &Path, force: bool) -> Result<Self> {
+        let mut lock_name = job_file.file_name().unwrap_or_default().to_os_string();
+        lock_name.push(".lock");
+        let path = job_file.with_file_name(lock_name);
+
+        // Two attempts: the second one after removing a stale lock
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    writeln!(file, "{}", std::process::id())
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    *HELD_LOCK.lock().unwrap() = Some(path.clone());
+                    return Ok(JobLock { path });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to create {}", path.display()));
+                }
+            }
+
+            // Someone else got there first; find out whether they are still around
+            let owner = fs::read_to_string(&path)
+                .ok()
+                .and_then(|contents| contents.trim().parse::<u32>().ok());
+
+            match owner {
+                Some(pid) if process_alive(pid) => {
+                    anyhow::bail!(
+                        "Job file {} is in use by another checkflac process (PID {}).\n  \
+                         If that is not the case, delete {}",
+                        job_file.display(),
+                        pid,
+                        path.display()
+                    );
+                }
+                _ if !force => {
+                    let holder = owner
+                        .map(|pid| format!("process {} which is no longer running", pid))
+                        .unwrap_or_else(|| "an unknown process".to_string());
+                    anyhow::bail!(
+                        "Job file {} is locked by {} ({}).\n  Use --force to take over the stale lock",
+                        job_file.display(),
+                        holder,
+                        path.display()
+                    );
+                }
+                _ => {
+                    println!(
+                        "{} Removing stale lock file {}",
+                        "⚠".yellow().bold(),
+                        path.display()
+                    );
+                    fs::remove_file(&path)
+                        .or_else(|e| {
+                            if e.kind() == ErrorKind::NotFound {
+                                Ok(())
+                            } else {
+                                Err(e)
+                            }
+                        })
+                        .with_context(|| format!("Failed to remove {}", path.display()))?;
+                }
+            }
+        }
+
+        anyhow::bail!(
+            "Job file {} was locked again by another process while taking over the lock",
+            job_file.display()
+        )
+    }
+}
+
+impl Drop for JobLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        HELD_LOCK.lock().unwrap_or_else(|e| e.into_inner()).take();
+    }
+}
+
+/// Remove the lock file held by this process, for code paths that exit without
+/// unwinding (such as a forced quit)
+pub fn release_held_lock() {
+    // Don't block (or panic) if the exit happens while the lock is being taken
+    if let Ok(mut held) = HELD_LOCK.try_lock()
+        && let Some(path) = held.take()
+    {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Whether a process with this PID currently exists
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Whether a process with this PID currently exists
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(true)
+}
+
+/// Whether a process with this PID currently exists
+/// There is no cheap way to tell here, so the lock is treated as stale and --force decides
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    false
+}
@@ -1,12 +1,34 @@
+use chrono::{DateTime, Utc};
 // Import the clap derive macros for parsing command-line arguments
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 
-// Declare the modules - Rust will look for explore.rs, types.rs, check.rs, and stats.rs
+// Declare the modules - Rust will look for album.rs, explore.rs, types.rs, check.rs, dump.rs, ffp.rs,
+// ignore.rs, lock.rs, metadata.rs, priority.rs, quarantine.rs, runlog.rs, sidecar.rs, stats.rs,
+// tags.rs, terminate.rs, update.rs, util.rs and verify.rs
+mod album;
 mod check;
+mod diff;
+mod dump;
 mod explore;
+mod ffp;
+mod ignore;
+mod lock;
+mod metadata;
+mod priority;
+mod quarantine;
+mod report;
+mod runlog;
+mod sidecar;
 mod stats;
+mod tags;
+mod terminate;
 mod types;
+mod update;
+mod util;
+mod verify;
 
 /// A tool for checking FLAC file integrity
 #[derive(Parser)]
@@ -17,37 +39,356 @@ struct Cli {
     command: Commands,
 }
 
+// Parsed once at startup, so the size of the Check variant doesn't matter
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
-    /// Explore a directory and create a job file with all FLAC files
+    /// Explore one or more directories and create a job file with all FLAC files
     Explore {
-        /// Directory to explore
-        #[arg(value_name = "DIR")]
-        directory: PathBuf,
+        /// Directories to explore (all go into the same job file), or `-` to read a list
+        /// of files from stdin
+        #[arg(value_name = "DIR", required_unless_present = "from_file")]
+        directories: Vec<PathBuf>,
 
-        /// Output job file path (defaults to auto-generated based on directory name)
+        /// Output job file path (defaults to auto-generated based on the directory names);
+        /// `-` writes the job file to stdout and everything else to stderr
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        /// Leave files and directories matching this glob out of the job (repeatable;
+        /// matched against the path below each DIR, or the name without a `/`)
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Leave out the files already in this job file, e.g. one for an overlapping
+        /// subtree (repeatable)
+        #[arg(long, value_name = "JOB_FILE")]
+        exclude_job: Vec<PathBuf>,
+
+        /// Follow symbolic links to files and directories (links back up the tree are
+        /// not walked again, and a file reached by several paths is listed once)
+        #[arg(long)]
+        follow_symlinks: bool,
+
+        /// Descend at most this many levels below each DIR (1 = only the files directly
+        /// in DIR)
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+
+        /// Leave out files smaller than this (e.g. 500K), such as stubs from aborted encodes
+        #[arg(long, value_name = "SIZE", value_parser = util::parse_size)]
+        min_size: Option<u64>,
+
+        /// Leave out files larger than this (e.g. 2G), such as long vinyl transfers
+        #[arg(long, value_name = "SIZE", value_parser = util::parse_size)]
+        max_size: Option<u64>,
+
+        /// Only take files modified after this time: an RFC 3339 timestamp
+        /// (2024-05-01T18:00:00Z) or a duration back from now (7d)
+        #[arg(long, value_name = "WHEN", value_parser = util::parse_time_reference)]
+        modified_since: Option<DateTime<Utc>>,
+
+        /// Only take files modified after this file was, e.g. the job file of the last run
+        #[arg(long, value_name = "PATH", conflicts_with = "modified_since")]
+        modified_since_file: Option<PathBuf>,
+
+        /// List the files left out by --min-size and --max-size
+        #[arg(long)]
+        show_skipped: bool,
+
+        /// File extensions to pick up, comma-separated and case-insensitive (e.g.
+        /// flac,fla,oga)
+        #[arg(long, value_name = "LIST", value_delimiter = ',', default_value = "flac")]
+        extensions: Vec<String>,
+
+        /// Take the files listed in this file (one path per line, `#` for comments)
+        /// instead of scanning directories
+        #[arg(long, value_name = "LIST_FILE", conflicts_with = "directories")]
+        from_file: Option<PathBuf>,
+
+        /// Root directory of the listed files (repeatable; defaults to the directory they
+        /// all share)
+        #[arg(long = "root", value_name = "DIR")]
+        roots: Vec<PathBuf>,
+
+        /// Store paths relative to the root directory, so the job file keeps working
+        /// with the library mounted elsewhere (see --root of check and stats)
+        #[arg(long)]
+        relative: bool,
+
+        /// Fail without writing a job file if any directory or file can't be read
+        /// (permission denied, unreadable mount, ...) instead of warning about it
+        #[arg(long)]
+        strict_scan: bool,
+
+        /// Keep the jobs in the order the directories were walked (or the list was
+        /// given) instead of sorting them by path
+        #[arg(long)]
+        no_sort: bool,
+
+        /// Also scan hidden files and directories (names starting with a dot, or with
+        /// the hidden attribute on Windows), which are skipped by default
+        #[arg(long)]
+        include_hidden: bool,
+
+        /// Don't read the first bytes of each file to mark files that aren't FLAC (e.g.
+        /// renamed MP3s) Bad right away, e.g. on a slow network filesystem
+        #[arg(long)]
+        no_probe: bool,
+
+        /// Give every hardlink of a file its own job, instead of checking the file once
+        /// and marking its other paths Duplicate (Unix only; elsewhere there is no
+        /// inode information to find them)
+        #[arg(long)]
+        no_dedupe_hardlinks: bool,
+
+        /// Read this many directories at once while scanning, which is much faster on
+        /// network filesystems (CIFS, NFS) where every directory listing is a round trip
+        #[arg(
+            long,
+            value_name = "N",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        scan_threads: Option<usize>,
+
+        /// Scan and filter as usual, but only show what the job file would contain
+        /// (file count, total size, files per directory) without writing it; exits with
+        /// 1 if no files would be included
+        #[arg(long)]
+        dry_run: bool,
+
+        /// With --dry-run, also list every file that would be included
+        #[arg(long, requires = "dry_run")]
+        list: bool,
+
+        /// Put only N randomly picked files in the job, for a spot check of a large
+        /// library (the job file records that it is a sample)
+        #[arg(long, value_name = "N", conflicts_with = "sample_percent")]
+        sample: Option<usize>,
+
+        /// Put a random P percent of the files in the job (e.g. 2.5)
+        #[arg(long, value_name = "P")]
+        sample_percent: Option<f64>,
+
+        /// Put the jobs in random order instead of sorting them by path, so runs that
+        /// only check part of the job (--limit) cover the library evenly
+        #[arg(long, conflicts_with = "no_sort")]
+        shuffle: bool,
+
+        /// Seed for --sample/--sample-percent/--shuffle, to pick the same files in the
+        /// same order again (shown by every explore that uses one)
+        #[arg(long, value_name = "SEED")]
+        seed: Option<u64>,
+
+        /// Read each file's STREAMINFO header (sample rate, channels, bit depth, length)
+        /// into the job; update reads it for the files it adds
+        #[arg(long)]
+        read_metadata: bool,
+
+        /// Fail without writing a job file if any paths differ only in letter case, as
+        /// they would collide on a case-insensitive filesystem (e.g. an exFAT copy)
+        #[arg(long)]
+        fail_on_case_collision: bool,
+
+        /// Replace the output file if it exists (it may hold check results)
+        #[arg(long, conflicts_with = "append")]
+        force: bool,
+
+        /// Add the newly found files to the existing output file, keeping its entries and
+        /// their statuses
+        #[arg(long, conflicts_with_all = ["sample", "sample_percent", "shuffle"])]
+        append: bool,
     },
     /// Check FLAC files from a job file
     Check {
-        /// Job file to process
+        /// Job file to process (`-` reads it from stdin, which needs --results)
         #[arg(value_name = "JOB_FILE")]
         job_file: PathBuf,
 
         /// Number of parallel threads (defaults to number of CPU cores)
-        #[arg(short, long)]
+        #[arg(
+            short,
+            long,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
         threads: Option<usize>,
 
+        /// Number of threads reading files from disk (defaults to the thread count);
+        /// use a small value like 1 or 2 on spinning disks to avoid seek thrashing
+        #[arg(long, value_name = "N")]
+        io_threads: Option<usize>,
+
+        /// Limit the memory held by files being checked (e.g. 2G): files wait for running
+        /// ones to finish rather than go over it, and one larger than the limit runs alone
+        #[arg(long, value_name = "SIZE", value_parser = util::parse_size)]
+        max_memory: Option<u64>,
+
         /// Continue checking even if errors occur
         #[arg(short, long)]
         continue_on_error: bool,
+
+        /// How often to save the job file: a file count (e.g. 50) or a duration (e.g. 30s)
+        /// (defaults to saving after every status change)
+        #[arg(long, value_name = "FILES|DURATION")]
+        save_interval: Option<check::SaveInterval>,
+
+        /// Give up on a single file after this many seconds and mark it as Error
+        #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: Option<u64>,
+
+        /// Only check files with these statuses, comma-separated (e.g. bad,error)
+        /// (defaults to to_be_checked,checking,error,skipped)
+        #[arg(long, value_name = "STATUS", value_delimiter = ',')]
+        status: Vec<types::FlacStatus>,
+
+        /// Also re-verify files previously marked OK
+        #[arg(long)]
+        recheck_ok: bool,
+
+        /// Also retry files previously found to be missing
+        #[arg(long)]
+        retry_missing: bool,
+
+        /// Re-test files claxon cannot decode with `flac -t` (optionally give the path
+        /// to the flac binary; defaults to `flac` on PATH)
+        #[arg(long, value_name = "FLAC_BINARY", num_args = 0..=1, default_missing_value = "flac")]
+        fallback_flac_binary: Option<PathBuf>,
+
+        /// Stop after the first bad or errored file (in-flight files still finish)
+        #[arg(long)]
+        fail_fast: bool,
+
+        /// Abort the run once this many files have finished with Error
+        #[arg(
+            long,
+            value_name = "N",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        max_errors: Option<usize>,
+
+        /// Check at most N files (never-checked and least recently checked first), then
+        /// save and exit so a large job can be verified a bit at a time
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        limit: Option<u64>,
+
+        /// Stop starting new files after this much wall-clock time (e.g. 90m, 2h); files
+        /// already being checked are finished and recorded
+        #[arg(long, value_name = "DURATION", value_parser = util::parse_duration)]
+        max_duration: Option<Duration>,
+
+        /// Order to check the files in: path (album by album), size (largest first),
+        /// random or status-priority (interrupted and new files first)
+        #[arg(long, value_name = "ORDER", default_value = "path")]
+        order: check::CheckOrder,
+
+        /// Seed for --order random, to check the files in the same order again
+        #[arg(long, value_name = "SEED")]
+        seed: Option<u64>,
+
+        /// Leave files matching this glob out of the run and mark them Skipped (repeatable;
+        /// matched against the path below the job's root, or the file name without a `/`)
+        #[arg(long, value_name = "GLOB")]
+        skip: Vec<String>,
+
+        /// List the files an interrupted earlier run left in CHECKING (they are always
+        /// re-verified, this only shows which ones)
+        #[arg(long)]
+        show_interrupted: bool,
+
+        /// Leave the job file untouched and write this run's results to a new file with
+        /// the same format (usable with stats, or as the job file of a later check)
+        #[arg(long, value_name = "PATH")]
+        results: Option<PathBuf>,
+
+        /// Take over a lock file left behind by a checkflac process that no longer exists
+        #[arg(long)]
+        force: bool,
+
+        /// Show one progress line per worker thread with its current file
+        #[arg(long)]
+        per_thread_progress: bool,
+
+        /// Run at the lowest CPU priority and idle I/O priority so other services aren't
+        /// starved (silently skipped where the platform doesn't support it)
+        #[arg(long)]
+        low_priority: bool,
+
+        /// Move files found to be Bad into this directory, keeping their path relative
+        /// to the job's root directory (Error files are never moved)
+        #[arg(long, value_name = "DIR")]
+        quarantine: Option<PathBuf>,
+
+        /// Write what decodes of damaged files (Bad, or failing with a decoding error)
+        /// to WAV files in this directory, keeping their path relative to the job's root
+        #[arg(long, value_name = "DIR")]
+        dump_bad: Option<PathBuf>,
+
+        /// Stop writing dumps once they add up to this size (e.g. 500M, 2G)
+        #[arg(long, value_name = "SIZE", default_value = "1G", value_parser = util::parse_size)]
+        dump_bad_max_size: u64,
+
+        /// Retry a file up to N times when reading it fails with a transient I/O error
+        /// (e.g. a network share dropping out); decoding errors are never retried
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        retries: u32,
+
+        /// Append a timestamped line per checked file, plus run start/end markers, to
+        /// this log file
+        #[arg(long, value_name = "PATH")]
+        log_file: Option<PathBuf>,
+
+        /// Also compare each file against .ffp/.md5 checksum files in its directory
+        /// and mark mismatches as Bad
+        #[arg(long)]
+        verify_sidecars: bool,
+
+        /// Also validate metadata blocks (Vorbis comments, embedded pictures) and record
+        /// problems as warnings
+        #[arg(long)]
+        check_metadata: bool,
+
+        /// Also check that every SEEKTABLE entry points at the frame it claims (reads one
+        /// frame header per seek point) and record bad entries as warnings
+        #[arg(long)]
+        check_seektable: bool,
+
+        /// Mark files whose STREAMINFO header has no MD5 signature as Bad instead of
+        /// accepting them because they decode
+        #[arg(long)]
+        require_md5: bool,
+
+        /// Also record a SHA-256 of each whole file and warn when it changed since the
+        /// last run while the audio still verifies (tag edits, damaged metadata)
+        #[arg(long)]
+        hash_file: bool,
+
+        /// Compute the MD5 on a separate thread while decoding for every file (by default
+        /// only files of 64 MB or more are pipelined)
+        #[arg(long)]
+        pipeline: bool,
+
+        /// Fast sanity pass: only check the fLaC marker, STREAMINFO, the metadata blocks
+        /// and the first audio frame, without decoding the audio. Files are recorded as
+        /// quick-checked, and the next check without --quick verifies them fully.
+        #[arg(long)]
+        quick: bool,
+
+        /// Mark files with warnings (e.g. ID3 tags wrapped around the FLAC stream) as
+        /// Bad instead of OK
+        #[arg(long)]
+        strict: bool,
+
+        /// Where the library is mounted on this machine, for a job file with relative
+        /// paths (explore --relative); the job file keeps its own root directory
+        #[arg(long, value_name = "DIR")]
+        root: Option<PathBuf>,
     },
     /// Show statistics and lists of files by status
     Stats {
-        /// Job file to analyze
-        #[arg(value_name = "JOB_FILE")]
-        job_file: PathBuf,
+        /// Job file to analyze (`-` reads it from stdin); several (or a glob such as
+        /// `shelf-*.json`) for a combined overview
+        #[arg(value_name = "JOB_FILE", required = true)]
+        job_files: Vec<PathBuf>,
 
         /// Show list of OK files
         #[arg(long)]
@@ -57,39 +398,564 @@ enum Commands {
         #[arg(long)]
         show_pending: bool,
 
+        /// Show list of files verified by decoding only, because their header has no MD5
+        #[arg(long)]
+        show_no_md5: bool,
+
+        /// Show list of files left out of the last check by --skip, with the pattern
+        #[arg(long)]
+        show_skipped: bool,
+
+        /// Show list of files that so far only had a quick check (check --quick)
+        #[arg(long)]
+        show_quick: bool,
+
+        /// Show list of hardlinked duplicates, with the file each one links to
+        #[arg(long)]
+        show_duplicates: bool,
+
         /// Show full paths instead of relative paths
         #[arg(long)]
         full_paths: bool,
+
+        /// Show when each listed file was last checked
+        #[arg(long)]
+        show_times: bool,
+
+        /// Exit with code 2 if the job file contains Bad files, or 3 if it contains
+        /// Error files (for gating CI on a job file without re-checking)
+        #[arg(long)]
+        check_exit_code: bool,
+
+        /// Where the library is mounted on this machine, for a job file with relative
+        /// paths (explore --relative)
+        #[arg(long, value_name = "DIR")]
+        root: Option<PathBuf>,
+
+        /// Output format: text, json for one machine-readable document, or csv for one
+        /// row per file (both on stdout unless --output is given)
+        #[arg(long, value_name = "FORMAT", default_value = "text")]
+        format: stats::StatsFormat,
+
+        /// Only list the files with these statuses, comma-separated (e.g. bad,error),
+        /// instead of the default lists; with --format csv or --paths-only, only their
+        /// rows (not allowed with --format json, which has every status)
+        #[arg(long, value_name = "STATUS", value_delimiter = ',')]
+        status: Vec<types::FlacStatus>,
+
+        /// Write the --format json or csv output (or --paths-only) to this file instead
+        /// of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Print only the paths of the files with the --status statuses (all files without
+        /// it), one per line with nothing else, for scripts
+        #[arg(long)]
+        paths_only: bool,
+
+        /// With --paths-only, end each path with a NUL byte instead of a newline (for
+        /// xargs -0 and rsync --from0 --files-from)
+        #[arg(short = '0', long)]
+        print0: bool,
+
+        /// Order of the listed files: path, status, size, checked-at or duration (length
+        /// of the audio); files without the value come last
+        #[arg(long, value_name = "KEY", default_value = "path")]
+        sort: stats::StatsSort,
+
+        /// Reverse the --sort order, e.g. --sort size --reverse for the largest first
+        #[arg(long)]
+        reverse: bool,
+
+        /// Also print a table of the directories with bad, error or pending files and
+        /// their counts per status, worst first
+        #[arg(long)]
+        group_by_dir: bool,
+
+        /// With --group-by-dir or --report, group by this many directory levels below the
+        /// root (2 = artist/album) instead of each file's own directory
+        #[arg(long, value_name = "N")]
+        depth: Option<usize>,
+
+        /// Instead of the file lists, print each error and bad reason (the file's path
+        /// and position details taken out) with how many files gave it and a few of them
+        #[arg(long)]
+        error_summary: bool,
+
+        /// Also write each listed status (bad, error, ... as picked by --status and the
+        /// --show-* flags) to DIR/<status>.txt, one path per line
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+
+        /// Replace list files that already exist in the --output-dir directory
+        #[arg(long, requires = "output_dir")]
+        force: bool,
+
+        /// Also write an extended M3U playlist (UTF-8) of the Bad files to this file, to
+        /// audition them in a player
+        #[arg(long, value_name = "FILE")]
+        playlist: Option<PathBuf>,
+
+        /// Write the playlist's paths relative to the playlist's directory instead of
+        /// absolute
+        #[arg(long, requires = "playlist")]
+        relative_playlist: bool,
+
+        /// Only take in the entries whose displayed path contains this text (ignoring
+        /// case): every list and count, e.g. --filter "Artists/Radiohead"
+        #[arg(long, value_name = "PATTERN")]
+        filter: Option<String>,
+
+        /// Treat the --filter pattern as a regular expression
+        #[arg(long, requires = "filter")]
+        regex: bool,
+
+        /// Also write a report for sharing: summary, success rate, problem directories
+        /// and the Bad and Error files, as Markdown (.md) or a self-contained HTML page
+        /// (.html)
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+
+        /// Count the OK results last checked longer ago than this (e.g. 180d), or never,
+        /// as stale
+        #[arg(long, value_name = "DURATION", value_parser = util::parse_duration)]
+        stale: Option<Duration>,
+
+        /// Show list of the stale OK files
+        #[arg(long, requires = "stale")]
+        show_stale: bool,
+
+        /// Show the audio totals as exact byte counts and h:mm:ss
+        #[arg(long, overrides_with = "human")]
+        bytes: bool,
+
+        /// Show the audio totals in TB and hours (the default; overrides --bytes)
+        #[arg(long, overrides_with = "bytes")]
+        human: bool,
+
+        /// Fail unless the whole library is verified: exit with 2 if there are Bad files,
+        /// or 3 if there are Error, Missing or unchecked files (output is unchanged)
+        #[arg(long, conflicts_with = "check_exit_code")]
+        check: bool,
+
+        /// With --check, files still to be checked don't make it fail
+        #[arg(long, requires = "check")]
+        allow_pending: bool,
+
+        /// Also print how many files there are per sample format (16-bit / 44.1 kHz, ...)
+        /// and per channel layout, and how many of them are bad; files whose STREAMINFO
+        /// wasn't read are counted as unknown
+        #[arg(long)]
+        breakdown: bool,
+
+        /// Order of the --breakdown tables: format (lowest first) or count (most files
+        /// first)
+        #[arg(
+            long,
+            value_name = "KEY",
+            default_value = "format",
+            requires = "breakdown"
+        )]
+        breakdown_sort: stats::BreakdownSort,
+    },
+    /// Compare two job files of the same library: files that are new, removed or whose
+    /// status changed (exits with 2 when a file that was OK is now Bad)
+    Diff {
+        /// The earlier job file
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+
+        /// The later job file
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+
+        /// Output format: text, or json for one machine-readable document on stdout
+        #[arg(long, value_name = "FORMAT", default_value = "text")]
+        format: diff::DiffFormat,
+
+        /// Show full paths instead of relative paths
+        #[arg(long)]
+        full_paths: bool,
+
+        /// Where the library is mounted on this machine, for job files with relative
+        /// paths (explore --relative)
+        #[arg(long, value_name = "DIR")]
+        root: Option<PathBuf>,
+    },
+    /// Refresh a job file: add FLAC files that are new under its root directories and
+    /// mark the entries of files that are gone as Missing
+    Update {
+        /// Job file to update
+        #[arg(value_name = "JOB_FILE")]
+        job_file: PathBuf,
+
+        /// Remove the entries of files that no longer exist instead of marking them Missing
+        #[arg(long)]
+        prune: bool,
+
+        /// Don't read the first bytes of new files to mark files that aren't FLAC Bad
+        /// right away
+        #[arg(long)]
+        no_probe: bool,
+
+        /// Read this many directories at once while scanning, as for explore
+        #[arg(
+            long,
+            value_name = "N",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        scan_threads: Option<usize>,
+
+        /// Where the library is mounted on this machine, for a job file with relative
+        /// paths (explore --relative)
+        #[arg(long, value_name = "DIR")]
+        root: Option<PathBuf>,
+
+        /// Take over a lock file left behind by a checkflac process that no longer exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Verify FLAC files or directories directly, without a job file
+    Verify {
+        /// Files to verify, or directories to search for .flac files
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Number of parallel threads (defaults to number of CPU cores)
+        #[arg(
+            short,
+            long,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        threads: Option<usize>,
+    },
+    /// Write FLAC fingerprint (.ffp) files from the audio MD5s in a checked job file
+    ExportFfp {
+        /// Job file with checked files
+        #[arg(value_name = "JOB_FILE")]
+        job_file: PathBuf,
+
+        /// Write a single combined ffp file (paths relative to the job's root directories)
+        /// instead of one per album directory
+        #[arg(long, value_name = "PATH")]
+        single: Option<PathBuf>,
+
+        /// Overwrite existing ffp files
+        #[arg(long)]
+        force: bool,
     },
 }
 
-fn main() -> anyhow::Result<()> {
+/// Exit codes (see the readme):
+/// 0 everything OK, 2 Bad files, 3 Error files, 4 operational failure,
+/// 5 --max-errors reached, 130 interrupted, 143 terminated
+fn main() -> ExitCode {
     // Parse command-line arguments
-    let cli = Cli::parse();
+    // Usage errors are operational failures too (clap would otherwise exit with 2)
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            return if e.use_stderr() {
+                ExitCode::from(types::EXIT_OPERATIONAL)
+            } else {
+                // --help and --version
+                ExitCode::SUCCESS
+            };
+        }
+    };
 
+    match run(cli) {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(types::EXIT_OPERATIONAL)
+        }
+    }
+}
+
+/// Run the selected subcommand and return the process exit code
+fn run(cli: Cli) -> anyhow::Result<u8> {
     match cli.command {
-        Commands::Explore { directory, output } => {
+        Commands::Explore {
+            directories,
+            output,
+            exclude,
+            exclude_job,
+            follow_symlinks,
+            max_depth,
+            min_size,
+            max_size,
+            modified_since,
+            modified_since_file,
+            show_skipped,
+            extensions,
+            from_file,
+            roots,
+            relative,
+            strict_scan,
+            no_sort,
+            include_hidden,
+            no_probe,
+            no_dedupe_hardlinks,
+            scan_threads,
+            dry_run,
+            list,
+            sample,
+            sample_percent,
+            shuffle,
+            seed,
+            read_metadata,
+            fail_on_case_collision,
+            force,
+            append,
+        } => {
             // Run the explore command
-            explore::explore_directory(directory, output)?;
+            let options = explore::ExploreOptions {
+                output,
+                exclude,
+                exclude_jobs: exclude_job,
+                follow_symlinks,
+                max_depth,
+                min_size,
+                max_size,
+                modified_since,
+                modified_since_file,
+                show_skipped,
+                extensions,
+                from_file,
+                roots,
+                relative,
+                strict_scan,
+                no_sort,
+                include_hidden,
+                no_probe,
+                no_dedupe_hardlinks,
+                scan_threads,
+                dry_run,
+                list,
+                sample,
+                sample_percent,
+                shuffle,
+                seed,
+                read_metadata,
+                fail_on_case_collision,
+                force,
+                append,
+            };
+            return explore::explore_directories(directories, options);
         }
         Commands::Check {
             job_file,
             threads,
+            io_threads,
+            max_memory,
             continue_on_error,
+            save_interval,
+            timeout,
+            status,
+            recheck_ok,
+            retry_missing,
+            fallback_flac_binary,
+            fail_fast,
+            max_errors,
+            limit,
+            max_duration,
+            order,
+            seed,
+            skip,
+            show_interrupted,
+            results,
+            force,
+            per_thread_progress,
+            low_priority,
+            quarantine,
+            dump_bad,
+            dump_bad_max_size,
+            retries,
+            log_file,
+            verify_sidecars,
+            check_metadata,
+            check_seektable,
+            require_md5,
+            hash_file,
+            pipeline,
+            quick,
+            strict,
+            root,
         } => {
             // Run the check command
-            check::check_flac_files(job_file, threads, continue_on_error)?;
+            let options = check::CheckOptions {
+                threads,
+                io_threads,
+                continue_on_error,
+                save_interval,
+                timeout: timeout.map(Duration::from_secs),
+                statuses: status,
+                recheck_ok,
+                retry_missing,
+                fail_fast,
+                max_errors,
+                limit: limit.map(|n| n as usize),
+                max_duration,
+                order,
+                seed,
+                skip,
+                show_interrupted,
+                results,
+                force,
+                per_thread_progress,
+                low_priority,
+                quarantine,
+                dump_bad,
+                dump_bad_max_size,
+                retries,
+                log_file,
+                verify_sidecars,
+                strict,
+                require_md5,
+                hash_file,
+                max_memory,
+                root,
+                verify: check::VerifySettings {
+                    fallback_flac: fallback_flac_binary,
+                    check_metadata,
+                    check_seektable,
+                    pipeline,
+                    quick,
+                },
+            };
+            return check::check_flac_files(job_file, options);
         }
         Commands::Stats {
-            job_file,
+            job_files,
             show_ok,
             show_pending,
+            show_no_md5,
+            show_skipped,
+            show_quick,
+            show_duplicates,
             full_paths,
+            show_times,
+            check_exit_code,
+            root,
+            format,
+            status,
+            output,
+            paths_only,
+            print0,
+            sort,
+            reverse,
+            group_by_dir,
+            depth,
+            error_summary,
+            output_dir,
+            force,
+            playlist,
+            relative_playlist,
+            filter,
+            regex,
+            report,
+            stale,
+            show_stale,
+            bytes,
+            human: _,
+            check,
+            allow_pending,
+            breakdown,
+            breakdown_sort,
         } => {
             // Run the stats command
-            stats::show_statistics(job_file, show_ok, show_pending, full_paths)?;
+            let options = stats::StatsOptions {
+                show_ok,
+                show_pending,
+                show_no_md5,
+                show_skipped,
+                show_quick,
+                show_duplicates,
+                full_paths,
+                show_times,
+                check_exit_code,
+                root,
+                format,
+                statuses: status,
+                output,
+                paths_only,
+                print0,
+                sort,
+                reverse,
+                group_by_dir,
+                depth,
+                error_summary,
+                output_dir,
+                force,
+                playlist,
+                relative_playlist,
+                filter: filter
+                    .map(|pattern| stats::PathFilter::new(&pattern, regex))
+                    .transpose()?,
+                report,
+                stale,
+                show_stale,
+                bytes,
+                check,
+                allow_pending,
+                breakdown,
+                breakdown_sort,
+            };
+            let mut job_files = stats::expand_job_files(job_files)?;
+            if job_files.len() > 1 {
+                return stats::show_combined_statistics(job_files, options);
+            }
+            return stats::show_statistics(job_files.remove(0), options);
+        }
+        Commands::Diff {
+            old,
+            new,
+            format,
+            full_paths,
+            root,
+        } => {
+            let options = diff::DiffOptions {
+                format,
+                full_paths,
+                root,
+            };
+            return diff::diff_job_files(old, new, options);
+        }
+        Commands::Update {
+            job_file,
+            prune,
+            no_probe,
+            scan_threads,
+            root,
+            force,
+        } => {
+            // Run the update command
+            let options = update::UpdateOptions {
+                prune,
+                no_probe,
+                scan_threads,
+                root,
+                force,
+            };
+            update::update_job_file(job_file, options)?;
+        }
+        Commands::Verify { paths, threads } => {
+            // Run the verify command
+            let options = verify::VerifyOptions { threads };
+            return verify::verify_paths(paths, options);
+        }
+        Commands::ExportFfp {
+            job_file,
+            single,
+            force,
+        } => {
+            // Run the export-ffp command
+            let options = ffp::ExportFfpOptions { single, force };
+            ffp::export_ffp(job_file, options)?;
         }
     }
 
-    Ok(())
+    Ok(0)
 }
\ No newline at end of file
@@ -0,0 +1,490 @@
+use crate::util::format_count;
+use indicatif::DecimalBytes;
+use std::collections::HashSet;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Metadata block types that are examined
+const STREAMINFO: u8 = 0;
+const SEEKTABLE: u8 = 3;
+const VORBIS_COMMENT: u8 = 4;
+const PICTURE: u8 = 6;
+
+/// Size of one SEEKTABLE entry: sample number, byte offset, samples in the frame
+const SEEK_POINT_SIZE: usize = 18;
+
+/// Sample number marking an unused SEEKTABLE entry
+const PLACEHOLDER_SEEK_POINT: u64 = u64::MAX;
+
+/// A block type reserved by the FLAC format, which decoders skip without looking inside
+const RESERVED_BLOCK_TYPE: u8 = 126;
+
+/// Which metadata checks to run
+#[derive(Debug, Clone, Copy)]
+pub struct MetadataChecks {
+    /// Validate VORBIS_COMMENT and PICTURE blocks (tags and embedded cover art)
+    pub tags: bool,
+    /// Check that every SEEKTABLE entry points at the frame it claims (reads one frame
+    /// header per seek point)
+    pub seektable: bool,
+}
+
+/// Problems found in the metadata blocks of a FLAC stream
+#[derive(Debug, Default)]
+pub struct MetadataReport {
+    /// One description per problem
+    pub warnings: Vec<String>,
+    /// Offsets (from the start of the stream) of the VORBIS_COMMENT block headers
+    pub vorbis_comment_headers: Vec<u64>,
+}
+
+/// Walk the metadata blocks of the stream between `start` and `end` and validate those
+/// the audio MD5 doesn't cover. A stream that doesn't start with "fLaC" is left for the
+/// decoder to reject.
+pub fn check_metadata<R: Read + Seek>(
+    input: &mut R,
+    start: u64,
+    end: u64,
+    checks: MetadataChecks,
+) -> io::Result<MetadataReport> {
+    let mut report = MetadataReport::default();
+
+    input.seek(SeekFrom::Start(start))?;
+    let mut marker = [0u8; 4];
+    if end < start + 4 || input.read_exact(&mut marker).is_err() || &marker != b"fLaC" {
+        return Ok(report);
+    }
+
+    // Block size of the stream, used to turn frame numbers into sample numbers
+    let mut block_size = 0;
+    let mut seek_points = None;
+    let mut pictures = 0;
+
+    let mut offset = start + 4;
+    loop {
+        let mut header = [0u8; 4];
+        if offset + 4 > end {
+            report
+                .warnings
+                .push("metadata ends before the last block was announced".to_string());
+            break;
+        }
+        input.read_exact(&mut header)?;
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let length = u64::from(u32::from_be_bytes([0, header[1], header[2], header[3]]));
+
+        if offset + 4 + length > end {
+            report.warnings.push(format!(
+                "{} block declares {} bytes but only {} remain in the file",
+                block_name(block_type),
+                format_count(length),
+                format_count(end - offset - 4)
+            ));
+            break;
+        }
+
+        if block_type == STREAMINFO && length >= 4 {
+            // In a fixed-blocksize stream every frame but the last has the maximum size
+            let mut sizes = [0u8; 4];
+            input.read_exact(&mut sizes)?;
+            block_size = u64::from(u16::from_be_bytes([sizes[2], sizes[3]]));
+            input.seek(SeekFrom::Current(length as i64 - 4))?;
+        } else if block_type == SEEKTABLE && checks.seektable {
+            let mut data = vec![0; length as usize];
+            input.read_exact(&mut data)?;
+            seek_points = Some(data);
+        } else if block_type == PICTURE && checks.tags {
+            pictures += 1;
+            let mut data = vec![0; length as usize];
+            input.read_exact(&mut data)?;
+            if let Some(problem) = picture_problem(&data) {
+                report
+                    .warnings
+                    .push(format!("PICTURE block {}: {}", pictures, problem));
+            }
+        } else if block_type == VORBIS_COMMENT && checks.tags {
+            if !report.vorbis_comment_headers.is_empty() {
+                report
+                    .warnings
+                    .push("more than one VORBIS_COMMENT block".to_string());
+            }
+            report.vorbis_comment_headers.push(offset - start);
+
+            let mut data = vec![0; length as usize];
+            input.read_exact(&mut data)?;
+            check_vorbis_comment(&data, &mut report.warnings);
+        } else {
+            input.seek(SeekFrom::Current(length as i64))?;
+        }
+
+        offset += 4 + length;
+        if is_last {
+            // The first frame follows the last metadata block; seek points count from it
+            if let Some(points) = &seek_points
+                && let Some(warning) = check_seek_points(input, points, offset, end, block_size)?
+            {
+                report.warnings.push(warning);
+            }
+            break;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Check that each seek point lands on a valid frame header that starts at the seek
+/// point's sample number. Returns a description if any of them don't.
+fn check_seek_points<R: Read + Seek>(
+    input: &mut R,
+    points: &[u8],
+    first_frame: u64,
+    end: u64,
+    block_size: u64,
+) -> io::Result<Option<String>> {
+    if !points.len().is_multiple_of(SEEK_POINT_SIZE) {
+        return Ok(Some(format!(
+            "SEEKTABLE: block length {} is not a multiple of {}",
+            format_count(points.len() as u64),
+            SEEK_POINT_SIZE
+        )));
+    }
+
+    let mut total = 0;
+    let mut invalid = Vec::new();
+
+    for (number, point) in points.chunks_exact(SEEK_POINT_SIZE).enumerate() {
+        let sample = u64::from_be_bytes(point[0..8].try_into().unwrap());
+        let frame_offset = u64::from_be_bytes(point[8..16].try_into().unwrap());
+        if sample == PLACEHOLDER_SEEK_POINT {
+            continue;
+        }
+        total += 1;
+
+        let position = first_frame.saturating_add(frame_offset);
+        let found = if position < end {
+            input.seek(SeekFrom::Start(position))?;
+            let mut header = [0u8; MAX_FRAME_HEADER_SIZE];
+            let available = (end - position).min(MAX_FRAME_HEADER_SIZE as u64) as usize;
+            input.read_exact(&mut header[..available])?;
+            frame_start_sample(&header[..available], block_size)
+        } else {
+            None
+        };
+        if found != Some(sample) {
+            invalid.push(number + 1);
+        }
+    }
+
+    Ok((!invalid.is_empty()).then(|| {
+        let listed: Vec<String> = invalid.iter().take(5).map(|n| n.to_string()).collect();
+        format!(
+            "SEEKTABLE: {} of {} seek points don't point at the frame they claim ({} {}{})",
+            invalid.len(),
+            total,
+            if invalid.len() == 1 {
+                "point"
+            } else {
+                "points"
+            },
+            listed.join(", "),
+            if invalid.len() > listed.len() {
+                ", ..."
+            } else {
+                ""
+            }
+        )
+    }))
+}
+
+/// Longest possible frame header: sync and codes (4), coded number (up to 7), block size
+/// (up to 2), sample rate (up to 2) and CRC-8 (1)
+const MAX_FRAME_HEADER_SIZE: usize = 16;
+
+/// Parse a frame header and return the number of the first sample in the frame, or None
+/// if the bytes aren't a valid frame header (bad sync code, reserved values or CRC)
+/// Frames in a fixed-blocksize stream carry a frame number instead, which is multiplied
+/// by the stream's block size
+fn frame_start_sample(header: &[u8], block_size: u64) -> Option<u64> {
+    // 14-bit sync code and a reserved zero bit, then the blocking strategy
+    if header.len() < 5 || header[0] != 0xff || header[1] & 0xfe != 0xf8 {
+        return None;
+    }
+    let variable_blocksize = header[1] & 0x01 != 0;
+    let block_size_code = header[2] >> 4;
+    let sample_rate_code = header[2] & 0x0f;
+    if block_size_code == 0 || sample_rate_code == 0x0f || header[3] & 0x01 != 0 {
+        return None;
+    }
+
+    // "UTF-8" style coded frame or sample number
+    let first = header[4];
+    let extra_bytes = match first.leading_ones() {
+        0 => 0,
+        ones @ 2..=7 => ones as usize - 1,
+        _ => return None,
+    };
+    let mut number = u64::from(first & (0x7f >> extra_bytes));
+    let mut at = 5;
+    for _ in 0..extra_bytes {
+        let byte = *header.get(at)?;
+        if byte & 0xc0 != 0x80 {
+            return None;
+        }
+        number = (number << 6) | u64::from(byte & 0x3f);
+        at += 1;
+    }
+
+    // Block size and sample rate stored at the end of the header
+    at += match block_size_code {
+        6 => 1,
+        7 => 2,
+        _ => 0,
+    };
+    at += match sample_rate_code {
+        12 => 1,
+        13 | 14 => 2,
+        _ => 0,
+    };
+    if *header.get(at)? != crc8(&header[..at]) {
+        return None;
+    }
+
+    if variable_blocksize {
+        Some(number)
+    } else {
+        Some(number * block_size)
+    }
+}
+
+/// CRC-8 of a frame header (polynomial x^8 + x^2 + x + 1)
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Validate a VORBIS_COMMENT block: lengths that run past the block, text that isn't
+/// UTF-8, field names that are empty or invalid, and entries that are repeated verbatim.
+/// (A field name may legitimately appear more than once, e.g. one ARTIST per artist.)
+fn check_vorbis_comment(data: &[u8], warnings: &mut Vec<String>) {
+    let mut warn = |message: String| warnings.push(format!("VORBIS_COMMENT: {}", message));
+    let mut rest = data;
+
+    // Every length in the block is a little-endian u32
+    let take_length = |rest: &mut &[u8]| -> Option<u64> {
+        let (bytes, tail) = rest.split_first_chunk::<4>()?;
+        *rest = tail;
+        Some(u64::from(u32::from_le_bytes(*bytes)))
+    };
+
+    let Some(vendor_length) = take_length(&mut rest) else {
+        warn("block is too short for a vendor string".to_string());
+        return;
+    };
+    if vendor_length > rest.len() as u64 {
+        warn(format!(
+            "vendor string declares {} bytes but only {} remain in the block",
+            format_count(vendor_length),
+            format_count(rest.len() as u64)
+        ));
+        return;
+    }
+    let (vendor, tail) = rest.split_at(vendor_length as usize);
+    rest = tail;
+    if std::str::from_utf8(vendor).is_err() {
+        warn("vendor string is not valid UTF-8".to_string());
+    }
+
+    let Some(count) = take_length(&mut rest) else {
+        warn("block ends before the comment count".to_string());
+        return;
+    };
+
+    let mut seen = HashSet::new();
+    for number in 1..=count {
+        let Some(length) = take_length(&mut rest) else {
+            warn(format!(
+                "block declares {} comments but ends after {}",
+                format_count(count),
+                format_count(number - 1)
+            ));
+            return;
+        };
+        if length > rest.len() as u64 {
+            warn(format!(
+                "comment {} declares {} bytes but only {} remain in the block",
+                number,
+                format_count(length),
+                format_count(rest.len() as u64)
+            ));
+            return;
+        }
+        let (comment, tail) = rest.split_at(length as usize);
+        rest = tail;
+
+        let Some(separator) = comment.iter().position(|&b| b == b'=') else {
+            warn(format!(
+                "comment {} has no '=' between name and value",
+                number
+            ));
+            continue;
+        };
+        let name = &comment[..separator];
+
+        // Field names are printable ASCII other than '=', and case-insensitive
+        if name.is_empty() {
+            warn(format!("comment {} has an empty field name", number));
+        } else if name.iter().any(|&b| !(0x20..=0x7d).contains(&b)) {
+            warn(format!("comment {} has an invalid field name", number));
+        }
+        let name = String::from_utf8_lossy(name).to_uppercase();
+
+        if std::str::from_utf8(&comment[separator + 1..]).is_err() {
+            warn(format!("comment {} ({}) is not valid UTF-8", number, name));
+        }
+        if !seen.insert((name.clone(), &comment[separator + 1..])) {
+            warn(format!(
+                "comment {} repeats {} with the same value",
+                number, name
+            ));
+        }
+    }
+}
+
+/// Validate a PICTURE block: every length must fit inside the block, the MIME type must
+/// be printable ASCII, and JPEG/PNG data must start with the signature of its MIME type
+fn picture_problem(data: &[u8]) -> Option<String> {
+    let too_long = |what: &str, (declared, present): (u64, u64)| {
+        format!(
+            "{} declared {} but only {} present",
+            what,
+            DecimalBytes(declared),
+            DecimalBytes(present)
+        )
+    };
+
+    // Picture type, then the MIME type and description, each preceded by its length
+    let Some(mut rest) = data.get(4..) else {
+        return Some("block is too short for a picture type".to_string());
+    };
+    let mime = match take_field(&mut rest) {
+        Ok(mime) => mime,
+        Err(lengths) => return Some(too_long("MIME type", lengths)),
+    };
+    if mime.is_empty() || mime.iter().any(|&b| !(0x20..=0x7e).contains(&b)) {
+        return Some("MIME type is empty or not printable ASCII".to_string());
+    }
+    let mime = String::from_utf8_lossy(mime).to_lowercase();
+    if let Err(lengths) = take_field(&mut rest) {
+        return Some(too_long("description", lengths));
+    }
+
+    // Width, height, colour depth and number of colours come before the picture data
+    let Some(mut rest) = rest.get(16..) else {
+        return Some("block ends before the picture data".to_string());
+    };
+    let image = match take_field(&mut rest) {
+        Ok(image) => image,
+        Err(lengths) => return Some(too_long("picture data", lengths)),
+    };
+
+    let detected = if image.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else {
+        None
+    };
+    let expected = match mime.as_str() {
+        "image/jpeg" | "image/jpg" => Some("image/jpeg"),
+        "image/png" => Some("image/png"),
+        _ => None,
+    };
+    match (expected, detected) {
+        (Some(expected), detected) if detected != Some(expected) => Some(format!(
+            "MIME type is {} but the data {}",
+            mime,
+            match detected {
+                Some(detected) => format!("is {}", detected),
+                None => "has no image signature".to_string(),
+            }
+        )),
+        // "-->" means the data is a URL rather than an image
+        (None, Some(detected)) if mime != "-->" => Some(format!(
+            "MIME type is {} but the data is {}",
+            mime, detected
+        )),
+        _ => None,
+    }
+}
+
+/// Split a field preceded by a big-endian u32 length off the front of `rest`
+/// Fails with the declared and available length if the field runs past the end
+fn take_field<'a>(rest: &mut &'a [u8]) -> Result<&'a [u8], (u64, u64)> {
+    let Some((length, tail)) = rest.split_first_chunk::<4>() else {
+        return Err((4, rest.len() as u64));
+    };
+    let length = u32::from_be_bytes(*length) as usize;
+    if length > tail.len() {
+        return Err((length as u64, tail.len() as u64));
+    }
+    let (field, tail) = tail.split_at(length);
+    *rest = tail;
+    Ok(field)
+}
+
+/// Name of a metadata block type, as used in the FLAC format specification
+fn block_name(block_type: u8) -> String {
+    match block_type {
+        0 => "STREAMINFO".to_string(),
+        1 => "PADDING".to_string(),
+        2 => "APPLICATION".to_string(),
+        3 => "SEEKTABLE".to_string(),
+        4 => "VORBIS_COMMENT".to_string(),
+        5 => "CUESHEET".to_string(),
+        6 => "PICTURE".to_string(),
+        other => format!("metadata block type {}", other),
+    }
+}
+
+/// A reader that changes the type of certain metadata blocks to a reserved one on the
+/// fly, so the decoder skips them. claxon refuses to open a file at all when its tags are
+/// malformed, which would otherwise stop the audio from being verified.
+pub struct HideBlocks<R> {
+    inner: R,
+    position: u64,
+    /// Offsets of the block headers to rewrite
+    headers: Vec<u64>,
+}
+
+impl<R: Read> HideBlocks<R> {
+    pub fn new(inner: R, headers: Vec<u64>) -> Self {
+        HideBlocks {
+            inner,
+            position: 0,
+            headers,
+        }
+    }
+}
+
+impl<R: Read> Read for HideBlocks<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let range = self.position..self.position + read as u64;
+        for &header in &self.headers {
+            if range.contains(&header) {
+                let byte = &mut buf[(header - self.position) as usize];
+                // Keep the "last metadata block" flag
+                *byte = (*byte & 0x80) | RESERVED_BLOCK_TYPE;
+            }
+        }
+        self.position = range.end;
+        Ok(read)
+    }
+}
@@ -0,0 +1,57 @@
+/// Lower the CPU and I/O priority of the calling thread (and of the threads it starts
+/// afterwards) so a long check doesn't get in the way of other services
+/// Returns a description of each change that took effect; anything the platform doesn't
+/// support is skipped without an error
+pub fn lower_priority() -> Vec<&'static str> {
+    [lower_cpu_priority(), lower_io_priority()]
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Lowest scheduling priority for nice(1)
+/// On Linux this only affects the calling thread, so the workers call it for themselves too
+#[cfg(unix)]
+fn lower_cpu_priority() -> Option<&'static str> {
+    // The type of the `which` argument differs between platforms
+    let done = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, 19) == 0 };
+    done.then_some("CPU nice 19")
+}
+
+/// Background mode lowers CPU, I/O and memory priority for the whole process at once
+#[cfg(windows)]
+fn lower_cpu_priority() -> Option<&'static str> {
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, PROCESS_MODE_BACKGROUND_BEGIN, SetPriorityClass,
+    };
+    let done = unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) != 0 };
+    done.then_some("background processing mode (low CPU and I/O priority)")
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lower_cpu_priority() -> Option<&'static str> {
+    None
+}
+
+/// ioprio_set(IOPRIO_WHO_PROCESS, 0, IOPRIO_CLASS_IDLE), what `ionice -c 3` does
+#[cfg(target_os = "linux")]
+fn lower_io_priority() -> Option<&'static str> {
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+    let done = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        ) == 0
+    };
+    done.then_some("idle I/O priority")
+}
+
+/// Windows gets it from background processing mode; elsewhere there is no I/O class to set
+#[cfg(not(target_os = "linux"))]
+fn lower_io_priority() -> Option<&'static str> {
+    None
+}
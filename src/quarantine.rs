@@ -0,0 +1,94 @@
+use crate::types::find_root;
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Move a bad file into the quarantine directory, keeping its path relative to the
+/// library root it lies under (files outside every root keep just their name)
+/// Returns the new location of the file
+pub fn quarantine_file(path: &Path, roots: &[PathBuf], quarantine_dir: &Path) -> Result<PathBuf> {
+    let relative = match find_root(roots, path) {
+        Some(root) => path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+        None => PathBuf::from(path.file_name().unwrap_or_default()),
+    };
+    let target = free_path(&quarantine_dir.join(relative));
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    match fs::rename(path, &target) {
+        Ok(()) => {}
+        // The quarantine directory is on another filesystem: copy, then delete
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => move_across_devices(path, &target)?,
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!(
+                    "Failed to move {} to {}",
+                    path.display(),
+                    target.display()
+                )
+            });
+        }
+    }
+
+    Ok(target)
+}
+
+/// Copy a file to its new location and remove the original once the copy is on disk
+fn move_across_devices(path: &Path, target: &Path) -> Result<()> {
+    let result = (|| -> Result<()> {
+        let copied = fs::copy(path, target)
+            .with_context(|| format!("Failed to copy {} to {}", path.display(), target.display()))?;
+        let expected = fs::metadata(path)?.len();
+        if copied != expected {
+            anyhow::bail!(
+                "Copy of {} is incomplete ({} of {} bytes)",
+                path.display(),
+                copied,
+                expected
+            );
+        }
+        File::open(target)
+            .and_then(|file| file.sync_all())
+            .with_context(|| format!("Failed to sync {}", target.display()))?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        // Leave the original where it was and don't keep a partial copy around
+        let _ = fs::remove_file(target);
+        return result;
+    }
+
+    fs::remove_file(path).with_context(|| {
+        format!(
+            "Copied to {} but failed to remove the original {}",
+            target.display(),
+            path.display()
+        )
+    })
+}
+
+/// Find a name that is not taken yet: "track.flac", then "track (1).flac", "track (2).flac", ...
+fn free_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| e.to_string_lossy());
+
+    (1..)
+        .map(|n| {
+            let name = match &extension {
+                Some(extension) => format!("{} ({}).{}", stem, n, extension),
+                None => format!("{} ({})", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
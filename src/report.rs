@@ -0,0 +1,408 @@
+use crate::stats::DirectoryCounts;
+use anyhow::Result;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Format of a `stats --report` file, from its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Markdown,
+    /// One self-contained page with inline CSS
+    Html,
+}
+
+impl ReportFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        match extension.as_str() {
+            "md" | "markdown" => Ok(ReportFormat::Markdown),
+            "html" | "htm" => Ok(ReportFormat::Html),
+            _ => anyhow::bail!(
+                "Can't tell the report format from {} (name it .md or .html)",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// What a report shows, gathered by stats from the job file
+pub struct Report {
+    /// The job file's name
+    pub title: String,
+    /// When the report was made, in local time
+    pub generated: String,
+    /// Label and value rows of the summary table (job file, root, totals, check times)
+    pub summary: Vec<(String, String)>,
+    /// Files per status, in the order of the status table
+    pub statuses: Vec<(&'static str, usize)>,
+    /// Share of the checked files that verified, and how many were checked
+    pub success_rate: Option<(f64, usize)>,
+    /// Directories with bad, error or pending files, worst first
+    pub directories: Vec<(String, DirectoryCounts)>,
+    /// Number of directories in the job
+    pub total_directories: usize,
+    /// The Bad and Error files
+    pub lists: Vec<FileList>,
+}
+
+/// One collapsible list of files
+pub struct FileList {
+    pub title: &'static str,
+    pub files: Vec<ReportFile>,
+}
+
+pub struct ReportFile {
+    pub path: String,
+    pub message: Option<String>,
+    /// Local time of the last check, if any
+    pub checked: Option<String>,
+}
+
+impl Report {
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.markdown(),
+            ReportFormat::Html => self.html(),
+        }
+    }
+
+    fn markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(
+            md,
+            "# FLAC verification report: {}\n",
+            md_escape(&self.title)
+        );
+        let _ = writeln!(md, "Generated {} by checkflac.\n", self.generated);
+
+        md.push_str("## Summary\n\n| | |\n| --- | --- |\n");
+        for (label, value) in &self.summary {
+            let _ = writeln!(md, "| {} | {} |", label, md_escape(value));
+        }
+
+        md.push_str("\n## Status\n\n");
+        if let Some((rate, checked)) = self.success_rate {
+            let _ = writeln!(
+                md,
+                "**{:.1}%** of the {} checked files verified successfully.\n",
+                rate, checked
+            );
+        }
+        md.push_str("| Status | Files |\n| --- | ---: |\n");
+        for (status, count) in &self.statuses {
+            let _ = writeln!(md, "| {} | {} |", status, count);
+        }
+
+        md.push_str("\n## Directories with problems\n\n");
+        if self.directories.is_empty() {
+            let _ = writeln!(
+                md,
+                "None of the {} directories has bad, error or pending files.",
+                self.total_directories
+            );
+        } else {
+            let _ = writeln!(
+                md,
+                "{} of {} directories, worst first.\n",
+                self.directories.len(),
+                self.total_directories
+            );
+            md.push_str("| Directory | Bad | Error | Pending | Warning | OK | Files |\n");
+            md.push_str("| --- | ---: | ---: | ---: | ---: | ---: | ---: |\n");
+            for (dir, counts) in &self.directories {
+                let _ = writeln!(
+                    md,
+                    "| {} | {} | {} | {} | {} | {} | {} |",
+                    md_escape(dir),
+                    counts.bad,
+                    counts.error,
+                    counts.pending,
+                    counts.warning,
+                    counts.ok,
+                    counts.total
+                );
+            }
+        }
+
+        for list in &self.lists {
+            let _ = writeln!(md, "\n## {} ({})\n", list.title, list.files.len());
+            if list.files.is_empty() {
+                md.push_str("None.\n");
+                continue;
+            }
+            // GitHub and most other renderers fold <details>, the rest show it open
+            md.push_str("<details>\n<summary>Show the list</summary>\n\n");
+            md.push_str("| File | Reason | Checked |\n| --- | --- | --- |\n");
+            for file in &list.files {
+                let _ = writeln!(
+                    md,
+                    "| {} | {} | {} |",
+                    md_escape(&file.path),
+                    md_escape(file.message.as_deref().unwrap_or("")),
+                    file.checked.as_deref().unwrap_or("never")
+                );
+            }
+            md.push_str("\n</details>\n");
+        }
+        md
+    }
+
+    fn html(&self) -> String {
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>FLAC verification report: {}</title>\n<style>\n{}</style>\n</head>\n<body>\n",
+            html_escape(&self.title),
+            HTML_STYLE
+        );
+        let _ = writeln!(
+            html,
+            "<h1>FLAC verification report: {}</h1>\n<p class=\"muted\">Generated {} by checkflac.</p>",
+            html_escape(&self.title),
+            self.generated
+        );
+
+        html.push_str("<h2>Summary</h2>\n<table>\n");
+        for (label, value) in &self.summary {
+            let _ = writeln!(
+                html,
+                "<tr><th>{}</th><td>{}</td></tr>",
+                label,
+                html_escape(value)
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Status</h2>\n");
+        if let Some((rate, checked)) = self.success_rate {
+            let _ = writeln!(
+                html,
+                "<div class=\"rate\"><span class=\"figure\">{:.1}%</span> of the {} checked files \
+                 verified successfully\n<div class=\"bar\"><div style=\"width: {:.1}%\"></div></div></div>",
+                rate, checked, rate
+            );
+        }
+        html.push_str("<table>\n<tr><th>Status</th><th class=\"num\">Files</th></tr>\n");
+        for (status, count) in &self.statuses {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"num\">{}</td></tr>",
+                status, count
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Directories with problems</h2>\n");
+        if self.directories.is_empty() {
+            let _ = writeln!(
+                html,
+                "<p>None of the {} directories has bad, error or pending files.</p>",
+                self.total_directories
+            );
+        } else {
+            let _ = writeln!(
+                html,
+                "<p>{} of {} directories, worst first.</p>",
+                self.directories.len(),
+                self.total_directories
+            );
+            html.push_str(
+                "<table>\n<tr><th>Directory</th><th class=\"num\">Bad</th>\
+                 <th class=\"num\">Error</th><th class=\"num\">Pending</th>\
+                 <th class=\"num\">Warning</th><th class=\"num\">OK</th>\
+                 <th class=\"num\">Files</th></tr>\n",
+            );
+            for (dir, counts) in &self.directories {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td class=\"num bad\">{}</td><td class=\"num error\">{}</td>\
+                     <td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+                     <td class=\"num\">{}</td></tr>",
+                    html_escape(dir),
+                    counts.bad,
+                    counts.error,
+                    counts.pending,
+                    counts.warning,
+                    counts.ok,
+                    counts.total
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        for list in &self.lists {
+            let _ = writeln!(
+                html,
+                "<h2>{} ({})</h2>",
+                html_escape(list.title),
+                list.files.len()
+            );
+            if list.files.is_empty() {
+                html.push_str("<p>None.</p>\n");
+                continue;
+            }
+            html.push_str(
+                "<details>\n<summary>Show the list</summary>\n<table>\n\
+                 <tr><th>File</th><th>Reason</th><th>Checked</th></tr>\n",
+            );
+            for file in &list.files {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td class=\"muted\">{}</td></tr>",
+                    html_escape(&file.path),
+                    html_escape(file.message.as_deref().unwrap_or("")),
+                    file.checked.as_deref().unwrap_or("never")
+                );
+            }
+            html.push_str("</table>\n</details>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+/// The page's only styling, so the file needs nothing else to display
+const HTML_STYLE: &str = "\
+body { font-family: system-ui, sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; color: #222; }
+h1 { font-size: 1.6em; }
+h2 { font-size: 1.25em; margin-top: 1.8em; border-bottom: 1px solid #ddd; }
+table { border-collapse: collapse; margin: 0.5em 0; }
+th, td { text-align: left; padding: 0.25em 0.8em; border-bottom: 1px solid #eee; vertical-align: top; }
+td { word-break: break-word; }
+.num { text-align: right; }
+.bad { color: #b00020; }
+.error { color: #a66a00; }
+.muted { color: #777; }
+.rate { margin: 0.5em 0 1em; }
+.figure { font-size: 2em; font-weight: bold; }
+.bar { background: #f1c9c9; height: 0.8em; width: 20em; margin-top: 0.3em; }
+.bar div { background: #3a8f3a; height: 100%; }
+summary { cursor: pointer; margin: 0.5em 0; }
+";
+
+/// Escape text for HTML element content and attribute values
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escape text for a Markdown table cell: characters with a meaning in Markdown are
+/// backslash-escaped, HTML is neutralized and line breaks become spaces
+fn md_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '|' | '*' | '_' | '`' | '[' | ']' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A report with a bit of everything, including text that needs escaping
+    fn report() -> Report {
+        Report {
+            title: "library.json".to_string(),
+            generated: "2026-10-14 09:30".to_string(),
+            summary: vec![
+                ("Job file".to_string(), "library.json".to_string()),
+                ("Root directory".to_string(), "/music".to_string()),
+                ("Files".to_string(), "4".to_string()),
+            ],
+            statuses: vec![
+                ("OK", 2),
+                ("Warning", 0),
+                ("Bad", 1),
+                ("Error", 1),
+                ("Missing", 0),
+                ("To be checked", 0),
+                ("Skipped", 0),
+            ],
+            success_rate: Some((50.0, 4)),
+            directories: vec![(
+                "Artist/Album".to_string(),
+                DirectoryCounts {
+                    bad: 1,
+                    error: 1,
+                    pending: 0,
+                    warning: 0,
+                    ok: 2,
+                    total: 4,
+                },
+            )],
+            total_directories: 2,
+            lists: vec![
+                FileList {
+                    title: "Bad files",
+                    files: vec![ReportFile {
+                        path: "Artist/Album/01 <Intro> | *Live*.flac".to_string(),
+                        message: Some("FLAC verification failed: MD5 mismatch".to_string()),
+                        checked: Some("2026-10-13 22:15".to_string()),
+                    }],
+                },
+                FileList {
+                    title: "Error files",
+                    files: vec![ReportFile {
+                        path: "Artist/Album/02 Rock & Roll.flac".to_string(),
+                        message: Some("Failed to open FLAC file".to_string()),
+                        checked: None,
+                    }],
+                },
+            ],
+        }
+    }
+
+    /// Compare with tests/snapshots/<name>, or rewrite it when UPDATE_SNAPSHOTS is set
+    fn assert_snapshot(name: &str, rendered: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/snapshots")
+            .join(name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, rendered).unwrap();
+            return;
+        }
+        let snapshot = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            rendered == snapshot,
+            "{} changed (run the tests with UPDATE_SNAPSHOTS=1 to accept the new output):\n{}",
+            name,
+            rendered
+        );
+    }
+
+    #[test]
+    fn html_matches_snapshot() {
+        assert_snapshot("report.html", &report().render(ReportFormat::Html));
+    }
+
+    #[test]
+    fn markdown_matches_snapshot() {
+        assert_snapshot("report.md", &report().render(ReportFormat::Markdown));
+    }
+}
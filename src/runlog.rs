@@ -0,0 +1,156 @@
+use crate::types::FlacJob;
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use colored::*;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Append-only log with one line per checked file, an audit trail of each run
+/// Lines are tab-separated and start with a UTC timestamp and a keyword:
+///   <time>  START  job=<job file>  files=<count>
+///   <time>  OK|WARNING|BAD|ERROR|MISSING  <duration ms>  <path>  [<message>]
+///   <time>  END  <outcome>  ok=<n>  warning=<n>  bad=<n>  error=<n>  missing=<n>  skipped=<n>  unchecked=<n>
+/// In paths and messages, a tab, newline or carriage return is written as `\t`, `\n` or
+/// `\r`, so every entry stays on one line with its columns (backslashes are left alone,
+/// they separate the directories of Windows paths)
+/// Every line is written with a single unbuffered write, so a crash leaves at most the
+/// line being written incomplete
+pub struct RunLog {
+    file: File,
+    /// Set after the first failed write so a full disk doesn't print a warning per file
+    failed: bool,
+}
+
+/// Counts reported on the END line of a run
+pub struct RunSummary<'a> {
+    /// "completed", "interrupted", "terminated" or "stopped"
+    pub outcome: &'a str,
+    pub ok: usize,
+    pub warnings: usize,
+    pub bad: usize,
+    pub errors: usize,
+    pub missing: usize,
+    pub skipped: usize,
+    pub unchecked: usize,
+}
+
+impl RunLog {
+    /// Open (or create) a log file for appending
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))?;
+        Ok(RunLog {
+            file,
+            failed: false,
+        })
+    }
+
+    /// Mark the start of a run
+    pub fn start(&mut self, job_file: &Path, files: usize) {
+        self.write(&[
+            "START",
+            &format!("job={}", escape(&job_file.display().to_string())),
+            &format!("files={}", files),
+        ]);
+    }
+
+    /// Record the result of a single file (missing files have no duration)
+    pub fn file(&mut self, job: &FlacJob, duration_ms: Option<u64>) {
+        let status = job.status.name().to_uppercase();
+        let duration = duration_ms
+            .map(|ms| ms.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let path = escape(&job.path.display().to_string());
+
+        let mut fields = vec![status.as_str(), duration.as_str(), path.as_str()];
+        // Keep every entry on one line, whatever is in the file name or the decoder put
+        // in the message
+        let message = job.error_message.as_deref().map(escape);
+        if let Some(message) = &message {
+            fields.push(message);
+        }
+        self.write(&fields);
+    }
+
+    /// Mark the end of a run with its summary counts
+    pub fn end(&mut self, summary: &RunSummary) {
+        self.write(&[
+            "END",
+            summary.outcome,
+            &format!("ok={}", summary.ok),
+            &format!("warning={}", summary.warnings),
+            &format!("bad={}", summary.bad),
+            &format!("error={}", summary.errors),
+            &format!("missing={}", summary.missing),
+            &format!("skipped={}", summary.skipped),
+            &format!("unchecked={}", summary.unchecked),
+        ]);
+    }
+
+    fn write(&mut self, fields: &[&str]) {
+        let mut line = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        for field in fields {
+            line.push('\t');
+            line.push_str(field);
+        }
+        line.push('\n');
+
+        if let Err(e) = self.file.write_all(line.as_bytes())
+            && !self.failed
+        {
+            self.failed = true;
+            eprintln!("{} Failed to write to log file: {}", "⚠".yellow().bold(), e);
+        }
+    }
+}
+
+/// A path or message as a log field: no tabs or line breaks (see `RunLog`)
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FlacStatus;
+    use crate::util::test_dir;
+
+    #[test]
+    fn odd_file_names_stay_on_one_line() {
+        let dir = test_dir("runlog");
+        let path = dir.join("check.log");
+        let mut job = FlacJob::new("/music/two\nlines\tand\r.flac".into());
+        job.status = FlacStatus::Bad;
+        job.error_message = Some("bad frame\n\tat 0:01".to_string());
+
+        let mut log = RunLog::open(&path).unwrap();
+        log.file(&job, Some(12));
+        drop(log);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let fields: Vec<&str> = contents.strip_suffix('\n').unwrap().split('\t').collect();
+        assert_eq!(
+            fields[1..],
+            [
+                "BAD",
+                "12",
+                "/music/two\\nlines\\tand\\r.flac",
+                "bad frame\\n\\tat 0:01"
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+use md5::{Digest, Md5};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A checksum for one FLAC file, as recorded in a sidecar file next to it
+#[derive(Debug, Clone)]
+pub struct SidecarEntry {
+    /// Recorded MD5 as lowercase hex
+    pub md5: String,
+    /// The sidecar file the entry came from
+    pub sidecar: PathBuf,
+}
+
+/// Checksums from the *.ffp and *.md5 files of each album directory, parsed the first
+/// time a file in that directory is checked and shared between worker threads
+pub struct SidecarCache {
+    /// Directory -> (lowercased file path -> entry)
+    dirs: Mutex<HashMap<PathBuf, Arc<HashMap<String, SidecarEntry>>>>,
+}
+
+impl SidecarCache {
+    pub fn new() -> Self {
+        SidecarCache {
+            dirs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Find the sidecar entry for a file, if its directory has one
+    /// Malformed sidecar lines are skipped and reported through `warn`
+    pub fn lookup(&self, path: &Path, warn: impl Fn(String)) -> Option<SidecarEntry> {
+        let dir = path.parent()?;
+
+        // Parsing happens under the lock so each directory is only read once
+        let entries = {
+            let mut dirs = self.dirs.lock().unwrap();
+            dirs.entry(dir.to_path_buf())
+                .or_insert_with(|| Arc::new(read_sidecars(dir, &warn)))
+                .clone()
+        };
+
+        entries.get(&entry_key(path)).cloned()
+    }
+}
+
+/// Check a verified file against its sidecar entry
+/// FLAC fingerprints (.ffp) are the STREAMINFO MD5, which is also what many tools put
+/// in .md5 files; others use plain md5sum of the whole file, so that is tried last
+/// Returns a reason if the recorded checksum matches none of them
+pub fn sidecar_mismatch(
+    entry: &SidecarEntry,
+    path: &Path,
+    header_md5: Option<&str>,
+    audio_md5: Option<&str>,
+) -> Option<String> {
+    if header_md5 == Some(entry.md5.as_str()) || audio_md5 == Some(entry.md5.as_str()) {
+        return None;
+    }
+
+    let is_md5sum = entry
+        .sidecar
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("md5"));
+    if is_md5sum && file_md5(path).ok().as_deref() == Some(entry.md5.as_str()) {
+        return None;
+    }
+
+    let sidecar_name = entry
+        .sidecar
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    Some(match header_md5.or(audio_md5) {
+        Some(actual) => format!(
+            "MD5 does not match {} (recorded {}, file has {})",
+            sidecar_name, entry.md5, actual
+        ),
+        None => format!(
+            "MD5 does not match {} (recorded {})",
+            sidecar_name, entry.md5
+        ),
+    })
+}
+
+/// Parse every *.ffp and *.md5 file in a directory
+fn read_sidecars(dir: &Path, warn: &impl Fn(String)) -> HashMap<String, SidecarEntry> {
+    let mut entries = HashMap::new();
+
+    // A directory that can't be listed simply has no sidecars
+    let Ok(listing) = fs::read_dir(dir) else {
+        return entries;
+    };
+
+    let mut sidecars: Vec<PathBuf> = listing
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path.extension().is_some_and(|e| {
+                    e.eq_ignore_ascii_case("ffp") || e.eq_ignore_ascii_case("md5")
+                })
+        })
+        .collect();
+    sidecars.sort();
+
+    for sidecar in sidecars {
+        let contents = match fs::read(&sidecar) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn(format!("Failed to read {}: {}", sidecar.display(), e));
+                continue;
+            }
+        };
+        // Rippers on Windows may write these in a legacy code page
+        let contents = String::from_utf8_lossy(&contents);
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim().trim_start_matches('\u{feff}');
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_line(line) {
+                Some((name, md5)) => {
+                    let path = dir.join(name.replace('\\', "/"));
+                    entries.insert(
+                        entry_key(&path),
+                        SidecarEntry {
+                            md5,
+                            sidecar: sidecar.clone(),
+                        },
+                    );
+                }
+                None => warn(format!(
+                    "Skipping malformed line {} in {}",
+                    number + 1,
+                    sidecar.display()
+                )),
+            }
+        }
+    }
+
+    entries
+}
+
+/// Parse "path:md5" (ffp) or "md5  path" / "md5 *path" (md5sum) into (path, lowercase md5)
+fn parse_line(line: &str) -> Option<(&str, String)> {
+    // md5sum style: the checksum comes first
+    if let Some((md5, name)) = line.split_once(char::is_whitespace)
+        && is_md5_hex(md5)
+    {
+        let name = name.trim_start().trim_start_matches('*');
+        if !name.is_empty() {
+            return Some((name, md5.to_ascii_lowercase()));
+        }
+    }
+
+    // ffp style: the checksum comes after the last colon
+    if let Some((name, md5)) = line.rsplit_once(':')
+        && is_md5_hex(md5.trim())
+        && !name.trim().is_empty()
+    {
+        return Some((name.trim(), md5.trim().to_ascii_lowercase()));
+    }
+
+    None
+}
+
+fn is_md5_hex(s: &str) -> bool {
+    s.len() == 32 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Sidecars written on Windows don't agree with the file system on case
+fn entry_key(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+/// MD5 of a whole file, as lowercase hex
+fn file_md5(path: &Path) -> io::Result<String> {
+    let mut hasher = Md5::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(crate::util::to_hex(&hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_dir;
+    use std::cell::RefCell;
+
+    const MD5: &str = "0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn both_sidecar_formats_parse() {
+        let parsed = |line: String| parse_line(&line).map(|(name, md5)| (name.to_string(), md5));
+        let expected = |name: &str| Some((name.to_string(), MD5.to_string()));
+
+        assert_eq!(
+            parsed(format!("01 - Intro.flac:{}", MD5)),
+            expected("01 - Intro.flac")
+        );
+        assert_eq!(
+            parsed(format!("{}  01 - Intro.flac", MD5)),
+            expected("01 - Intro.flac")
+        );
+        assert_eq!(
+            parsed(format!("{} *01 - Intro.flac", MD5)),
+            expected("01 - Intro.flac")
+        );
+        assert_eq!(
+            parsed(format!("Act 1: Overture.flac:{}", MD5)),
+            expected("Act 1: Overture.flac")
+        );
+        assert_eq!(
+            parsed(format!("{}  Act 1: Overture.flac", MD5)),
+            expected("Act 1: Overture.flac")
+        );
+        assert_eq!(
+            parsed(format!("01.flac:{}", MD5.to_ascii_uppercase())),
+            expected("01.flac")
+        );
+
+        assert_eq!(parsed("01.flac".to_string()), None);
+        assert_eq!(parsed(format!("01.flac:{}", &MD5[1..])), None);
+        assert_eq!(parsed(format!(":{}", MD5)), None);
+        assert_eq!(parsed(MD5.to_string()), None);
+    }
+
+    #[test]
+    fn malformed_sidecar_lines_are_skipped_with_a_warning() {
+        let dir = test_dir("sidecar");
+        fs::write(
+            dir.join("album.ffp"),
+            format!("; written by a ripper\n01.flac:{}\nnot a checksum\n", MD5),
+        )
+        .unwrap();
+        fs::write(dir.join("album.md5"), format!("{} *02.flac\n", MD5)).unwrap();
+
+        let warnings = RefCell::new(Vec::new());
+        let entries = read_sidecars(&dir, &|warning| warnings.borrow_mut().push(warning));
+
+        let mut names: Vec<(String, &str)> = entries
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.md5.as_str()))
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                (entry_key(&dir.join("01.flac")), MD5),
+                (entry_key(&dir.join("02.flac")), MD5)
+            ]
+        );
+        assert_eq!(
+            warnings.into_inner(),
+            [format!(
+                "Skipping malformed line 3 in {}",
+                dir.join("album.ffp").display()
+            )]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,85 +1,641 @@
-use crate::types::{FlacStatus, JobFile};
+use crate::album::ALBUM_WARNING_PREFIX;
+use crate::types::{
+    FlacJob, FlacStatus, JobFile, Sample, Statistics, find_root, result_exit_code,
+    write_file_atomic,
+};
+use crate::report::{FileList, Report, ReportFile, ReportFormat};
+use crate::util::{format_count, format_play_time, glob_match, path_relative_to};
 use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
 use colored::*;
-use std::fs;
-use std::path::PathBuf;
+use indicatif::DecimalBytes;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Options controlling what the stats command shows
+pub struct StatsOptions {
+    /// Show list of OK files
+    pub show_ok: bool,
+    /// Show list of files to be checked
+    pub show_pending: bool,
+    /// Show list of files verified without an MD5 in their header
+    pub show_no_md5: bool,
+    /// Show list of files left out of the last run by --skip
+    pub show_skipped: bool,
+    /// Show list of files that only passed a --quick check
+    pub show_quick: bool,
+    /// Show list of hardlinked duplicates, with the file they link to
+    pub show_duplicates: bool,
+    /// Show full paths instead of relative paths
+    pub full_paths: bool,
+    /// Show when each listed file was last checked
+    pub show_times: bool,
+    /// Exit with EXIT_BAD/EXIT_ERROR when the job file contains Bad/Error files
+    pub check_exit_code: bool,
+    /// Where the library is mounted, for a job file with relative paths
+    pub root: Option<PathBuf>,
+    /// Colored text for people, or a JSON document or CSV table for scripts
+    pub format: StatsFormat,
+    /// Only list the files with these statuses (empty means the default lists); for CSV
+    /// and paths_only, only the rows of these files
+    pub statuses: Vec<FlacStatus>,
+    /// Write the JSON or CSV output to this file instead of stdout
+    pub output: Option<PathBuf>,
+    /// Print only the paths of the files (with the --status statuses), one per line
+    pub paths_only: bool,
+    /// With paths_only, end each path with a NUL byte instead of a newline
+    pub print0: bool,
+    /// Order of the files in the lists, the CSV rows and the paths
+    pub sort: StatsSort,
+    /// Reverse that order (files without the sort value still come last)
+    pub reverse: bool,
+    /// Also print a table of the directories with bad, error or pending files
+    pub group_by_dir: bool,
+    /// Group by this many directory levels below the root (None: each file's own
+    /// directory)
+    pub depth: Option<usize>,
+    /// Print how often each error and bad reason occurs instead of the file lists
+    pub error_summary: bool,
+    /// Also write each listed status to a text file in this directory, one path per line
+    pub output_dir: Option<PathBuf>,
+    /// Replace list files that already exist in output_dir
+    pub force: bool,
+    /// Also write an M3U playlist of the Bad files here
+    pub playlist: Option<PathBuf>,
+    /// Write the playlist's paths relative to its own directory
+    pub relative_playlist: bool,
+    /// Only take in the entries whose displayed path matches
+    pub filter: Option<PathFilter>,
+    /// Also write a Markdown or HTML report (by the extension) here
+    pub report: Option<PathBuf>,
+    /// OK results last checked longer ago than this (or never) are counted as stale
+    pub stale: Option<Duration>,
+    /// Show list of the stale OK files
+    pub show_stale: bool,
+    /// Show the audio totals as exact byte counts and h:mm:ss instead of TB and hours
+    pub bytes: bool,
+    /// Exit with EXIT_BAD/EXIT_ERROR unless every file is verified: Error, Missing and
+    /// (without allow_pending) unchecked files count as errors
+    pub check: bool,
+    /// With check, files still to be checked don't fail it
+    pub allow_pending: bool,
+    /// Also print the files per sample format and per channel layout, with their statuses
+    pub breakdown: bool,
+    /// Order of the breakdown tables
+    pub breakdown_sort: BreakdownSort,
+}
+
+impl StatsOptions {
+    /// The exit code for the statistics: 0 unless --check-exit-code or --check is given
+    fn exit_code(&self, statistics: &Statistics) -> u8 {
+        if self.check {
+            let pending = if self.allow_pending {
+                0
+            } else {
+                statistics.to_be_checked + statistics.checking
+            };
+            result_exit_code(
+                statistics.bad,
+                statistics.error + statistics.missing + pending,
+            )
+        } else if self.check_exit_code {
+            result_exit_code(statistics.bad, statistics.error)
+        } else {
+            0
+        }
+    }
+
+    /// Whether an OK result is older than --stale, or has no check time at all
+    fn is_stale(&self, job: &FlacJob) -> bool {
+        let Some(stale) = self.stale else {
+            return false;
+        };
+        job.status == FlacStatus::Ok
+            && job.checked_at.is_none_or(|checked_at| {
+                (Utc::now() - checked_at)
+                    .to_std()
+                    .is_ok_and(|age| age > stale)
+            })
+    }
+}
+
+/// A --stale threshold for the summaries, e.g. "180 days"
+fn describe_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    let (value, unit) = if seconds >= 86400 && seconds.is_multiple_of(86400) {
+        (seconds / 86400, "days")
+    } else if seconds >= 3600 && seconds.is_multiple_of(3600) {
+        (seconds / 3600, "hours")
+    } else if seconds >= 60 && seconds.is_multiple_of(60) {
+        (seconds / 60, "minutes")
+    } else {
+        (seconds, "seconds")
+    };
+    if value == 1 {
+        format!("1 {}", unit.trim_end_matches('s'))
+    } else {
+        format!("{} {}", format_count(value), unit)
+    }
+}
+
+/// Which entries `stats --filter` takes in, by the path as it is displayed (relative to
+/// the root unless --full-paths is given; the original place of a quarantined file)
+pub enum PathFilter {
+    /// Paths containing the text, ignoring case
+    Substring(String),
+    /// Paths the regex matches somewhere (--regex)
+    Regex(regex::Regex),
+}
+
+impl PathFilter {
+    pub fn new(pattern: &str, regex: bool) -> Result<Self> {
+        if regex {
+            let regex = regex::Regex::new(pattern)
+                .with_context(|| format!("Invalid --filter regex `{}`", pattern))?;
+            Ok(PathFilter::Regex(regex))
+        } else {
+            Ok(PathFilter::Substring(pattern.to_string()))
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        match self {
+            PathFilter::Substring(text) => path.to_lowercase().contains(&text.to_lowercase()),
+            PathFilter::Regex(regex) => regex.is_match(path),
+        }
+    }
+
+    /// The filter for the summaries, e.g. "`disc 2` (substring)"
+    fn describe(&self) -> String {
+        match self {
+            PathFilter::Substring(text) => format!("`{}` (substring)", text),
+            PathFilter::Regex(regex) => format!("`{}` (regex)", regex.as_str()),
+        }
+    }
+}
+
+/// Load a job file for stats, keeping only the entries --filter matches
+/// Returns it with the number of entries before filtering
+fn load_job_file(path: &Path, options: &StatsOptions) -> Result<(JobFile, usize)> {
+    let mut job_file = JobFile::load_with_root(path, options.root.as_deref())?;
+    let total = job_file.jobs.len();
+    if let Some(filter) = &options.filter {
+        let jobs = std::mem::take(&mut job_file.jobs);
+        job_file.jobs = jobs
+            .into_iter()
+            .filter(|job| {
+                let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+                let path = if options.full_paths {
+                    path.as_path()
+                } else {
+                    job_file.relative_path(path)
+                };
+                filter.matches(&path.display().to_string())
+            })
+            .collect();
+    }
+    Ok((job_file, total))
+}
+
+/// What the stats listings are sorted by; ties are broken by path
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum StatsSort {
+    #[default]
+    Path,
+    /// In the order of the status table (to be checked, checking, OK, ...)
+    Status,
+    /// File size recorded by explore
+    Size,
+    /// Time of the last check
+    CheckedAt,
+    /// Length of the audio, from STREAMINFO
+    Duration,
+}
+
+impl FromStr for StatsSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "path" => Ok(StatsSort::Path),
+            "status" => Ok(StatsSort::Status),
+            "size" => Ok(StatsSort::Size),
+            "checked-at" | "checked" | "time" => Ok(StatsSort::CheckedAt),
+            "duration" | "length" => Ok(StatsSort::Duration),
+            _ => Err(format!(
+                "unknown sort '{}' (expected path, status, size, checked-at or duration)",
+                s
+            )),
+        }
+    }
+}
+
+/// Order of the --breakdown tables
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BreakdownSort {
+    /// Lowest sample rate and bit depth (or fewest channels) first
+    #[default]
+    Format,
+    /// Most files first
+    Count,
+}
+
+impl FromStr for BreakdownSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "format" => Ok(BreakdownSort::Format),
+            "count" => Ok(BreakdownSort::Count),
+            _ => Err(format!(
+                "unknown breakdown sort '{}' (expected format or count)",
+                s
+            )),
+        }
+    }
+}
+
+/// The jobs in the --sort/--reverse order. Files without the value sorted by (no size
+/// recorded, never checked, STREAMINFO not read) come last either way, and ties go by
+/// path, so the order is the same on every run.
+fn sorted_jobs<'a>(job_file: &'a JobFile, options: &StatsOptions) -> Vec<&'a FlacJob> {
+    let mut jobs: Vec<&FlacJob> = job_file.jobs.iter().collect();
+    jobs.sort_by(|a, b| compare_jobs(a, b, options));
+    jobs
+}
+
+/// The --sort/--reverse order of two jobs (see `sorted_jobs`)
+fn compare_jobs(a: &FlacJob, b: &FlacJob, options: &StatsOptions) -> Ordering {
+    // Quarantined files are sorted (and listed) under their original place
+    fn path(job: &FlacJob) -> &Path {
+        job.quarantined_from.as_deref().unwrap_or(&job.path)
+    }
+    let rank = |status: &FlacStatus| FlacStatus::ALL.iter().position(|s| s == status);
+    let directed = |ordering: Ordering| {
+        if options.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    };
+
+    let by_value = match options.sort {
+        StatsSort::Path => Ordering::Equal,
+        StatsSort::Status => directed(rank(&a.status).cmp(&rank(&b.status))),
+        StatsSort::Size => compare_optional(a.size, b.size, options.reverse),
+        StatsSort::CheckedAt => compare_optional(a.checked_at, b.checked_at, options.reverse),
+        StatsSort::Duration => {
+            compare_optional(a.duration_seconds, b.duration_seconds, options.reverse)
+        }
+    };
+    let by_path = path(a).cmp(path(b));
+    by_value.then(if options.sort == StatsSort::Path {
+        directed(by_path)
+    } else {
+        by_path
+    })
+}
+
+/// Compare two optional sort values: missing ones last, the rest in the requested direction
+fn compare_optional<T: PartialOrd>(a: Option<T>, b: Option<T>, reverse: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if reverse => b.partial_cmp(&a).unwrap_or(Ordering::Equal),
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    }
+}
+
+impl StatsOptions {
+    /// Whether the text output lists the files with a status: Bad, Error, Warning and
+    /// Missing files by default, or those picked by --status; the --show-* flags add theirs
+    fn lists(&self, status: FlacStatus) -> bool {
+        let shown = match status {
+            FlacStatus::Ok => self.show_ok,
+            FlacStatus::ToBeChecked | FlacStatus::Checking => self.show_pending,
+            FlacStatus::Skipped => self.show_skipped,
+            FlacStatus::Duplicate => self.show_duplicates,
+            FlacStatus::Bad | FlacStatus::Error | FlacStatus::Warning | FlacStatus::Missing => {
+                self.statuses.is_empty()
+            }
+        };
+        shown || self.statuses.contains(&status)
+    }
+}
+
+/// Output of the stats command
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum StatsFormat {
+    #[default]
+    Text,
+    /// One JSON document on stdout (see `StatsReport`)
+    Json,
+    /// One row per job entry, for spreadsheets (see `CSV_COLUMNS`)
+    Csv,
+}
+
+impl FromStr for StatsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(StatsFormat::Text),
+            "json" => Ok(StatsFormat::Json),
+            "csv" => Ok(StatsFormat::Csv),
+            _ => Err(format!("unknown format '{}' (expected text, json or csv)", s)),
+        }
+    }
+}
+
+/// The document printed by `stats --format json`. Scripts rely on it, so fields keep their
+/// name and meaning; new ones may be added, and `schema_version` goes up only when an
+/// existing field changes or goes away.
+#[derive(Serialize)]
+struct StatsReport<'a> {
+    /// 1 for this layout
+    schema_version: u32,
+    /// The job file as given on the command line (`-` for stdin)
+    job_file: &'a Path,
+    root_directories: &'a [PathBuf],
+    total_files: usize,
+    /// Counts per status, recomputed from the entries
+    statistics: &'a Statistics,
+    /// Present for a job made by explore --sample
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample: Option<&'a Sample>,
+    /// Oldest and newest check of any file (null if nothing was checked)
+    oldest_check: Option<DateTime<Utc>>,
+    newest_check: Option<DateTime<Utc>>,
+    /// The entries by status name (the names of check --status, e.g. "to_be_checked");
+    /// every status has a key, in the --sort order, so an empty list means none
+    files: BTreeMap<&'static str, Vec<ReportEntry<'a>>>,
+    /// Present with --filter; total_files, statistics and files only cover the matches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filter: Option<ReportFilter>,
+}
+
+#[derive(Serialize)]
+struct ReportFilter {
+    pattern: String,
+    regex: bool,
+    matched: usize,
+    total: usize,
+}
+
+/// One file of the JSON report
+#[derive(Serialize)]
+struct ReportEntry<'a> {
+    /// Relative to its root directory unless --full-paths is given; the original place of
+    /// a quarantined file
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    warnings: &'a [String],
+    /// Null for files never checked
+    checked_at: Option<DateTime<Utc>>,
+    /// Size or modification time differed from the job at the last check
+    changed: bool,
+    /// Where --quarantine moved the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quarantined_to: Option<&'a Path>,
+    /// For a Duplicate: the path of the file it is a hardlink to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate_of: Option<String>,
+    /// For a Skipped file: the check --skip pattern that matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skip_pattern: Option<&'a str>,
+}
 
 /// Show statistics and lists of files by status from a job file
-pub fn show_statistics(
-    job_file_path: PathBuf,
-    show_ok: bool,
-    show_pending: bool,
-    full_paths: bool,
-) -> Result<()> {
+/// Returns the process exit code: 0, or with --check-exit-code EXIT_BAD/EXIT_ERROR
+/// depending on the statuses in the job file
+pub fn show_statistics(job_file_path: PathBuf, options: StatsOptions) -> Result<u8> {
+    if options.paths_only {
+        return print_paths(&job_file_path, &options);
+    }
+    if options.print0 {
+        anyhow::bail!(
+            "--print0 separates the paths of --paths-only, it can't be used with the table, JSON or CSV output"
+        );
+    }
+    match options.format {
+        StatsFormat::Text => {}
+        StatsFormat::Json => return print_json(&job_file_path, &options),
+        StatsFormat::Csv => return print_csv(&job_file_path, &options),
+    }
+    if options.output.is_some() {
+        anyhow::bail!("--output writes --format json or csv to a file, the text output goes to the terminal");
+    }
+
+    if options.depth.is_some() && !options.group_by_dir && options.report.is_none() {
+        anyhow::bail!("--depth sets the directories of --group-by-dir and --report");
+    }
+    let report_format = options
+        .report
+        .as_deref()
+        .map(ReportFormat::from_path)
+        .transpose()?;
+
     println!("{} Loading job file...", "→".blue().bold());
 
     // Read and parse the job file
-    let job_file_content = fs::read_to_string(&job_file_path)
-        .with_context(|| format!("Failed to read job file: {}", job_file_path.display()))?;
-
-    let mut job_file: JobFile = serde_json::from_str(&job_file_content)
-        .context("Failed to parse job file JSON")?;
+    let (mut job_file, total) = load_job_file(&job_file_path, &options)?;
 
     // Recalculate statistics from actual job statuses
     // (in case the JSON file's statistics are outdated)
     job_file.statistics = crate::types::Statistics::from_jobs(&job_file.jobs);
 
     // Print summary (same as explore command)
-    print_summary(&job_file);
+    print_summary(&job_file, &options, total);
+    print_audio_totals(&job_file, &options);
+    print_check_times(&job_file);
+    if options.breakdown {
+        print_breakdown(&job_file, &options);
+    }
+
+    if options.error_summary {
+        if options.output_dir.is_some() {
+            anyhow::bail!("--output-dir writes the file lists, which --error-summary leaves out");
+        }
+        if let Some(playlist) = &options.playlist {
+            write_playlist(&job_file, &options, playlist)?;
+        }
+        if let (Some(path), Some(format)) = (&options.report, report_format) {
+            write_report(&job_file_path, &job_file, &options, total, path, format)?;
+        }
+        return print_error_summary(&job_file, &options);
+    }
 
     // Collect files by status
     let mut bad_files = Vec::new();
     let mut error_files = Vec::new();
     let mut ok_files = Vec::new();
+    let mut warning_files = Vec::new();
     let mut pending_files = Vec::new();
+    let mut missing_files = Vec::new();
+    let mut skipped_files = Vec::new();
+    let mut duplicate_files = Vec::new();
+
+    for job in sorted_jobs(&job_file, &options) {
+        // Quarantined files are listed under their original place in the library
+        let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
 
-    for job in &job_file.jobs {
         // Get the path to display (full or relative to root)
-        let display_path = if full_paths {
-            job.path.display().to_string()
+        let display_path = if options.full_paths {
+            path.display().to_string()
         } else {
-            // Try to strip the root directory prefix
-            match job.path.strip_prefix(&job_file.root_directory) {
-                Ok(relative) => relative.display().to_string(),
-                Err(_) => job.path.display().to_string(),
-            }
+            // Try to strip the prefix of the root directory the file lies under
+            job_file.relative_path(path).display().to_string()
         };
 
         match job.status {
-            FlacStatus::Bad => bad_files.push((display_path, job.error_message.clone())),
-            FlacStatus::Error => error_files.push((display_path, job.error_message.clone())),
-            FlacStatus::Ok => ok_files.push(display_path),
-            FlacStatus::ToBeChecked | FlacStatus::Checking => pending_files.push(display_path),
+            FlacStatus::Bad => bad_files.push((display_path, job)),
+            FlacStatus::Error => error_files.push((display_path, job)),
+            FlacStatus::Ok => ok_files.push((display_path, job)),
+            FlacStatus::Warning => warning_files.push((display_path, job)),
+            FlacStatus::Missing => missing_files.push((display_path, job)),
+            FlacStatus::Skipped => skipped_files.push((display_path, job)),
+            FlacStatus::Duplicate => duplicate_files.push((display_path, job)),
+            FlacStatus::ToBeChecked | FlacStatus::Checking => {
+                pending_files.push((display_path, job))
+            }
         }
     }
 
-    // Print BAD files list (always shown)
-    if !bad_files.is_empty() {
+    // Print BAD files list (shown unless --status leaves it out)
+    if options.lists(FlacStatus::Bad) && !bad_files.is_empty() {
         println!("\n{}", "BAD Files (corrupted):".red().bold());
-        for (path, error_msg) in &bad_files {
-            println!("  {} {}", "✗".red(), path);
-            if let Some(msg) = error_msg {
+        for (path, job) in &bad_files {
+            println!("  {} {}{}", "✗".red(), path, checked_suffix(job, &options));
+            if let Some(msg) = &job.error_message {
                 println!("    {}: {}", "Reason".dimmed(), msg.dimmed());
             }
+            if job.quarantined_from.is_some() {
+                println!(
+                    "    {}: {}",
+                    "Quarantined".dimmed(),
+                    job.path.display().to_string().dimmed()
+                );
+            }
         }
     }
 
-    // Print ERROR files list (always shown)
-    if !error_files.is_empty() {
+    // Print ERROR files list (shown unless --status leaves it out)
+    if options.lists(FlacStatus::Error) && !error_files.is_empty() {
         println!("\n{}", "ERROR Files (could not check):".yellow().bold());
-        for (path, error_msg) in &error_files {
-            println!("  {} {}", "⚠".yellow(), path);
-            if let Some(msg) = error_msg {
+        for (path, job) in &error_files {
+            println!(
+                "  {} {}{}",
+                "⚠".yellow(),
+                path,
+                checked_suffix(job, &options)
+            );
+            if let Some(msg) = &job.error_message {
                 println!("    {}: {}", "Error".dimmed(), msg.dimmed());
             }
         }
     }
 
+    // Print WARNING files list with the findings (shown unless --status leaves it out)
+    if options.lists(FlacStatus::Warning) && !warning_files.is_empty() {
+        println!(
+            "\n{}",
+            "WARNING Files (verified, with non-fatal findings):"
+                .bright_yellow()
+                .bold()
+        );
+        for (path, job) in &warning_files {
+            println!(
+                "  {} {}{}",
+                "!".bright_yellow(),
+                path,
+                checked_suffix(job, &options)
+            );
+            for warning in &job.warnings {
+                println!("    {}: {}", "Warning".dimmed(), warning.dimmed());
+            }
+        }
+    }
+
+    // Print albums whose tracks don't agree on the audio format (with the warnings)
+    let mut albums: BTreeMap<&Path, Vec<(&OsStr, Vec<&str>)>> = BTreeMap::new();
+    for (path, job) in &warning_files {
+        let findings: Vec<&str> = job
+            .warnings
+            .iter()
+            .filter_map(|w| w.strip_prefix(ALBUM_WARNING_PREFIX))
+            .collect();
+        if !findings.is_empty() {
+            let path = Path::new(path);
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let name = path.file_name().unwrap_or_default();
+            albums.entry(dir).or_default().push((name, findings));
+        }
+    }
+    if options.lists(FlacStatus::Warning) && !albums.is_empty() {
+        println!(
+            "\n{}",
+            "Inconsistent Albums (tracks differ in format):".bright_yellow().bold()
+        );
+        for (dir, tracks) in &albums {
+            println!("  {} {}", "!".bright_yellow(), dir.display());
+            for (name, findings) in tracks {
+                println!(
+                    "    {}: {}",
+                    name.to_string_lossy(),
+                    findings.join(", ").dimmed()
+                );
+            }
+        }
+    }
+
+    // Print MISSING files list (shown unless --status leaves it out)
+    if options.lists(FlacStatus::Missing) && !missing_files.is_empty() {
+        println!("\n{}", "MISSING Files (no longer on disk):".blue().bold());
+        for (path, job) in &missing_files {
+            println!("  {} {}{}", "?".blue(), path, checked_suffix(job, &options));
+        }
+    }
+
+    // Print files whose size or modification time changed since explore (those with a
+    // listed status)
+    let changed_files: Vec<&(String, &FlacJob)> = bad_files
+        .iter()
+        .chain(&error_files)
+        .chain(&ok_files)
+        .chain(&warning_files)
+        .chain(&pending_files)
+        .filter(|(_, job)| job.changed && options.lists(job.status.clone()))
+        .collect();
+    if !changed_files.is_empty() {
+        println!(
+            "\n{}",
+            "Changed Files (modified on disk since the job was created):"
+                .magenta()
+                .bold()
+        );
+        for (path, job) in &changed_files {
+            println!("  {} {} [{}]", "≠".magenta(), path, job.status);
+        }
+    }
+
     // Print OK files list (optional)
-    if show_ok && !ok_files.is_empty() {
+    if options.lists(FlacStatus::Ok) && !ok_files.is_empty() {
         println!("\n{}", "OK Files (verified):".green().bold());
-        for path in &ok_files {
-            println!("  {} {}", "✓".green(), path);
+        for (path, job) in &ok_files {
+            println!(
+                "  {} {}{}",
+                "✓".green(),
+                path,
+                checked_suffix(job, &options)
+            );
         }
     } else if !ok_files.is_empty() {
         println!(
@@ -90,14 +646,166 @@ pub fn show_statistics(
         );
     }
 
+    // Print files that passed without an MD5 to compare against (optional)
+    let no_md5_files: Vec<&(String, &FlacJob)> = ok_files
+        .iter()
+        .chain(&warning_files)
+        .filter(|(_, job)| job.verified_without_md5())
+        .collect();
+    if options.show_no_md5 && !no_md5_files.is_empty() {
+        println!(
+            "\n{}",
+            "Files verified by decode only (no MD5 in header, re-encode to add one):"
+                .cyan()
+                .bold()
+        );
+        for (path, job) in &no_md5_files {
+            println!(
+                "  {} {}{}",
+                "-".cyan(),
+                path,
+                checked_suffix(job, &options)
+            );
+        }
+    } else if !no_md5_files.is_empty() {
+        println!(
+            "\n{} {} files without MD5 in header (use {} to list them)",
+            "→".blue(),
+            no_md5_files.len(),
+            "--show-no-md5".cyan()
+        );
+    }
+
+    // Print OK files whose last check is older than --stale (optional)
+    let stale_files: Vec<&(String, &FlacJob)> = ok_files
+        .iter()
+        .filter(|(_, job)| options.is_stale(job))
+        .collect();
+    if let Some(stale) = options.stale
+        && options.show_stale
+        && !stale_files.is_empty()
+    {
+        println!(
+            "\n{}",
+            format!(
+                "Stale OK Files (last checked more than {} ago, or never):",
+                describe_age(stale)
+            )
+            .yellow()
+            .bold()
+        );
+        for (path, job) in &stale_files {
+            println!(
+                "  {} {}{}",
+                "…".yellow(),
+                path,
+                checked_suffix(job, &options)
+            );
+        }
+    } else if !stale_files.is_empty() {
+        println!(
+            "\n{} {} stale OK files (use {} to list them)",
+            "→".blue(),
+            stale_files.len(),
+            "--show-stale".cyan()
+        );
+    }
+
+    // Print files that still need their audio verified after check --quick (optional)
+    let quick_files: Vec<&(String, &FlacJob)> = ok_files
+        .iter()
+        .chain(&warning_files)
+        .filter(|(_, job)| job.quick_only())
+        .collect();
+    if options.show_quick && !quick_files.is_empty() {
+        println!(
+            "\n{}",
+            "Quick-checked Files (headers only, run check without --quick to verify the audio):"
+                .cyan()
+                .bold()
+        );
+        for (path, job) in &quick_files {
+            println!(
+                "  {} {}{}",
+                "~".cyan(),
+                path,
+                checked_suffix(job, &options)
+            );
+        }
+    } else if !quick_files.is_empty() {
+        println!(
+            "\n{} {} files only had a quick check (use {} to list them)",
+            "→".blue(),
+            quick_files.len(),
+            "--show-quick".cyan()
+        );
+    }
+
+    // Print files left out by --skip (optional)
+    if options.lists(FlacStatus::Skipped) && !skipped_files.is_empty() {
+        println!("\n{}", "Skipped Files (excluded by --skip):".dimmed().bold());
+        for (path, job) in &skipped_files {
+            println!("  {} {}", "-".dimmed(), path);
+            if let Some(pattern) = &job.skip_pattern {
+                println!("    {}: {}", "Pattern".dimmed(), pattern.dimmed());
+            }
+        }
+    } else if !skipped_files.is_empty() {
+        println!(
+            "\n{} {} skipped files (use {} to list them)",
+            "→".blue(),
+            skipped_files.len(),
+            "--show-skipped".cyan()
+        );
+    }
+
+    // Print hardlinked duplicates (optional)
+    if options.lists(FlacStatus::Duplicate) && !duplicate_files.is_empty() {
+        println!(
+            "\n{}",
+            "Duplicate Files (hardlinks, checked through another path):"
+                .dimmed()
+                .bold()
+        );
+        for (path, job) in &duplicate_files {
+            println!("  {} {}", "=".dimmed(), path);
+            if let Some(original) = &job.duplicate_of {
+                let original = if options.full_paths {
+                    original.as_path()
+                } else {
+                    job_file.relative_path(original)
+                };
+                println!(
+                    "    {}: {}",
+                    "Same file as".dimmed(),
+                    original.display().to_string().dimmed()
+                );
+            }
+        }
+    } else if !duplicate_files.is_empty() {
+        println!(
+            "\n{} {} hardlinked duplicates (use {} to list them)",
+            "→".blue(),
+            duplicate_files.len(),
+            "--show-duplicates".cyan()
+        );
+    }
+
     // Print pending files list (optional)
-    if show_pending && !pending_files.is_empty() {
+    if (options.lists(FlacStatus::ToBeChecked) || options.lists(FlacStatus::Checking))
+        && !pending_files.is_empty()
+    {
         println!(
             "\n{}",
             "Pending Files (to be checked):".yellow().bold()
         );
-        for path in &pending_files {
-            println!("  {} {}", "○".yellow(), path);
+        for (path, job) in &pending_files {
+            println!(
+                "  {} {}{}",
+                "○".yellow(),
+                path,
+                checked_suffix(job, &options)
+            );
         }
     } else if !pending_files.is_empty() {
         println!(
@@ -108,11 +816,38 @@ pub fn show_statistics(
         );
     }
 
+    if options.group_by_dir {
+        print_directories(&job_file, &options);
+    }
+
+    if let Some(dir) = &options.output_dir {
+        write_lists(&job_file, &options, dir)?;
+    }
+    if let Some(playlist) = &options.playlist {
+        write_playlist(&job_file, &options, playlist)?;
+    }
+    if let (Some(path), Some(format)) = (&options.report, report_format) {
+        write_report(&job_file_path, &job_file, &options, total, path, format)?;
+    }
+
     // Summary message
     println!();
     if bad_files.is_empty() && error_files.is_empty() {
-        if pending_files.is_empty() {
-            println!("{} All files verified successfully!", "✓".green().bold());
+        if pending_files.is_empty() && quick_files.is_empty() {
+            match job_file.sample_note() {
+                Some(note) => println!(
+                    "{} All files of the sample verified successfully ({}, not the whole library)",
+                    "✓".green().bold(),
+                    note
+                ),
+                None => println!("{} All files verified successfully!", "✓".green().bold()),
+            }
+        } else if pending_files.is_empty() {
+            println!(
+                "{} No issues found in checked files. {} files still need a full check.",
+                "✓".green().bold(),
+                quick_files.len()
+            );
         } else {
             println!(
                 "{} No issues found in checked files. {} files pending.",
@@ -128,38 +863,1431 @@ pub fn show_statistics(
             error_files.len()
         );
     }
+    if !warning_files.is_empty() {
+        println!(
+            "{} {} files verified with warnings.",
+            "!".bright_yellow().bold(),
+            warning_files.len()
+        );
+    }
+    if !missing_files.is_empty() {
+        println!(
+            "{} {} files listed in the job are missing from disk.",
+            "?".blue().bold(),
+            missing_files.len()
+        );
+    }
+    if !job_file.scan_errors.is_empty() {
+        println!(
+            "{} {} paths could not be read when the job was created, files below them are not in the job:",
+            "⚠".yellow().bold(),
+            job_file.scan_errors.len()
+        );
+        for scan_error in &job_file.scan_errors {
+            println!(
+                "  {} {}: {}",
+                "✗".red(),
+                job_file.relative_path(&scan_error.path).display(),
+                scan_error.error.dimmed()
+            );
+        }
+    }
 
-    Ok(())
+    // Let scripts gate on the contents of the job file without re-checking
+    Ok(options.exit_code(&job_file.statistics))
 }
 
-/// Print a summary of the job file (same as explore command)
-fn print_summary(job_file: &JobFile) {
-    println!("\n{}", "Summary:".bold().underline());
-    println!("  Root directory: {}", job_file.root_directory.display());
-    println!("  Total files:    {}", job_file.total_files);
-    println!("\n{}", "Status breakdown:".bold());
+/// Job files to take stats of: the arguments, with a `*` or `?` in a file name (a glob the
+/// shell didn't expand, as on Windows) replaced by the files it matches in its directory
+pub fn expand_job_files(arguments: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut job_files = Vec::new();
+    for argument in arguments {
+        let name = argument.file_name().unwrap_or_default().to_string_lossy();
+        if argument.exists() || !(name.contains('*') || name.contains('?')) {
+            job_files.push(argument);
+            continue;
+        }
+
+        let dir = match argument.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut matches: Vec<PathBuf> = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter(|entry| glob_match(&name, &entry.file_name().to_string_lossy()))
+            .map(|entry| argument.with_file_name(entry.file_name()))
+            .collect();
+        if matches.is_empty() {
+            anyhow::bail!("No job files match {}", argument.display());
+        }
+        matches.sort();
+        job_files.append(&mut matches);
+    }
+    Ok(job_files)
+}
+
+/// Counts of one job file for the combined stats table
+fn print_job_row(statistics: &Statistics, label: &str) {
+    let passed = statistics.ok + statistics.warning;
+    let checked = passed + statistics.bad + statistics.error;
+    let success = if checked > 0 {
+        format!("{:.1}%", passed as f64 * 100.0 / checked as f64)
+    } else {
+        "-".to_string()
+    };
+    let files = statistics.to_be_checked
+        + statistics.checking
+        + checked
+        + statistics.missing
+        + statistics.skipped
+        + statistics.duplicate;
     println!(
-        "  {} To be checked: {}",
-        "○".yellow(),
-        job_file.statistics.to_be_checked
+        "  {:>8} {:>8} {:>8} {} {} {:>8} {:>8} {:>8}  {}",
+        files,
+        statistics.ok,
+        statistics.warning,
+        highlight(statistics.bad, 6, Color::Red),
+        highlight(statistics.error, 6, Color::Yellow),
+        statistics.to_be_checked + statistics.checking,
+        statistics.missing,
+        success,
+        label
     );
+}
+
+/// `stats` of several job files (one per disk, say): a table of each job's counts with
+/// the combined total, the listed files of all of them with the job each came from, and
+/// the files that are in more than one job with a different status in each
+pub fn show_combined_statistics(job_file_paths: Vec<PathBuf>, options: StatsOptions) -> Result<u8> {
+    let single_only = [
+        (options.format != StatsFormat::Text, "--format"),
+        (options.paths_only, "--paths-only"),
+        (options.print0, "--print0"),
+        (options.output.is_some(), "--output"),
+        (options.output_dir.is_some(), "--output-dir"),
+        (options.playlist.is_some(), "--playlist"),
+        (options.report.is_some(), "--report"),
+        (options.stale.is_some(), "--stale"),
+        (options.group_by_dir, "--group-by-dir"),
+        (options.error_summary, "--error-summary"),
+        (options.breakdown, "--breakdown"),
+        (options.show_no_md5, "--show-no-md5"),
+        (options.show_quick, "--show-quick"),
+    ];
+    if let Some((_, flag)) = single_only.iter().find(|(given, _)| *given) {
+        anyhow::bail!(
+            "{} works on one job file, several job files are only combined in the text overview",
+            flag
+        );
+    }
+
     println!(
-        "  {} Checking:      {}",
-        "◐".cyan(),
-        job_file.statistics.checking
+        "{} Loading {} job files...",
+        "→".blue().bold(),
+        job_file_paths.len()
     );
-    println!("  {} OK:            {}", "✓".green(), job_file.statistics.ok);
-    println!("  {} Bad:           {}", "✗".red(), job_file.statistics.bad);
+    let mut job_files = Vec::new();
+    let mut unfiltered = 0;
+    for path in &job_file_paths {
+        let (mut job_file, total) = load_job_file(path, &options)?;
+        job_file.statistics = Statistics::from_jobs(&job_file.jobs);
+        job_files.push((path.display().to_string(), job_file));
+        unfiltered += total;
+    }
+    if let Some(filter) = &options.filter {
+        let matched: usize = job_files.iter().map(|(_, job_file)| job_file.jobs.len()).sum();
+        println!(
+            "  Filter: {}, {} of {} entries",
+            filter.describe(),
+            format_count(matched as u64),
+            format_count(unfiltered as u64)
+        );
+    }
+
+    println!("\n{}", "Job files:".bold().underline());
     println!(
-        "  {} Error:         {}",
-        "⚠".yellow(),
-        job_file.statistics.error
+        "  {}",
+        format!(
+            "{:>8} {:>8} {:>8} {:>6} {:>6} {:>8} {:>8} {:>8}  Job",
+            "Files", "OK", "Warning", "Bad", "Error", "Pending", "Missing", "Success"
+        )
+        .dimmed()
     );
+    let mut combined: Vec<FlacJob> = Vec::new();
+    for (label, job_file) in &job_files {
+        print_job_row(&job_file.statistics, label);
+        combined.extend(job_file.jobs.iter().cloned());
+    }
+    let total = Statistics::from_jobs(&combined);
+    print_job_row(&total, &"Combined".bold().to_string());
 
-    // Show percentage if any files have been checked
-    let checked_files = job_file.statistics.ok + job_file.statistics.bad + job_file.statistics.error;
-    if checked_files > 0 {
-        let ok_percent = (job_file.statistics.ok as f64 / checked_files as f64) * 100.0;
-        println!("\n  Success rate: {:.1}%", ok_percent);
+    // The listed files of every job, in one --sort order
+    let mut listed: Vec<(&str, &JobFile, &FlacJob)> = job_files
+        .iter()
+        .flat_map(|(label, job_file)| {
+            job_file
+                .jobs
+                .iter()
+                .filter(|job| options.lists(job.status.clone()))
+                .map(move |job| (label.as_str(), job_file, job))
+        })
+        .collect();
+    listed.sort_by(|(_, _, a), (_, _, b)| compare_jobs(a, b, &options));
+
+    let sections = [
+        (&[FlacStatus::Bad][..], "BAD Files (corrupted):".red().bold(), "✗".red()),
+        (&[FlacStatus::Error], "ERROR Files (could not check):".yellow().bold(), "⚠".yellow()),
+        (
+            &[FlacStatus::Warning],
+            "WARNING Files (verified, with non-fatal findings):".bright_yellow().bold(),
+            "!".bright_yellow(),
+        ),
+        (&[FlacStatus::Missing], "MISSING Files (no longer on disk):".blue().bold(), "?".blue()),
+        (&[FlacStatus::Ok], "OK Files (verified):".green().bold(), "✓".green()),
+        (
+            &[FlacStatus::Skipped],
+            "Skipped Files (excluded by --skip):".dimmed().bold(),
+            "-".dimmed(),
+        ),
+        (
+            &[FlacStatus::Duplicate],
+            "Duplicate Files (hardlinks, checked through another path):".dimmed().bold(),
+            "=".dimmed(),
+        ),
+        (
+            &[FlacStatus::ToBeChecked, FlacStatus::Checking],
+            "Pending Files (to be checked):".yellow().bold(),
+            "○".yellow(),
+        ),
+    ];
+    for (statuses, heading, marker) in sections {
+        let files: Vec<_> = listed
+            .iter()
+            .filter(|(_, _, job)| statuses.contains(&job.status))
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+        println!("\n{}", heading);
+        for (label, job_file, job) in files {
+            let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+            let path = if options.full_paths {
+                path.as_path()
+            } else {
+                job_file.relative_path(path)
+            };
+            println!(
+                "  {} {} {}{}",
+                marker,
+                path.display(),
+                format!("[{}]", label).dimmed(),
+                checked_suffix(job, &options)
+            );
+            if let Some(message) = &job.error_message {
+                println!("    {}: {}", "Reason".dimmed(), message.dimmed());
+            }
+            for warning in &job.warnings {
+                println!("    {}: {}", "Warning".dimmed(), warning.dimmed());
+            }
+        }
+    }
+
+    // The same file in several jobs (overlapping shelves, a job made twice) is counted in
+    // each; it's only a problem when the jobs disagree on its status
+    let mut entries: BTreeMap<PathBuf, Vec<(&str, &FlacStatus)>> = BTreeMap::new();
+    for (label, job_file) in &job_files {
+        for job in &job_file.jobs {
+            // Jobs made from different working directories may name the file differently
+            let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+            let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+            entries.entry(path).or_default().push((label, &job.status));
+        }
+    }
+    let overlapping = entries.values().filter(|jobs| jobs.len() > 1).count();
+    let conflicts: Vec<_> = entries
+        .iter()
+        .filter(|(_, jobs)| jobs.iter().any(|(_, status)| *status != jobs[0].1))
+        .collect();
+    if !conflicts.is_empty() {
+        println!(
+            "\n{}",
+            "Conflicting Entries (same file, different status in different jobs):"
+                .magenta()
+                .bold()
+        );
+        for (path, jobs) in &conflicts {
+            let statuses: Vec<String> = jobs
+                .iter()
+                .map(|(label, status)| format!("{} in {}", status, label))
+                .collect();
+            println!("  {} {}", "≠".magenta(), path.display());
+            println!("    {}", statuses.join(", ").dimmed());
+        }
     }
-}
\ No newline at end of file
+
+    println!();
+    let passed = total.ok + total.warning;
+    let checked = passed + total.bad + total.error;
+    if checked > 0 {
+        println!(
+            "  Combined success rate: {:.1}% of {} checked files in {} jobs",
+            passed as f64 * 100.0 / checked as f64,
+            format_count(checked as u64),
+            job_files.len()
+        );
+    }
+    if overlapping > 0 {
+        println!(
+            "{} {} files are in more than one job and counted in each ({} with conflicting statuses)",
+            "!".bright_yellow().bold(),
+            format_count(overlapping as u64),
+            conflicts.len()
+        );
+    }
+    if total.bad + total.error == 0 {
+        println!("{} No bad or error files in any job", "✓".green().bold());
+    } else {
+        println!(
+            "{} Found {} bad and {} error files.",
+            "⚠".yellow().bold(),
+            total.bad,
+            total.error
+        );
+    }
+
+    Ok(options.exit_code(&total))
+}
+
+/// The --show-* options only pick what the text output lists; the other outputs have
+/// every (selected) file, so they are refused rather than silently ignored
+fn refuse_text_options(options: &StatsOptions, mode: &str) -> Result<()> {
+    let text_only = [
+        (options.show_ok, "--show-ok"),
+        (options.show_pending, "--show-pending"),
+        (options.show_no_md5, "--show-no-md5"),
+        (options.show_skipped, "--show-skipped"),
+        (options.show_quick, "--show-quick"),
+        (options.show_duplicates, "--show-duplicates"),
+        (options.show_times, "--show-times"),
+        (options.group_by_dir, "--group-by-dir"),
+        (options.error_summary, "--error-summary"),
+        (options.output_dir.is_some(), "--output-dir"),
+        (options.playlist.is_some(), "--playlist"),
+        (options.report.is_some(), "--report"),
+        (options.stale.is_some(), "--stale"),
+        (options.bytes, "--bytes"),
+        (options.breakdown, "--breakdown"),
+    ];
+    if let Some((_, flag)) = text_only.iter().find(|(given, _)| *given) {
+        anyhow::bail!("{} only applies to the text output, not to {}", flag, mode);
+    }
+    Ok(())
+}
+
+/// Where the JSON or CSV output goes: the --output file, or stdout
+fn report_writer(output: Option<&Path>) -> Result<Box<dyn Write>> {
+    Ok(match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path).with_context(|| {
+            format!("Failed to create {}", path.display())
+        })?)),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
+/// Where the report went, for the error when writing it fails
+fn report_destination(output: Option<&Path>) -> String {
+    output.map_or_else(|| "stdout".to_string(), |path| path.display().to_string())
+}
+
+/// `stats --format json`: the report as one document and nothing else, so it can be
+/// piped straight into a parser
+fn print_json(job_file_path: &Path, options: &StatsOptions) -> Result<u8> {
+    refuse_text_options(options, "--format json")?;
+    if !options.statuses.is_empty() {
+        anyhow::bail!(
+            "--status picks the files of the text, CSV and --paths-only output, the JSON report lists every status"
+        );
+    }
+
+    let (mut job_file, total) = load_job_file(job_file_path, options)?;
+    job_file.statistics = Statistics::from_jobs(&job_file.jobs);
+
+    let display = |path: &Path| {
+        if options.full_paths {
+            path.display().to_string()
+        } else {
+            job_file.relative_path(path).display().to_string()
+        }
+    };
+    let mut files: BTreeMap<&'static str, Vec<ReportEntry>> = FlacStatus::ALL
+        .iter()
+        .map(|status| (status.name(), Vec::new()))
+        .collect();
+    for job in sorted_jobs(&job_file, options) {
+        let entry = ReportEntry {
+            path: display(job.quarantined_from.as_ref().unwrap_or(&job.path)),
+            error_message: job.error_message.as_deref(),
+            warnings: &job.warnings,
+            checked_at: job.checked_at,
+            changed: job.changed,
+            quarantined_to: job.quarantined_from.is_some().then_some(job.path.as_path()),
+            duplicate_of: job.duplicate_of.as_deref().map(display),
+            skip_pattern: job.skip_pattern.as_deref(),
+        };
+        files.entry(job.status.name()).or_default().push(entry);
+    }
+
+    let report = StatsReport {
+        schema_version: 1,
+        job_file: job_file_path,
+        root_directories: &job_file.root_directories,
+        total_files: job_file.jobs.len(),
+        statistics: &job_file.statistics,
+        sample: job_file.sample.as_ref(),
+        oldest_check: job_file.jobs.iter().filter_map(|job| job.checked_at).min(),
+        newest_check: job_file.jobs.iter().filter_map(|job| job.checked_at).max(),
+        files,
+        filter: options.filter.as_ref().map(|filter| ReportFilter {
+            pattern: match filter {
+                PathFilter::Substring(text) => text.clone(),
+                PathFilter::Regex(regex) => regex.as_str().to_string(),
+            },
+            regex: matches!(filter, PathFilter::Regex(_)),
+            matched: job_file.jobs.len(),
+            total,
+        }),
+    };
+    let mut out = report_writer(options.output.as_deref())?;
+    serde_json::to_writer_pretty(&mut out, &report)
+        .map_err(io::Error::from)
+        .and_then(|()| writeln!(out))
+        .and_then(|()| out.flush())
+        .with_context(|| {
+            format!(
+                "Failed to write the report to {}",
+                report_destination(options.output.as_deref())
+            )
+        })?;
+
+    Ok(options.exit_code(&job_file.statistics))
+}
+
+/// `stats --paths-only`: the path of every file with the --status statuses (all files
+/// without it), one per line and nothing else, for pipelines such as `xargs -d '\n' rm`.
+/// With --print0 each path ends with a NUL byte instead, for names with line breaks.
+/// A quarantined file is printed where it is now, so the path can be acted on.
+fn print_paths(job_file_path: &Path, options: &StatsOptions) -> Result<u8> {
+    refuse_text_options(options, "--paths-only")?;
+    if options.format != StatsFormat::Text {
+        anyhow::bail!("--paths-only is an output format of its own, it can't be combined with --format");
+    }
+    let (job_file, _) = load_job_file(job_file_path, options)?;
+
+    let mut out = report_writer(options.output.as_deref())?;
+    let result = (|| -> io::Result<()> {
+        for job in sorted_jobs(&job_file, options) {
+            if !options.statuses.is_empty() && !options.statuses.contains(&job.status) {
+                continue;
+            }
+            let path = if options.full_paths {
+                job.path.as_path()
+            } else {
+                job_file.relative_path(&job.path)
+            };
+            write_path(&mut out, path)?;
+            out.write_all(if options.print0 { b"\0" } else { b"\n" })?;
+        }
+        out.flush()
+    })();
+    // A consumer such as `head` closing the pipe early is not a failure
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        result => result.with_context(|| {
+            format!(
+                "Failed to write the paths to {}",
+                report_destination(options.output.as_deref())
+            )
+        })?,
+    }
+
+    let statistics = Statistics::from_jobs(&job_file.jobs);
+    Ok(options.exit_code(&statistics))
+}
+
+/// Write a path as it is, so names that aren't valid UTF-8 still work
+fn write_path(out: &mut dyn Write, path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    out.write_all(std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()))?;
+    #[cfg(not(unix))]
+    out.write_all(path.display().to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Name of the --output-dir file a status is listed in
+fn list_file_name(status: &FlacStatus) -> &'static str {
+    match status {
+        FlacStatus::ToBeChecked | FlacStatus::Checking => "pending.txt",
+        FlacStatus::Ok => "ok.txt",
+        FlacStatus::Warning => "warning.txt",
+        FlacStatus::Bad => "bad.txt",
+        FlacStatus::Error => "error.txt",
+        FlacStatus::Missing => "missing.txt",
+        FlacStatus::Skipped => "skipped.txt",
+        FlacStatus::Duplicate => "duplicate.txt",
+    }
+}
+
+/// `stats --output-dir`: each list the text output shows, as its own file of paths (one
+/// per line, in the --sort order). Lists without files aren't written, and nothing is
+/// written if one of the files already exists, unless --force is given.
+fn write_lists(job_file: &JobFile, options: &StatsOptions, dir: &Path) -> Result<()> {
+    let mut lists: BTreeMap<&str, Vec<&Path>> = BTreeMap::new();
+    for job in sorted_jobs(job_file, options) {
+        if !options.lists(job.status.clone()) {
+            continue;
+        }
+        // Listed under their original place, as in the text output
+        let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+        let path = if options.full_paths {
+            path.as_path()
+        } else {
+            job_file.relative_path(path)
+        };
+        lists
+            .entry(list_file_name(&job.status))
+            .or_default()
+            .push(path);
+    }
+
+    println!();
+    if lists.is_empty() {
+        println!(
+            "{} No listed files, nothing written to {}",
+            "→".blue().bold(),
+            dir.display()
+        );
+        return Ok(());
+    }
+    if !options.force
+        && let Some(existing) = lists.keys().map(|name| dir.join(name)).find(|path| path.exists())
+    {
+        anyhow::bail!(
+            "{} already exists, use --force to replace the lists in {}",
+            existing.display(),
+            dir.display()
+        );
+    }
+
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    for (name, paths) in &lists {
+        let list_path = dir.join(name);
+        let mut list = Vec::new();
+        for path in paths {
+            write_path(&mut list, path)?;
+            list.push(b'\n');
+        }
+        fs::write(&list_path, list)
+            .with_context(|| format!("Failed to write {}", list_path.display()))?;
+        println!(
+            "{} Wrote {} paths to {}",
+            "✓".green().bold(),
+            format_count(paths.len() as u64),
+            list_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// `stats --report`: the summary, status counts, success rate, problem directories and
+/// the Bad and Error files as one Markdown or self-contained HTML file, for people who
+/// don't use the command line
+fn write_report(
+    job_file_path: &Path,
+    job_file: &JobFile,
+    options: &StatsOptions,
+    total: usize,
+    path: &Path,
+    format: ReportFormat,
+) -> Result<()> {
+    let statistics = &job_file.statistics;
+    let mut summary = vec![("Job file".to_string(), job_file_path.display().to_string())];
+    for root in &job_file.root_directories {
+        summary.push(("Root directory".to_string(), root.display().to_string()));
+    }
+    if let Some(note) = job_file.sample_note() {
+        summary.push(("Sample".to_string(), format!("{} (a spot check, not the whole library)", note)));
+    }
+    if let Some(filter) = &options.filter {
+        summary.push((
+            "Filter".to_string(),
+            format!(
+                "{}, {} of {} entries",
+                filter.describe(),
+                format_count(job_file.jobs.len() as u64),
+                format_count(total as u64)
+            ),
+        ));
+    }
+    summary.push(("Files".to_string(), format_count(job_file.jobs.len() as u64)));
+    let sizes: Vec<u64> = job_file
+        .jobs
+        .iter()
+        .filter(|job| job.status != FlacStatus::Duplicate)
+        .filter_map(|job| job.size)
+        .collect();
+    if !sizes.is_empty() {
+        summary.push((
+            "Total size".to_string(),
+            DecimalBytes(sizes.iter().sum()).to_string(),
+        ));
+    }
+    let oldest = job_file.jobs.iter().filter_map(|job| job.checked_at).min();
+    let newest = job_file.jobs.iter().filter_map(|job| job.checked_at).max();
+    if let (Some(oldest), Some(newest)) = (oldest, newest) {
+        summary.push(("Oldest check".to_string(), format_checked_at(&oldest)));
+        summary.push(("Newest check".to_string(), format_checked_at(&newest)));
+    }
+
+    let mut statuses = vec![
+        ("OK", statistics.ok),
+        ("Warning", statistics.warning),
+        ("Bad", statistics.bad),
+        ("Error", statistics.error),
+        ("Missing", statistics.missing),
+        ("To be checked", statistics.to_be_checked + statistics.checking),
+        ("Skipped", statistics.skipped),
+    ];
+    if statistics.duplicate > 0 {
+        statuses.push(("Duplicate", statistics.duplicate));
+    }
+    let passed = statistics.ok + statistics.warning;
+    let checked = passed + statistics.bad + statistics.error;
+    let success_rate = (checked > 0).then(|| (passed as f64 * 100.0 / checked as f64, checked));
+
+    let list = |status: FlacStatus, title: &'static str| FileList {
+        title,
+        files: sorted_jobs(job_file, options)
+            .into_iter()
+            .filter(|job| job.status == status)
+            .map(|job| {
+                let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+                let path = if options.full_paths {
+                    path.as_path()
+                } else {
+                    job_file.relative_path(path)
+                };
+                ReportFile {
+                    path: path.display().to_string(),
+                    message: job.error_message.clone(),
+                    checked: job.checked_at.as_ref().map(format_checked_at),
+                }
+            })
+            .collect(),
+    };
+    let (directories, total_directories) = problem_directories(job_file, options);
+    let report = Report {
+        title: job_file_path
+            .file_name()
+            .unwrap_or(job_file_path.as_os_str())
+            .to_string_lossy()
+            .into_owned(),
+        generated: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+        summary,
+        statuses,
+        success_rate,
+        directories,
+        total_directories,
+        lists: vec![
+            list(FlacStatus::Bad, "Bad files"),
+            list(FlacStatus::Error, "Error files"),
+        ],
+    };
+
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    write_file_atomic(path, report.render(format).as_bytes())?;
+    println!(
+        "\n{} Wrote the {} report to {}",
+        "✓".green().bold(),
+        match format {
+            ReportFormat::Markdown => "Markdown",
+            ReportFormat::Html => "HTML",
+        },
+        path.display()
+    );
+    Ok(())
+}
+
+/// `stats --playlist`: an extended M3U (UTF-8) of the Bad files, to audition them in a
+/// player. Paths are written as they are, not percent-encoded, with an #EXTINF line
+/// when the job has the file's length. No file is written when nothing is bad.
+fn write_playlist(job_file: &JobFile, options: &StatsOptions, playlist: &Path) -> Result<()> {
+    let bad: Vec<&FlacJob> = sorted_jobs(job_file, options)
+        .into_iter()
+        .filter(|job| job.status == FlacStatus::Bad)
+        .collect();
+    println!();
+    if bad.is_empty() {
+        println!(
+            "{} No bad files, so no playlist written to {}",
+            "→".blue().bold(),
+            playlist.display()
+        );
+        return Ok(());
+    }
+
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let base = absolute(playlist)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let mut m3u = String::from("#EXTM3U\n");
+    for job in &bad {
+        // Where the file is now, quarantined or not, so the player finds it
+        let path = absolute(&job.path);
+        if let Some(seconds) = job.duration_seconds {
+            let title = path.file_stem().unwrap_or_default().to_string_lossy();
+            m3u.push_str(&format!("#EXTINF:{},{}\n", seconds.round() as u64, title));
+        }
+        let path = if options.relative_playlist {
+            // A file on another drive can only be given by its absolute path
+            path_relative_to(&path, &base).unwrap_or(path)
+        } else {
+            path
+        };
+        m3u.push_str(&path.to_string_lossy());
+        m3u.push('\n');
+    }
+
+    if let Some(parent) = playlist.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(playlist, m3u).with_context(|| format!("Failed to write {}", playlist.display()))?;
+    println!(
+        "{} Wrote a playlist of {} bad files to {}",
+        "✓".green().bold(),
+        format_count(bad.len() as u64),
+        playlist.display()
+    );
+    Ok(())
+}
+
+/// Header row of `stats --format csv`; columns are only ever added at the end
+const CSV_COLUMNS: [&str; 7] = [
+    "path",
+    "status",
+    "error_message",
+    "size",
+    "checked_at",
+    "duration_seconds",
+    "check_duration_ms",
+];
+
+/// `stats --format csv`: one row per job entry (those with the --status statuses), in
+/// the --sort order. Paths are shown as in the text output; size is in bytes,
+/// checked_at in RFC 3339 (UTC), duration_seconds the length of the audio and
+/// check_duration_ms how long the last check took. Unknown values are empty.
+fn print_csv(job_file_path: &Path, options: &StatsOptions) -> Result<u8> {
+    refuse_text_options(options, "--format csv")?;
+    let (job_file, _) = load_job_file(job_file_path, options)?;
+
+    let mut csv = String::new();
+    push_csv_row(&mut csv, CSV_COLUMNS.iter().map(|column| column.to_string()));
+    for job in sorted_jobs(&job_file, options) {
+        if !options.statuses.is_empty() && !options.statuses.contains(&job.status) {
+            continue;
+        }
+        let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+        let path = if options.full_paths {
+            path.as_path()
+        } else {
+            job_file.relative_path(path)
+        };
+        push_csv_row(
+            &mut csv,
+            [
+                path.display().to_string(),
+                job.status.name().to_string(),
+                job.error_message.clone().unwrap_or_default(),
+                job.size.map(|size| size.to_string()).unwrap_or_default(),
+                job.checked_at
+                    .map(|checked_at| checked_at.to_rfc3339())
+                    .unwrap_or_default(),
+                job.duration_seconds
+                    .map(|seconds| format!("{:.3}", seconds))
+                    .unwrap_or_default(),
+                job.check_duration_ms
+                    .map(|ms| ms.to_string())
+                    .unwrap_or_default(),
+            ]
+            .into_iter(),
+        );
+    }
+
+    let mut out = report_writer(options.output.as_deref())?;
+    out.write_all(csv.as_bytes())
+        .and_then(|()| out.flush())
+        .with_context(|| {
+            format!(
+                "Failed to write the CSV to {}",
+                report_destination(options.output.as_deref())
+            )
+        })?;
+
+    let statistics = Statistics::from_jobs(&job_file.jobs);
+    Ok(options.exit_code(&statistics))
+}
+
+/// Append one CSV line (RFC 4180): fields holding a comma, quote or line break are put in
+/// quotes, with any quotes inside doubled
+fn push_csv_row(csv: &mut String, fields: impl Iterator<Item = String>) {
+    for (idx, field) in fields.enumerate() {
+        if idx > 0 {
+            csv.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            csv.push('"');
+            csv.push_str(&field.replace('"', "\"\""));
+            csv.push('"');
+        } else {
+            csv.push_str(&field);
+        }
+    }
+    csv.push_str("\r\n");
+}
+
+/// Format a check timestamp in local time for display
+fn format_checked_at(checked_at: &DateTime<Utc>) -> String {
+    checked_at
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Files of one directory (or directory tree, with --depth) by status, for --group-by-dir
+/// (and of one audio format, for --breakdown)
+#[derive(Default)]
+pub struct DirectoryCounts {
+    pub bad: usize,
+    pub error: usize,
+    pub pending: usize,
+    pub warning: usize,
+    pub ok: usize,
+    pub total: usize,
+}
+
+impl DirectoryCounts {
+    fn add(&mut self, status: &FlacStatus) {
+        self.total += 1;
+        match status {
+            FlacStatus::Bad => self.bad += 1,
+            FlacStatus::Error => self.error += 1,
+            FlacStatus::ToBeChecked | FlacStatus::Checking => self.pending += 1,
+            FlacStatus::Warning => self.warning += 1,
+            FlacStatus::Ok => self.ok += 1,
+            FlacStatus::Missing | FlacStatus::Skipped | FlacStatus::Duplicate => {}
+        }
+    }
+}
+
+/// The directory a file is counted under: its own, or with a depth the one that many
+/// levels below its root (files less deep count under their own)
+fn group_directory(job_file: &JobFile, path: &Path, depth: Option<usize>) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    let Some(depth) = depth else {
+        return parent.to_path_buf();
+    };
+    let root = find_root(&job_file.root_directories, path).unwrap_or(Path::new(""));
+    let below = parent.strip_prefix(root).unwrap_or(parent);
+    root.join(below.components().take(depth).collect::<PathBuf>())
+}
+
+/// The directories holding bad, error or pending files with their counts and how they
+/// are displayed, worst first (most bad files, then errors, then pending), and the
+/// number of directories there are
+fn problem_directories(
+    job_file: &JobFile,
+    options: &StatsOptions,
+) -> (Vec<(String, DirectoryCounts)>, usize) {
+    let mut directories: BTreeMap<PathBuf, DirectoryCounts> = BTreeMap::new();
+    for job in &job_file.jobs {
+        let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+        directories
+            .entry(group_directory(job_file, path, options.depth))
+            .or_default()
+            .add(&job.status);
+    }
+    let total = directories.len();
+
+    let mut problems: Vec<(String, DirectoryCounts)> = directories
+        .into_iter()
+        .filter(|(_, counts)| counts.bad + counts.error + counts.pending > 0)
+        .map(|(dir, counts)| {
+            let dir = if options.full_paths {
+                dir.as_path()
+            } else {
+                job_file.relative_path(&dir)
+            };
+            let dir = if dir.as_os_str().is_empty() {
+                ".".to_string()
+            } else {
+                dir.display().to_string()
+            };
+            (dir, counts)
+        })
+        .collect();
+    // Stable, so directories that are equally bad stay in path order
+    problems.sort_by(|(_, a), (_, b)| {
+        (b.bad, b.error, b.pending).cmp(&(a.bad, a.error, a.pending))
+    });
+    (problems, total)
+}
+
+/// Print the directories holding bad, error or pending files with their counts
+fn print_directories(job_file: &JobFile, options: &StatsOptions) {
+    let (problems, total) = problem_directories(job_file, options);
+    if problems.is_empty() {
+        println!(
+            "\n{} None of the {} directories has bad, error or pending files",
+            "✓".green().bold(),
+            format_count(total as u64)
+        );
+        return;
+    }
+
+    println!(
+        "\n{}",
+        format!(
+            "Directories with problems ({} of {}):",
+            format_count(problems.len() as u64),
+            format_count(total as u64)
+        )
+        .red()
+        .bold()
+    );
+    println!(
+        "  {}",
+        format!(
+            "{:>6} {:>6} {:>8} {:>8} {:>6} {:>6}  Directory",
+            "Bad", "Error", "Pending", "Warning", "OK", "Files"
+        )
+        .dimmed()
+    );
+    for (dir, counts) in &problems {
+        println!(
+            "  {} {} {} {:>8} {:>6} {:>6}  {}",
+            highlight(counts.bad, 6, Color::Red),
+            highlight(counts.error, 6, Color::Yellow),
+            highlight(counts.pending, 8, Color::Yellow),
+            counts.warning,
+            counts.ok,
+            counts.total,
+            dir
+        );
+    }
+}
+
+/// A right-aligned count, colored when it isn't zero
+fn highlight(count: usize, width: usize, color: Color) -> ColoredString {
+    let text = format!("{:>width$}", count);
+    if count == 0 {
+        text.normal()
+    } else {
+        text.color(color)
+    }
+}
+
+/// Example paths shown per message by --error-summary
+const ERROR_SUMMARY_EXAMPLES: usize = 3;
+
+/// Parts of check's messages that differ from file to file (where decoding failed, how
+/// much of a truncated file is there, how many attempts it took): the text from the
+/// start marker through the end marker is replaced
+const VARYING_PARTS: [(&str, &str, &str); 3] = [
+    (", failed at ~", ")", ""),
+    ("a frame after ", ")", "a frame"),
+    (" (after ", " attempts)", ""),
+];
+
+/// An error message with the file's own path and the other per-file details taken out,
+/// so the same failure reads the same for every file
+fn normalize_message(message: &str, job: &FlacJob) -> String {
+    let mut message = message.to_string();
+    for path in std::iter::once(&job.path).chain(&job.quarantined_from) {
+        message = message.replace(&path.display().to_string(), "<file>");
+    }
+    for (start, end, replacement) in VARYING_PARTS {
+        if let Some(from) = message.find(start)
+            && let Some(to) = message[from + start.len()..].find(end)
+        {
+            let to = from + start.len() + to + end.len();
+            message.replace_range(from..to, replacement);
+        }
+    }
+    message
+}
+
+/// `stats --error-summary`: the error and bad messages with how many files gave each,
+/// most frequent first, so one systemic cause (a dead mount, missing permissions)
+/// stands out from scattered corruption
+fn print_error_summary(job_file: &JobFile, options: &StatsOptions) -> Result<u8> {
+    let mut bad = 0;
+    let mut errors = 0;
+    for (status, heading) in [
+        (FlacStatus::Error, "Error messages (could not check):".yellow().bold()),
+        (FlacStatus::Bad, "Bad reasons (corrupted):".red().bold()),
+    ] {
+        let mut messages: BTreeMap<String, Vec<&Path>> = BTreeMap::new();
+        for job in sorted_jobs(job_file, options) {
+            if job.status != status {
+                continue;
+            }
+            let message = match &job.error_message {
+                Some(message) => normalize_message(message, job),
+                None => "(no message recorded)".to_string(),
+            };
+            let path = job.quarantined_from.as_ref().unwrap_or(&job.path);
+            let path = if options.full_paths {
+                path.as_path()
+            } else {
+                job_file.relative_path(path)
+            };
+            messages.entry(message).or_default().push(path);
+        }
+        if messages.is_empty() {
+            continue;
+        }
+        let files: usize = messages.values().map(Vec::len).sum();
+        match status {
+            FlacStatus::Bad => bad = files,
+            _ => errors = files,
+        }
+
+        let mut messages: Vec<(String, Vec<&Path>)> = messages.into_iter().collect();
+        // Stable, so messages given by as many files stay in alphabetical order
+        messages.sort_by_key(|(_, paths)| std::cmp::Reverse(paths.len()));
+
+        println!("\n{}", heading);
+        for (message, paths) in &messages {
+            println!(
+                "  {:>7}  {}",
+                format_count(paths.len() as u64).bold(),
+                message
+            );
+            for path in paths.iter().take(ERROR_SUMMARY_EXAMPLES) {
+                println!("           {}", path.display().to_string().dimmed());
+            }
+            if paths.len() > ERROR_SUMMARY_EXAMPLES {
+                println!(
+                    "           {}",
+                    format!(
+                        "... and {} more",
+                        format_count((paths.len() - ERROR_SUMMARY_EXAMPLES) as u64)
+                    )
+                    .dimmed()
+                );
+            }
+        }
+    }
+
+    println!();
+    if bad + errors == 0 {
+        println!("{} No bad or error files", "✓".green().bold());
+    } else {
+        println!(
+            "{} Found {} bad and {} error files.",
+            "⚠".yellow().bold(),
+            bad,
+            errors
+        );
+    }
+
+    Ok(options.exit_code(&job_file.statistics))
+}
+
+/// Suffix shown after a listed file with its last check time (when --show-times is given)
+fn checked_suffix(job: &FlacJob, options: &StatsOptions) -> String {
+    if !options.show_times {
+        return String::new();
+    }
+
+    match &job.checked_at {
+        Some(checked_at) => format!(
+            " {}",
+            format!("(checked {})", format_checked_at(checked_at)).dimmed()
+        ),
+        None => format!(" {}", "(never checked)".dimmed()),
+    }
+}
+
+/// Length of audio for the audio totals, e.g. "2,871 hours" or "4.5 hours"
+fn describe_play_time(seconds: f64) -> String {
+    let hours = seconds / 3600.0;
+    if hours >= 10.0 {
+        format!("{} hours", format_count(hours.round() as u64))
+    } else if hours >= 1.0 {
+        format!("{:.1} hours", hours)
+    } else if seconds >= 60.0 {
+        describe_age(Duration::from_secs((seconds / 60.0).round() as u64 * 60))
+    } else {
+        describe_age(Duration::from_secs(seconds.round() as u64))
+    }
+}
+
+/// Print the number of tracks, length of audio and size of the library, in total and per
+/// status (how much audio is still unverified), from the sizes and STREAMINFO lengths in
+/// the job. Hardlinked duplicates are left out, they are the same audio. Totals that
+/// leave out files without the value are marked partial.
+fn print_audio_totals(job_file: &JobFile, options: &StatsOptions) {
+    let jobs: Vec<&FlacJob> = job_file
+        .jobs
+        .iter()
+        .filter(|job| job.status != FlacStatus::Duplicate)
+        .collect();
+    if jobs
+        .iter()
+        .all(|job| job.size.is_none() && job.duration_seconds.is_none())
+    {
+        return;
+    }
+
+    let line = |label: &str, jobs: &[&FlacJob]| {
+        let sizes: Vec<u64> = jobs.iter().filter_map(|job| job.size).collect();
+        let lengths: Vec<f64> = jobs.iter().filter_map(|job| job.duration_seconds).collect();
+        let mut parts = vec![format!("{} tracks", format_count(jobs.len() as u64))];
+        if !lengths.is_empty() {
+            let seconds: f64 = lengths.iter().sum();
+            parts.push(if options.bytes {
+                format_play_time(seconds)
+            } else {
+                describe_play_time(seconds)
+            });
+        }
+        if !sizes.is_empty() {
+            let bytes: u64 = sizes.iter().sum();
+            parts.push(if options.bytes {
+                format!("{} bytes", format_count(bytes))
+            } else {
+                DecimalBytes(bytes).to_string()
+            });
+        }
+
+        let mut missing = Vec::new();
+        if lengths.len() < jobs.len() {
+            missing.push(format!(
+                "length known for {}",
+                format_count(lengths.len() as u64)
+            ));
+        }
+        if sizes.len() < jobs.len() {
+            missing.push(format!("size known for {}", format_count(sizes.len() as u64)));
+        }
+        let partial = if missing.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", format!("(partial: {})", missing.join(", ")).dimmed())
+        };
+        println!("  {:<15} {}{}", format!("{}:", label), parts.join(", "), partial);
+    };
+
+    println!("\n{}", "Audio totals:".bold());
+    line("All files", &jobs);
+    for (label, statuses) in [
+        ("OK", &[FlacStatus::Ok][..]),
+        ("Warning", &[FlacStatus::Warning]),
+        ("Bad", &[FlacStatus::Bad]),
+        ("Error", &[FlacStatus::Error]),
+        ("Missing", &[FlacStatus::Missing]),
+        ("To be checked", &[FlacStatus::ToBeChecked, FlacStatus::Checking]),
+        ("Skipped", &[FlacStatus::Skipped]),
+    ] {
+        let matching: Vec<&FlacJob> = jobs
+            .iter()
+            .copied()
+            .filter(|job| statuses.contains(&job.status))
+            .collect();
+        if !matching.is_empty() {
+            line(label, &matching);
+        }
+    }
+}
+
+/// A sample format for --breakdown, e.g. "16-bit / 44.1 kHz"
+fn describe_format(bits_per_sample: u32, sample_rate: u32) -> String {
+    format!(
+        "{}-bit / {} kHz",
+        bits_per_sample,
+        f64::from(sample_rate) / 1000.0
+    )
+}
+
+/// A channel layout for --breakdown
+fn describe_channels(channels: u32) -> String {
+    match channels {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        _ => format!("{} channels", channels),
+    }
+}
+
+/// `stats --breakdown`: the files per sample format (bit depth and sample rate) and per
+/// channel layout, from the STREAMINFO values in the job, with how many of each are bad,
+/// errors or still to be checked. Files whose header wasn't read count as "unknown",
+/// hardlinked duplicates are left out as in the audio totals.
+fn print_breakdown(job_file: &JobFile, options: &StatsOptions) {
+    let jobs: Vec<&FlacJob> = job_file
+        .jobs
+        .iter()
+        .filter(|job| job.status != FlacStatus::Duplicate)
+        .collect();
+
+    let formats = breakdown(&jobs, options, |job| {
+        let bits_per_sample = job.bits_per_sample?;
+        let sample_rate = job.sample_rate?;
+        Some((
+            (sample_rate, bits_per_sample),
+            describe_format(bits_per_sample, sample_rate),
+        ))
+    });
+    print_breakdown_table("Sample formats:", "Format", &formats);
+    let layouts = breakdown(&jobs, options, |job| {
+        job.channels
+            .map(|channels| (channels, describe_channels(channels)))
+    });
+    print_breakdown_table("Channel layouts:", "Channels", &layouts);
+}
+
+/// The files counted per bucket, in the --breakdown-sort order with "unknown" (the files
+/// the bucket function gives None for) last
+fn breakdown<K: Ord>(
+    jobs: &[&FlacJob],
+    options: &StatsOptions,
+    bucket: impl Fn(&FlacJob) -> Option<(K, String)>,
+) -> Vec<(String, DirectoryCounts)> {
+    let mut buckets: BTreeMap<K, (String, DirectoryCounts)> = BTreeMap::new();
+    let mut unknown = DirectoryCounts::default();
+    for job in jobs {
+        match bucket(job) {
+            Some((key, label)) => buckets
+                .entry(key)
+                .or_insert_with(|| (label, DirectoryCounts::default()))
+                .1
+                .add(&job.status),
+            None => unknown.add(&job.status),
+        }
+    }
+
+    let mut rows: Vec<(String, DirectoryCounts)> = buckets.into_values().collect();
+    if options.breakdown_sort == BreakdownSort::Count {
+        // Stable, so buckets with as many files stay in format order
+        rows.sort_by_key(|(_, counts)| std::cmp::Reverse(counts.total));
+    }
+    if unknown.total > 0 {
+        rows.push(("unknown".to_string(), unknown));
+    }
+    rows
+}
+
+/// Print one --breakdown table, the buckets with bad or error files highlighted
+fn print_breakdown_table(title: &str, heading: &str, rows: &[(String, DirectoryCounts)]) {
+    println!("\n{}", title.bold());
+    if rows.is_empty() {
+        println!("  {}", "(no files)".dimmed());
+        return;
+    }
+    let width = rows
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(heading.len());
+    println!(
+        "  {}",
+        format!(
+            "{:<width$} {:>7} {:>6} {:>6} {:>8} {:>8} {:>7}",
+            heading, "Files", "Bad", "Error", "Pending", "Warning", "OK"
+        )
+        .dimmed()
+    );
+    for (label, counts) in rows {
+        let label = format!("{:<width$}", label);
+        let label = if counts.bad > 0 {
+            label.red().bold()
+        } else if counts.error > 0 {
+            label.yellow()
+        } else {
+            label.normal()
+        };
+        println!(
+            "  {} {:>7} {} {} {} {:>8} {:>7}",
+            label,
+            format_count(counts.total as u64),
+            highlight(counts.bad, 6, Color::Red),
+            highlight(counts.error, 6, Color::Yellow),
+            highlight(counts.pending, 8, Color::Yellow),
+            counts.warning,
+            counts.ok
+        );
+    }
+}
+
+/// Print the oldest and newest check timestamps, if any file has been checked
+fn print_check_times(job_file: &JobFile) {
+    let oldest = job_file.jobs.iter().filter_map(|j| j.checked_at).min();
+    let newest = job_file.jobs.iter().filter_map(|j| j.checked_at).max();
+
+    if let (Some(oldest), Some(newest)) = (oldest, newest) {
+        println!("\n  Oldest check:   {}", format_checked_at(&oldest));
+        println!("  Newest check:   {}", format_checked_at(&newest));
+    }
+}
+
+/// Print a summary of the job file (same as explore command)
+fn print_summary(job_file: &JobFile, options: &StatsOptions, total: usize) {
+    println!("\n{}", "Summary:".bold().underline());
+    for root in &job_file.root_directories {
+        println!("  Root directory: {}", root.display());
+    }
+    if let Some(note) = job_file.sample_note() {
+        println!(
+            "  {} {} {}",
+            "Sample:        ".yellow().bold(),
+            note.yellow().bold(),
+            "(a spot check, not the whole library)".yellow()
+        );
+    }
+    if let Some(plan) = &job_file.results_of {
+        println!("  Results of:     {}", plan.display());
+    }
+    if !job_file.exclude.is_empty() {
+        println!("  Excluded:       {}", job_file.exclude.join(", "));
+    }
+    if let Some(depth) = job_file.max_depth {
+        println!("  Max depth:      {}", depth);
+    }
+    if let Some(range) = job_file.size_range() {
+        println!("  Size range:     {}", range);
+    }
+    if let Some(since) = job_file.modified_since {
+        println!(
+            "  Modified since: {}",
+            since.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    if let Some(stored_root) = &job_file.stored_root {
+        println!("  Stored root:    {} (replaced by --root)", stored_root.display());
+    }
+    if job_file.relative_paths {
+        println!("  Paths:          relative to the root directory");
+    }
+    if !job_file.scan_errors.is_empty() {
+        println!("  Scan errors:    {}", job_file.scan_errors.len());
+    }
+    for ignore_file in &job_file.ignore_files {
+        println!(
+            "  Ignore file:    {} ({} patterns)",
+            ignore_file.path.display(),
+            ignore_file.patterns.len()
+        );
+    }
+    if !job_file.extensions.is_empty() {
+        println!("  Extensions:     {}", job_file.extensions.join(", "));
+    }
+    if let Some(filter) = &options.filter {
+        println!(
+            "  {} {}, {} of {} entries",
+            "Filter:        ".cyan().bold(),
+            filter.describe(),
+            format_count(job_file.jobs.len() as u64),
+            format_count(total as u64)
+        );
+    }
+    println!("  Total files:    {}", job_file.total_files);
+    crate::explore::print_total_size(job_file);
+    println!("\n{}", "Status breakdown:".bold());
+    println!(
+        "  {} To be checked: {}",
+        "○".yellow(),
+        job_file.statistics.to_be_checked
+    );
+    println!(
+        "  {} Checking:      {}",
+        "◐".cyan(),
+        job_file.statistics.checking
+    );
+    println!("  {} OK:            {}", "✓".green(), job_file.statistics.ok);
+    println!(
+        "  {} Warning:       {}",
+        "!".bright_yellow(),
+        job_file.statistics.warning
+    );
+    println!("  {} Bad:           {}", "✗".red(), job_file.statistics.bad);
+    println!(
+        "  {} Error:         {}",
+        "⚠".yellow(),
+        job_file.statistics.error
+    );
+    println!(
+        "  {} Missing:       {}",
+        "?".blue(),
+        job_file.statistics.missing
+    );
+    println!(
+        "  {} Skipped:       {}",
+        "-".dimmed(),
+        job_file.statistics.skipped
+    );
+    if job_file.statistics.duplicate > 0 {
+        println!(
+            "  {} Duplicate:     {}",
+            "=".dimmed(),
+            job_file.statistics.duplicate
+        );
+    }
+
+    // Show percentage if any files have been checked (files with warnings verified fine)
+    let passed = job_file.statistics.ok + job_file.statistics.warning;
+    let checked_files = passed + job_file.statistics.bad + job_file.statistics.error;
+    if checked_files > 0 {
+        let ok_percent = (passed as f64 / checked_files as f64) * 100.0;
+        println!("\n  Success rate: {:.1}%", ok_percent);
+    }
+    if let Some(stale) = options.stale {
+        let stale_count = job_file.jobs.iter().filter(|job| options.is_stale(job)).count();
+        println!(
+            "  Stale:        {} of {} OK results are older than {} (or have no check time)",
+            format_count(stale_count as u64),
+            format_count(job_file.statistics.ok as u64),
+            describe_age(stale)
+        );
+    }
+
+    crate::check::print_decode_only_count(job_file);
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_per_rfc_4180() {
+        let mut csv = String::new();
+        let fields = [
+            "/music/plain.flac",
+            "Artist, The",
+            "12\" mix",
+            "two\nlines",
+            "carriage\rreturn",
+            "",
+        ];
+        push_csv_row(&mut csv, fields.iter().map(|field| field.to_string()));
+        assert_eq!(
+            csv,
+            "/music/plain.flac,\"Artist, The\",\"12\"\" mix\",\"two\nlines\",\"carriage\rreturn\",\r\n"
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Size of an ID3v1 tag, which always sits in the last 128 bytes of a file
+const ID3V1_SIZE: u64 = 128;
+
+/// Size of an APE tag footer (and of its optional header)
+const APE_FOOTER_SIZE: u64 = 32;
+
+/// Where the FLAC stream sits in a file that may have foreign tags wrapped around it
+#[derive(Debug)]
+pub struct StreamBounds {
+    /// Offset of the "fLaC" marker (after any ID3v2 tags)
+    pub start: u64,
+    /// Offset just after the last frame (before any ID3v1 or APE tag)
+    pub end: u64,
+    /// One description per foreign tag found
+    pub warnings: Vec<String>,
+}
+
+/// Look for ID3v2 tags before the stream and ID3v1/APE tags after it. These aren't
+/// allowed by the FLAC format; the reference decoder skips them but strict players don't.
+/// Only the first and last few bytes are read, and the input is left at an unspecified
+/// position.
+pub fn find_stream_bounds<R: Read + Seek>(input: &mut R) -> io::Result<StreamBounds> {
+    let len = input.seek(SeekFrom::End(0))?;
+    let mut bounds = StreamBounds {
+        start: 0,
+        end: len,
+        warnings: Vec::new(),
+    };
+
+    // Taggers that don't know FLAC sometimes prepend a tag more than once
+    while let Some(size) = id3v2_size(input, bounds.start, len)? {
+        bounds
+            .warnings
+            .push(format!("ID3v2 tag before the FLAC stream ({} bytes)", size));
+        bounds.start += size;
+    }
+
+    // ID3v1 is always last; an APE tag, if any, comes right before it
+    if bounds.end >= bounds.start + ID3V1_SIZE
+        && read_at(input, bounds.end - ID3V1_SIZE, 3)? == b"TAG"
+    {
+        bounds
+            .warnings
+            .push("ID3v1 tag after the last frame".to_string());
+        bounds.end -= ID3V1_SIZE;
+    }
+    if let Some(size) = ape_size(input, bounds.start, bounds.end)? {
+        bounds
+            .warnings
+            .push(format!("APE tag after the last frame ({} bytes)", size));
+        bounds.end -= size;
+    }
+
+    Ok(bounds)
+}
+
+/// Check that a file starts like a FLAC stream (after any ID3v2 tags), reading only the
+/// first bytes. Returns None for a FLAC stream, otherwise a guess at what the file is
+/// instead, e.g. "looks like MPEG audio". Ogg files count as FLAC streams, as they may
+/// well be Ogg FLAC.
+pub fn probe_stream_start<R: Read + Seek>(input: &mut R) -> io::Result<Option<&'static str>> {
+    let len = input.seek(SeekFrom::End(0))?;
+    let mut start = 0;
+    while let Some(size) = id3v2_size(input, start, len)? {
+        start += size;
+    }
+    if len == 0 {
+        return Ok(Some("empty file"));
+    }
+    if len < start + 4 {
+        return Ok(Some("too short to hold a FLAC stream"));
+    }
+
+    let magic = read_at(input, start, (len - start).min(16) as usize)?;
+    if magic.starts_with(b"fLaC") || magic.starts_with(b"OggS") {
+        return Ok(None);
+    }
+
+    let kind = match magic.as_slice() {
+        // An MPEG frame header starts with 11 set bits
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 => "looks like MPEG audio",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => "looks like WAV",
+        [b'F', b'O', b'R', b'M', ..] => "looks like AIFF",
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => "looks like MP4/M4A (AAC or ALAC)",
+        [b'w', b'v', b'p', b'k', ..] => "looks like WavPack",
+        [b'M', b'A', b'C', b' ', ..] => "looks like Monkey's Audio",
+        [0x30, 0x26, 0xB2, 0x75, ..] => "looks like WMA",
+        _ if magic.iter().all(|&b| b == 0) => "starts with zeros, the data may be lost",
+        _ => "unknown data",
+    };
+    Ok(Some(kind))
+}
+
+/// Total size of an ID3v2 tag starting at `offset`, if there is one
+fn id3v2_size<R: Read + Seek>(input: &mut R, offset: u64, len: u64) -> io::Result<Option<u64>> {
+    if len < offset + 10 {
+        return Ok(None);
+    }
+
+    // "ID3", version, revision, flags, then the size as four 7-bit "syncsafe" bytes
+    let header = read_at(input, offset, 10)?;
+    if &header[0..3] != b"ID3" || header[6..10].iter().any(|b| b & 0x80 != 0) {
+        return Ok(None);
+    }
+    let body = header[6..10]
+        .iter()
+        .fold(0u64, |size, &b| (size << 7) | u64::from(b));
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+
+    let size = 10 + body + footer;
+    Ok((offset + size <= len).then_some(size))
+}
+
+/// Total size of an APE tag ending at `end` (footer plus optional header), if there is one
+fn ape_size<R: Read + Seek>(input: &mut R, start: u64, end: u64) -> io::Result<Option<u64>> {
+    if end < start + APE_FOOTER_SIZE {
+        return Ok(None);
+    }
+
+    // "APETAGEX", version, tag size (items + footer), item count, flags, reserved
+    let footer = read_at(input, end - APE_FOOTER_SIZE, APE_FOOTER_SIZE as usize)?;
+    if &footer[0..8] != b"APETAGEX" {
+        return Ok(None);
+    }
+    let le_u32 = |at: usize| u32::from_le_bytes(footer[at..at + 4].try_into().unwrap());
+    let has_header = le_u32(20) & 0x8000_0000 != 0;
+    let size = u64::from(le_u32(12)) + if has_header { APE_FOOTER_SIZE } else { 0 };
+
+    Ok((size >= APE_FOOTER_SIZE && start + size <= end).then_some(size))
+}
+
+fn read_at<R: Read + Seek>(input: &mut R, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    input.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0; len];
+    input.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// The start of a FLAC stream: the marker and a STREAMINFO block header
+    const FLAC: &[u8] = b"fLaC\x80\x00\x00\x22";
+
+    fn probe(bytes: &[u8]) -> Option<&'static str> {
+        probe_stream_start(&mut Cursor::new(bytes)).unwrap()
+    }
+
+    /// An ID3v2.4 tag with `body` bytes of frames, and a footer if `footer`
+    fn id3v2(body: usize, footer: bool) -> Vec<u8> {
+        let size = [21, 14, 7, 0].map(|shift| (body >> shift) as u8 & 0x7F);
+        let mut tag = b"ID3\x04\x00".to_vec();
+        tag.push(if footer { 0x10 } else { 0 });
+        tag.extend(size);
+        tag.resize(10 + body, 0);
+        if footer {
+            tag.extend(b"3DI\x04\x00\x10");
+            tag.extend(size);
+        }
+        tag
+    }
+
+    #[test]
+    fn flac_streams_pass_the_probe() {
+        assert_eq!(probe(FLAC), None);
+        assert_eq!(probe(b"OggS\x00\x02"), None);
+
+        let mut tagged = id3v2(300, false);
+        tagged.extend(FLAC);
+        assert_eq!(probe(&tagged), None);
+
+        let mut twice = id3v2(20, false);
+        twice.extend(id3v2(200, false));
+        twice.extend(FLAC);
+        assert_eq!(probe(&twice), None);
+
+        // A body of 0x80 bytes needs the second syncsafe byte
+        let mut with_footer = id3v2(0x80, true);
+        with_footer.extend(FLAC);
+        assert_eq!(probe(&with_footer), None);
+    }
+
+    #[test]
+    fn other_files_fail_the_probe() {
+        assert_eq!(probe(b""), Some("empty file"));
+        assert_eq!(probe(b"fLa"), Some("too short to hold a FLAC stream"));
+        assert_eq!(
+            probe(&id3v2(10, false)),
+            Some("too short to hold a FLAC stream")
+        );
+        assert_eq!(
+            probe(b"\xFF\xFB\x90\x64\x00\x00\x00\x00"),
+            Some("looks like MPEG audio")
+        );
+        assert_eq!(
+            probe(b"RIFF\x24\x08\x00\x00WAVEfmt \x10\x00\x00\x00"),
+            Some("looks like WAV")
+        );
+        assert_eq!(
+            probe(&[0; 64]),
+            Some("starts with zeros, the data may be lost")
+        );
+        assert_eq!(probe(b"not audio at all"), Some("unknown data"));
+    }
+}
@@ -0,0 +1,121 @@
+use colored::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Once, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+/// How long a terminated run gets to abandon its in-flight files and save before the
+/// process exits anyway (well within systemd's default stop timeout of 90s)
+#[cfg(not(windows))]
+pub const TERMINATION_GRACE: Duration = Duration::from_secs(10);
+
+/// Windows force-ends a console process about 5s after a close, logoff or shutdown
+/// event, so the run has to be done (and the lock released) before then
+#[cfg(windows)]
+pub const TERMINATION_GRACE: Duration = Duration::from_secs(4);
+
+/// Set when the service manager (SIGTERM) or Windows (console closed, logoff,
+/// shutdown) asked the process to quit
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+/// The flag of the run to stop, set from the signal handler
+static INTERRUPTED: OnceLock<&'static AtomicBool> = OnceLock::new();
+
+/// Whether termination was requested since the handler was installed
+pub fn terminated() -> bool {
+    TERMINATED.load(Ordering::SeqCst)
+}
+
+/// Treat termination requests like Ctrl-C: `interrupted` is set so the run stops
+/// scheduling files, abandons the ones in flight and saves. If that takes longer than
+/// TERMINATION_GRACE, the lock is released and the process exits with `exit_code`.
+/// Installed once per process.
+pub fn install_termination_handler(interrupted: &'static AtomicBool, exit_code: u8) {
+    static INSTALL: Once = Once::new();
+
+    TERMINATED.store(false, Ordering::SeqCst);
+    INSTALL.call_once(|| {
+        INTERRUPTED.get_or_init(|| interrupted);
+        if !register() {
+            eprintln!("Warning: Failed to install the termination handler");
+            return;
+        }
+
+        // Signal handlers can't do much safely, so the reporting and the deadline live here
+        let watchdog = thread::Builder::new()
+            .name("termination watchdog".to_string())
+            .spawn(move || {
+                while !terminated() {
+                    thread::sleep(Duration::from_millis(100));
+                }
+                eprintln!(
+                    "\n{} Terminated, abandoning in-flight files and saving the job file...",
+                    "⚠".yellow().bold()
+                );
+                thread::sleep(TERMINATION_GRACE);
+                eprintln!(
+                    "{} Did not finish within {}s of termination, exiting",
+                    "⚠".yellow().bold(),
+                    TERMINATION_GRACE.as_secs()
+                );
+                crate::lock::release_held_lock();
+                std::process::exit(i32::from(exit_code));
+            });
+        if let Err(e) = watchdog {
+            eprintln!("Warning: Failed to start the termination watchdog: {}", e);
+        }
+    });
+}
+
+/// Only async-signal-safe work here: two atomic stores
+fn request_termination() {
+    TERMINATED.store(true, Ordering::SeqCst);
+    if let Some(interrupted) = INTERRUPTED.get() {
+        interrupted.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(unix)]
+fn register() -> bool {
+    extern "C" fn on_sigterm(_: libc::c_int) {
+        request_termination();
+    }
+
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigterm as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGTERM, &action, std::ptr::null_mut()) == 0
+    }
+}
+
+/// Windows ends the process as soon as the handler returns from a close, logoff or
+/// shutdown event (and about 5s after the event if it doesn't), so the handler waits
+/// out the shorter Windows grace period while the run saves
+#[cfg(windows)]
+fn register() -> bool {
+    use windows_sys::Win32::System::Console::{
+        CTRL_CLOSE_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT, SetConsoleCtrlHandler,
+    };
+    use windows_sys::core::BOOL;
+
+    unsafe extern "system" fn on_console_event(event: u32) -> BOOL {
+        match event {
+            CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT => {
+                request_termination();
+                thread::sleep(TERMINATION_GRACE);
+                1
+            }
+            // Ctrl-C and Ctrl-Break are left to the Ctrl-C handler
+            _ => 0,
+        }
+    }
+
+    unsafe { SetConsoleCtrlHandler(Some(on_console_event), 1) != 0 }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn register() -> bool {
+    false
+}
@@ -1,24 +1,139 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use indicatif::BinaryBytes;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use crate::util::{format_count, path_relative_to};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Exit code used by explore --dry-run when no FLAC files would be included
+pub const EXIT_NOTHING_FOUND: u8 = 1;
+
+/// Exit code used when at least one file is Bad
+pub const EXIT_BAD: u8 = 2;
+
+/// Exit code used when at least one file could not be checked (and none is Bad)
+pub const EXIT_ERROR: u8 = 3;
+
+/// Exit code used for operational failures (unreadable job file, invalid arguments, ...)
+pub const EXIT_OPERATIONAL: u8 = 4;
+
+/// Exit code used when a check run is aborted because --max-errors was reached
+pub const EXIT_MAX_ERRORS: u8 = 5;
+
+/// Exit code for a set of results: Bad takes precedence over Error
+pub fn result_exit_code(bad: usize, errors: usize) -> u8 {
+    if bad > 0 {
+        EXIT_BAD
+    } else if errors > 0 {
+        EXIT_ERROR
+    } else {
+        0
+    }
+}
+
+/// Exit code used when a check run is interrupted with Ctrl-C (128 + SIGINT, like shells)
+pub const EXIT_INTERRUPTED: u8 = 130;
+
+/// Exit code used when a check run is stopped by SIGTERM or the console closing
+/// (128 + SIGTERM)
+pub const EXIT_TERMINATED: u8 = 143;
 
 /// Status of a FLAC file check
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum FlacStatus {
     /// Not yet checked
+    #[default]
     ToBeChecked,
     /// Currently being checked
     Checking,
     /// Check passed successfully
     Ok,
+    /// Check passed, but with non-fatal findings (see the job's warnings)
+    Warning,
     /// File is corrupted or invalid
     Bad,
     /// An error occurred during checking
     Error,
+    /// File no longer exists on disk
+    Missing,
+    /// Left out of a check run by a --skip pattern (checked again by the next run without it)
+    Skipped,
+    /// A hardlink to the file of another job (see duplicate_of), not checked by default
+    Duplicate,
+}
+
+impl FlacStatus {
+    /// Every status, in the order they are shown in summaries
+    pub const ALL: [FlacStatus; 9] = [
+        FlacStatus::ToBeChecked,
+        FlacStatus::Checking,
+        FlacStatus::Ok,
+        FlacStatus::Warning,
+        FlacStatus::Bad,
+        FlacStatus::Error,
+        FlacStatus::Missing,
+        FlacStatus::Skipped,
+        FlacStatus::Duplicate,
+    ];
+
+    /// Name used for this status on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            FlacStatus::ToBeChecked => "to_be_checked",
+            FlacStatus::Checking => "checking",
+            FlacStatus::Ok => "ok",
+            FlacStatus::Warning => "warning",
+            FlacStatus::Bad => "bad",
+            FlacStatus::Error => "error",
+            FlacStatus::Missing => "missing",
+            FlacStatus::Skipped => "skipped",
+            FlacStatus::Duplicate => "duplicate",
+        }
+    }
+}
+
+impl std::fmt::Display for FlacStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for FlacStatus {
+    type Err = String;
+
+    /// Parse a status name case-insensitively, ignoring dashes and underscores
+    /// ("to_be_checked", "TOBECHECKED" and "pending" are all accepted)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s
+            .trim()
+            .chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .collect::<String>()
+            .to_lowercase();
+
+        if normalized == "pending" {
+            return Ok(FlacStatus::ToBeChecked);
+        }
+
+        FlacStatus::ALL
+            .into_iter()
+            .find(|status| status.name().replace('_', "") == normalized)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = FlacStatus::ALL.iter().map(|s| s.name()).collect();
+                format!(
+                    "unknown status '{}' (valid values: {})",
+                    s.trim(),
+                    valid.join(", ")
+                )
+            })
+    }
 }
 
 /// Represents a single FLAC file to be checked
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FlacJob {
     /// Full path to the FLAC file
     pub path: PathBuf,
@@ -27,19 +142,452 @@ pub struct FlacJob {
     /// Optional error message if status is Error
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_message: Option<String>,
+    /// Problems found by the last check that don't make the file Bad on their own
+    /// (e.g. ID3 tags wrapped around the stream); the file then has status Warning
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// When this file was last verified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<DateTime<Utc>>,
+    /// How long the last verification took, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check_duration_ms: Option<u64>,
+    /// File size in bytes when the job file was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// File modification time when the job file was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<DateTime<Utc>>,
+    /// Set when the last check found a different size or modification time on disk
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub changed: bool,
+    /// Which decoder produced the last verdict ("claxon" or "flac")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verifier: Option<String>,
+    /// MD5 of the decoded audio from the last check, as lowercase hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_md5: Option<String>,
+    /// Whether the STREAMINFO header carries an MD5 signature (None until claxon has
+    /// decoded the file)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_md5: Option<bool>,
+    /// Audio format from the STREAMINFO header, recorded when claxon decodes the file
+    /// (or by explore --read-metadata)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bits_per_sample: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u32>,
+    /// Samples per channel, if the header gives the length of the stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_samples: Option<u64>,
+    /// Length of the audio in seconds, from total_samples and sample_rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f64>,
+    /// Where the file was before --quarantine moved it (`path` is its new location)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantined_from: Option<PathBuf>,
+    /// The --skip pattern that kept the file out of the last run (status Skipped)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_pattern: Option<String>,
+    /// SHA-256 of the entire file as lowercase hex, recorded by check --hash-file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_sha256: Option<String>,
+    /// How thoroughly the last verdict was reached: "quick" files only had their headers
+    /// and first frame checked (--quick) and are picked up again by the next full check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified: Option<VerifyLevel>,
+    /// Where in the stream decoding failed, for files that are Bad or Error because of
+    /// damage at a specific spot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_at: Option<FailurePosition>,
+    /// For a Duplicate: the job whose file this path is a hardlink to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<PathBuf>,
+}
+
+/// How much of a file a check looked at
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyLevel {
+    /// Headers, metadata blocks and the first frame only
+    Quick,
+    /// Every frame decoded (and the MD5 compared, if there is one)
+    Full,
+}
+
+/// Position of the damage in a stream that could not be fully decoded
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct FailurePosition {
+    /// Samples per channel decoded before the damaged frame
+    pub sample: u64,
+    /// Approximate offset of the damaged frame in the file, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub byte: Option<u64>,
+}
+
+impl FlacJob {
+    /// Create a new job for a file that has not been checked yet
+    pub fn new(path: PathBuf) -> Self {
+        FlacJob {
+            path,
+            ..Default::default()
+        }
+    }
+
+    /// Record the file's current size and modification time
+    pub fn record_metadata(&mut self, metadata: &fs::Metadata) {
+        self.size = Some(metadata.len());
+        self.modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+    }
+
+    /// Record the audio format and length read from the STREAMINFO header
+    pub fn record_format(
+        &mut self,
+        sample_rate: u32,
+        bits_per_sample: u32,
+        channels: u32,
+        total_samples: Option<u64>,
+    ) {
+        self.sample_rate = Some(sample_rate);
+        self.bits_per_sample = Some(bits_per_sample);
+        self.channels = Some(channels);
+        // A header may leave the length out (0), and a rate of 0 would make it meaningless
+        self.total_samples = total_samples.filter(|&samples| samples > 0);
+        self.duration_seconds = self
+            .total_samples
+            .filter(|_| sample_rate > 0)
+            .map(|samples| samples as f64 / f64::from(sample_rate));
+    }
+
+    /// Compare the file's current metadata against what was recorded at explore time
+    /// Returns None if nothing was recorded (old job files), otherwise whether it differs
+    pub fn metadata_changed(&self, metadata: &fs::Metadata) -> Option<bool> {
+        let size = self.size?;
+        let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+        Some(size != metadata.len() || (self.modified.is_some() && self.modified != modified))
+    }
+
+    /// Whether the file passed only because it decodes, without an MD5 to compare against
+    pub fn verified_without_md5(&self) -> bool {
+        matches!(self.status, FlacStatus::Ok | FlacStatus::Warning)
+            && self.has_md5 == Some(false)
+            && !self.quick_only()
+    }
+
+    /// Whether the file passed a --quick check and has yet to have its audio verified
+    pub fn quick_only(&self) -> bool {
+        matches!(self.status, FlacStatus::Ok | FlacStatus::Warning)
+            && self.verified == Some(VerifyLevel::Quick)
+    }
 }
 
 /// Container for all FLAC jobs in a directory
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JobFile {
-    /// Root directory that was scanned
-    pub root_directory: PathBuf,
+    /// Root directories that were scanned (job files written before explore took several
+    /// directories have a single `root_directory`)
+    #[serde(alias = "root_directory", deserialize_with = "one_or_more_paths")]
+    pub root_directories: Vec<PathBuf>,
     /// Total number of FLAC files found
     pub total_files: usize,
     /// Statistics by status
     pub statistics: Statistics,
     /// List of all FLAC files to check
     pub jobs: Vec<FlacJob>,
+    /// For a results file written by check --results: the job file the run started from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub results_of: Option<PathBuf>,
+    /// The explore --exclude patterns the job was created with, for reference
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// The explore --exclude-job files whose files were left out of the job
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_jobs: Vec<PathBuf>,
+    /// The explore --max-depth the job was created with, for reference
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    /// The explore --min-size the job was created with, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    /// The explore --max-size the job was created with, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// The explore --modified-since (or --modified-since-file) time the job was created
+    /// with: older files were left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_since: Option<DateTime<Utc>>,
+    /// The explore --extensions the job was created with, when not just "flac"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// The job was created with explore --follow-symlinks
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub follow_symlinks: bool,
+    /// The job was created with explore --include-hidden
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub include_hidden: bool,
+    /// The job was created with explore --read-metadata
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_metadata: bool,
+    /// Job paths are stored relative to the root directory (explore --relative), so the
+    /// library can be mounted elsewhere. In memory they are always full paths.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub relative_paths: bool,
+    /// The root directory in the file, when --root replaced it for this run
+    #[serde(skip)]
+    pub stored_root: Option<PathBuf>,
+    /// Paths explore could not read, so anything below them is missing from the job
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scan_errors: Vec<ScanError>,
+    /// The .checkflacignore files that were in effect when the job was created
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_files: Vec<IgnoreFile>,
+    /// Set when explore --sample picked the jobs at random from everything it found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample: Option<Sample>,
+    /// Set when explore --shuffle put the jobs in random order, to the seed it used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shuffle_seed: Option<u64>,
+}
+
+/// A random selection made by explore --sample or --sample-percent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    /// Number of files picked
+    pub files: usize,
+    /// Number of files found, which the sample was taken from
+    pub population: usize,
+    /// Seed of the selection; explore --seed with the same value picks the same files
+    pub seed: u64,
+}
+
+/// A .checkflacignore file applied by explore, with its valid patterns
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IgnoreFile {
+    pub path: PathBuf,
+    pub patterns: Vec<String>,
+}
+
+/// A directory or file the explore walk could not read (permission denied, unreadable
+/// mount, ...)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Accept either a single path (old job files) or a list of paths
+fn one_or_more_paths<'de, D>(deserializer: D) -> Result<Vec<PathBuf>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMore {
+        One(PathBuf),
+        More(Vec<PathBuf>),
+    }
+
+    Ok(match OneOrMore::deserialize(deserializer)? {
+        OneOrMore::One(path) => vec![path],
+        OneOrMore::More(paths) => paths,
+    })
+}
+
+/// The root directory a path lies under (the deepest one, should roots be nested)
+pub fn find_root<'a>(roots: &'a [PathBuf], path: &Path) -> Option<&'a Path> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .map(PathBuf::as_path)
+}
+
+impl JobFile {
+    /// The explore size filter, e.g. "at least 100.00 KiB", for the summaries
+    pub fn size_range(&self) -> Option<String> {
+        match (self.min_size, self.max_size) {
+            (None, None) => None,
+            (Some(min), None) => Some(format!("at least {}", BinaryBytes(min))),
+            (None, Some(max)) => Some(format!("at most {}", BinaryBytes(max))),
+            (Some(min), Some(max)) => Some(format!("{} to {}", BinaryBytes(min), BinaryBytes(max))),
+        }
+    }
+
+    /// What the job is a sample of, e.g. "500 of 41,203 files (1.2%), seed 42", for the
+    /// summaries
+    pub fn sample_note(&self) -> Option<String> {
+        self.sample.as_ref().map(|sample| {
+            format!(
+                "{} of {} files ({:.1}%), seed {}",
+                format_count(sample.files as u64),
+                format_count(sample.population as u64),
+                sample.files as f64 * 100.0 / sample.population.max(1) as f64,
+                sample.seed
+            )
+        })
+    }
+
+    /// A path relative to the root directory it lies under, or the full path if it
+    /// lies under none of them
+    pub fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        find_root(&self.root_directories, path)
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+    }
+
+    /// Read and parse a job file from disk (`-` reads it from stdin)
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::load_with_root(path, None)
+    }
+
+    /// Read and parse a job file, resolving relative job paths against `root` instead of
+    /// the stored root directory (which is kept for saving)
+    pub fn load_with_root(path: &Path, root: Option<&Path>) -> Result<Self> {
+        let content = if path == Path::new("-") {
+            let mut content = String::new();
+            io::stdin()
+                .read_to_string(&mut content)
+                .context("Failed to read job file from stdin")?;
+            content
+        } else {
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read job file: {}", path.display()))?
+        };
+
+        let mut job_file: JobFile =
+            serde_json::from_str(&content).context("Failed to parse job file JSON")?;
+        if !job_file.relative_paths {
+            if root.is_some() {
+                anyhow::bail!(
+                    "--root needs a job file with relative paths (explore --relative), {} stores full paths",
+                    path.display()
+                );
+            }
+            return Ok(job_file);
+        }
+
+        let [stored_root] = job_file.root_directories.as_slice() else {
+            anyhow::bail!("Job file with relative paths must have exactly one root directory");
+        };
+        let stored_root = stored_root.clone();
+        let root = root.map_or_else(|| stored_root.clone(), Path::to_path_buf);
+        for job in &mut job_file.jobs {
+            let paths = std::iter::once(&mut job.path)
+                .chain(job.quarantined_from.as_mut())
+                .chain(job.duplicate_of.as_mut());
+            for path in paths {
+                if path.has_root() {
+                    anyhow::bail!(
+                        "Job file mixes relative and absolute paths: {} is absolute, but the job stores paths relative to its root directory",
+                        path.display()
+                    );
+                }
+                *path = root.join(&*path);
+            }
+        }
+        if root != stored_root {
+            job_file.root_directories = vec![root];
+            job_file.stored_root = Some(stored_root);
+        }
+        Ok(job_file)
+    }
+
+    /// Save the job file to disk atomically
+    /// The JSON is written to a sibling temporary file which is then renamed over the
+    /// original, so an interrupted write never leaves a truncated job file behind
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = self.to_json()?;
+        write_file_atomic(path, json.as_bytes())
+            .with_context(|| format!("Failed to write job file to {}", path.display()))
+    }
+
+    /// The job file as it is saved, pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        if self.relative_paths {
+            serde_json::to_string_pretty(&self.to_relative_json()?)
+        } else {
+            serde_json::to_string_pretty(self)
+        }
+        .context("Failed to serialize job file")
+    }
+
+    /// The JSON of a job file with relative paths: job paths relative to the root
+    /// directory, and the stored root rather than a --root replacement
+    fn to_relative_json(&self) -> Result<serde_json::Value> {
+        let root = &self.root_directories[0];
+        let mut json = serde_json::to_value(self)?;
+        if let Some(stored_root) = &self.stored_root {
+            json["root_directories"] = serde_json::to_value([stored_root])?;
+        }
+        for (idx, job) in self.jobs.iter().enumerate() {
+            let entry = &mut json["jobs"][idx];
+            entry["path"] = serde_json::to_value(relative_to_root(&job.path, root)?)?;
+            if let Some(from) = &job.quarantined_from {
+                entry["quarantined_from"] = serde_json::to_value(relative_to_root(from, root)?)?;
+            }
+            if let Some(original) = &job.duplicate_of {
+                entry["duplicate_of"] = serde_json::to_value(relative_to_root(original, root)?)?;
+            }
+        }
+        Ok(json)
+    }
+}
+
+/// A job path relative to the root directory, for a job file with relative paths
+fn relative_to_root(path: &Path, root: &Path) -> Result<PathBuf> {
+    path_relative_to(path, root).with_context(|| {
+        format!(
+            "Cannot store {} relative to the root directory {}",
+            path.display(),
+            root.display()
+        )
+    })
+}
+
+/// Write data to a file atomically using a temporary file and rename
+/// The temporary file is fsynced before the rename so the new contents are on disk
+/// before they replace the old ones
+pub fn write_file_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    // Build the temporary path next to the target (e.g. job.json -> job.json.tmp)
+    // so that the rename stays on the same filesystem
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        file.write_all(data)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        file.sync_all()
+            .with_context(|| format!("Failed to sync {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to rename {} into place", tmp_path.display()))?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        // Don't leave a half-written temporary file lying around
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    // Sync the parent directory so the rename itself is durable (best effort)
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
 }
 
 /// Statistics about the job file
@@ -48,8 +596,16 @@ pub struct Statistics {
     pub to_be_checked: usize,
     pub checking: usize,
     pub ok: usize,
+    #[serde(default)]
+    pub warning: usize,
     pub bad: usize,
     pub error: usize,
+    #[serde(default)]
+    pub missing: usize,
+    #[serde(default)]
+    pub skipped: usize,
+    #[serde(default)]
+    pub duplicate: usize,
 }
 
 impl Statistics {
@@ -59,8 +615,12 @@ impl Statistics {
             to_be_checked: 0,
             checking: 0,
             ok: 0,
+            warning: 0,
             bad: 0,
             error: 0,
+            missing: 0,
+            skipped: 0,
+            duplicate: 0,
         };
 
         // Count each status type
@@ -69,11 +629,67 @@ impl Statistics {
                 FlacStatus::ToBeChecked => stats.to_be_checked += 1,
                 FlacStatus::Checking => stats.checking += 1,
                 FlacStatus::Ok => stats.ok += 1,
+                FlacStatus::Warning => stats.warning += 1,
                 FlacStatus::Bad => stats.bad += 1,
                 FlacStatus::Error => stats.error += 1,
+                FlacStatus::Missing => stats.missing += 1,
+                FlacStatus::Skipped => stats.skipped += 1,
+                FlacStatus::Duplicate => stats.duplicate += 1,
             }
         }
 
         stats
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_dir;
+
+    const JOB_FILE: &str = r#"{
+  "root_directories": ["/music"],
+  "total_files": 1,
+  "statistics": {"to_be_checked": 0, "checking": 0, "ok": 1, "bad": 0, "error": 0},
+  "jobs": [{"path": "/music/a.flac", "status": "OK"}]
+}"#;
+
+    #[test]
+    fn interrupted_save_keeps_the_job_file() {
+        let dir = test_dir("interrupted-save");
+        let path = dir.join("job.json");
+        fs::write(&path, JOB_FILE).unwrap();
+        let mut job_file = JobFile::load(&path).unwrap();
+        job_file.jobs[0].status = FlacStatus::Bad;
+
+        // A directory in the way of the temporary file makes the write fail before the
+        // rename, as a full disk or a crash would
+        fs::create_dir(dir.join("job.json.tmp")).unwrap();
+        assert!(job_file.save(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), JOB_FILE);
+
+        // Once the write can go through, the new contents replace the old ones whole
+        fs::remove_dir(dir.join("job.json.tmp")).unwrap();
+        job_file.save(&path).unwrap();
+        let saved = JobFile::load(&path).unwrap();
+        assert_eq!(saved.jobs[0].status, FlacStatus::Bad);
+        assert!(!dir.join("job.json.tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_rename_leaves_no_temporary_file() {
+        let dir = test_dir("failed-rename");
+        // Renaming a file over a directory fails after the temporary file was written
+        let target = dir.join("job.json");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("keep"), "").unwrap();
+
+        assert!(write_file_atomic(&target, b"new contents").is_err());
+        assert!(target.join("keep").exists());
+        assert!(!dir.join("job.json.tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
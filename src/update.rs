@@ -0,0 +1,195 @@
+use crate::explore::{
+    ExploreOptions, dedup_by_real_path, exclude_covered_files, load_covered_files, new_job,
+    print_covered_counts, print_impostors, read_stream_info, scan_directories,
+};
+use crate::lock::JobLock;
+use crate::types::{FlacStatus, JobFile, Statistics};
+use anyhow::Result;
+use chrono::Utc;
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Options controlling the update command
+pub struct UpdateOptions {
+    /// Remove the entries of files that no longer exist instead of marking them Missing
+    pub prune: bool,
+    /// Don't probe new files for the fLaC marker
+    pub no_probe: bool,
+    /// Walk each root directory with this many threads
+    pub scan_threads: Option<usize>,
+    /// Where the library is mounted, for a job file with relative paths
+    pub root: Option<PathBuf>,
+    /// Take over a lock file left behind by a checkflac process that no longer exists
+    pub force: bool,
+}
+
+/// Bring a job file up to date with the filesystem: walk its root directories again with
+/// the settings it was explored with, add the FLAC files that are new, and mark the
+/// entries whose file is gone as Missing (or remove them with --prune)
+/// Entries whose file is still there keep their status and history.
+pub fn update_job_file(job_file_path: PathBuf, options: UpdateOptions) -> Result<()> {
+    if job_file_path == Path::new("-") {
+        anyhow::bail!("update rewrites the job file, so it can't be read from stdin (`-`)");
+    }
+
+    // Keep check runs off the job file while it is rewritten
+    let _lock = JobLock::acquire(&job_file_path, options.force)?;
+
+    println!("{} Loading job file...", "→".blue().bold());
+    let mut job_file = JobFile::load_with_root(&job_file_path, options.root.as_deref())?;
+    if let Some(note) = job_file.sample_note() {
+        anyhow::bail!(
+            "The job file is a sample ({}); adding every new file would make it something else, explore again instead",
+            note
+        );
+    }
+
+    // An unmounted library would otherwise turn every entry Missing
+    for directory in &job_file.root_directories {
+        if !directory.is_dir() {
+            anyhow::bail!(
+                "Root directory is not available: {} (is the library mounted?)",
+                directory.display()
+            );
+        }
+        println!(
+            "{} Exploring directory: {}",
+            "→".blue().bold(),
+            directory.display()
+        );
+    }
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.cyan} {msg}")
+            .unwrap(),
+    );
+    spinner.set_message("Scanning directory tree...");
+    let scan_options = ExploreOptions {
+        scan_threads: options.scan_threads,
+        ..ExploreOptions::from_job_file(&job_file)
+    };
+    let covered = load_covered_files(&scan_options.exclude_jobs)?;
+    let mut scan = scan_directories(&job_file.root_directories, &scan_options, &spinner)?;
+    spinner.finish_and_clear();
+    if scan_options.follow_symlinks {
+        scan.files = dedup_by_real_path(scan.files);
+    }
+    let covered_counts = exclude_covered_files(&mut scan.files, &covered);
+    print_covered_counts(&covered, &covered_counts);
+
+    // A sorted job file stays sorted; one explored with --no-sort gets the new files
+    // appended in the order they were found
+    let was_sorted = job_file
+        .jobs
+        .windows(2)
+        .all(|pair| pair[0].path <= pair[1].path);
+    if was_sorted {
+        scan.files.sort();
+    }
+
+    // Entries whose file is gone
+    let mut gone = Vec::new();
+    let mut reappeared = Vec::new();
+    let now = Utc::now();
+    job_file.jobs.retain_mut(|job| {
+        if job.path.exists() {
+            if job.status == FlacStatus::Missing {
+                job.status = FlacStatus::ToBeChecked;
+                job.error_message = None;
+                reappeared.push(job.path.clone());
+            }
+            return true;
+        }
+        if options.prune || job.status != FlacStatus::Missing {
+            gone.push(job.path.clone());
+        }
+        if options.prune {
+            return false;
+        }
+        if job.status != FlacStatus::Missing {
+            job.status = FlacStatus::Missing;
+            job.error_message = Some("File not found".to_string());
+            job.skip_pattern = None;
+            job.failed_at = None;
+            job.checked_at = Some(now);
+        }
+        true
+    });
+    let unchanged =
+        job_file.jobs.len() - reappeared.len() - if options.prune { 0 } else { gone.len() };
+
+    // Files the job doesn't know yet
+    let known: HashSet<PathBuf> = job_file.jobs.iter().map(|job| job.path.clone()).collect();
+    let mut seen = HashSet::new();
+    let mut added: Vec<_> = scan
+        .files
+        .into_iter()
+        .filter(|path| !known.contains(path) && seen.insert(path.clone()))
+        .map(|path| new_job(path, !options.no_probe))
+        .collect();
+    if scan_options.read_metadata {
+        read_stream_info(&mut added);
+    }
+
+    for job in &added {
+        println!("  {} {}", "+".green(), job.path.display());
+    }
+    for path in &gone {
+        println!("  {} {}", "-".red(), path.display());
+    }
+    for path in &reappeared {
+        println!("  {} {} {}", "+".green(), path.display(), "(back)".dimmed());
+    }
+    print_impostors(&added);
+
+    for warning in &scan.ignore_warnings {
+        println!("{} {}", "⚠".yellow().bold(), warning);
+    }
+    if !scan.errors.is_empty() {
+        println!(
+            "{} Could not read {} paths, files below them may be reported missing:",
+            "⚠".yellow().bold(),
+            scan.errors.len()
+        );
+        for scan_error in &scan.errors {
+            println!(
+                "  {} {}: {}",
+                "✗".red(),
+                scan_error.path.display(),
+                scan_error.error
+            );
+        }
+    }
+
+    let added_count = added.len();
+    job_file.jobs.extend(added);
+    if was_sorted {
+        job_file.jobs.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    job_file.total_files = job_file.jobs.len();
+    job_file.statistics = Statistics::from_jobs(&job_file.jobs);
+    job_file.scan_errors = scan.errors;
+    job_file.ignore_files = scan.ignore_files;
+
+    job_file.save(&job_file_path)?;
+
+    println!(
+        "{} Job file updated: {}",
+        "✓".green().bold(),
+        job_file_path.display()
+    );
+    println!(
+        "  Added {}, {} {}, back {}, unchanged {}",
+        added_count,
+        if options.prune { "removed" } else { "missing" },
+        gone.len(),
+        reappeared.len(),
+        unchanged
+    );
+
+    Ok(())
+}
@@ -0,0 +1,276 @@
+use chrono::{DateTime, Utc};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Set when stdout carries data (explore -o - writes the job file there), so progress and
+/// summary output goes to stderr instead
+static STATUS_ON_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Send the output of `outln!` to stderr from now on
+pub fn send_status_to_stderr() {
+    STATUS_ON_STDERR.store(true, Ordering::Relaxed);
+}
+
+pub fn status_on_stderr() -> bool {
+    STATUS_ON_STDERR.load(Ordering::Relaxed)
+}
+
+/// println! for progress and summary output: stdout, or stderr while stdout carries data
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        if $crate::util::status_on_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+pub(crate) use outln;
+
+/// A seed for `shuffle` that differs from run to run
+pub fn clock_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Fisher-Yates shuffle driven by a xorshift generator, so the same seed gives the same
+/// order
+pub fn shuffle<T>(items: &mut [T], seed: u64) {
+    // Spread the seed's bits, so nearby seeds (or 0) still start from a usable state
+    let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+    for i in (1..items.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        items.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
+
+/// Parse a human-friendly duration such as "30s", "90m", "2h", "7d" or "1h30m"
+/// A bare number is interpreted as seconds
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("empty duration".to_string());
+    }
+
+    // A plain number means seconds
+    if let Ok(seconds) = input.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    let mut total = 0u64;
+    let mut number = String::new();
+
+    // Walk through the string collecting <number><unit> pairs
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let value: u64 = number
+            .parse()
+            .map_err(|_| format!("invalid duration '{}'", input))?;
+        number.clear();
+
+        let multiplier = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => {
+                return Err(format!(
+                    "invalid duration unit '{}' in '{}' (use s, m, h, d or w)",
+                    c, input
+                ));
+            }
+        };
+        total += value * multiplier;
+    }
+
+    // Trailing digits without a unit are ambiguous (e.g. "1h30")
+    if !number.is_empty() {
+        return Err(format!("missing unit after '{}' in '{}'", number, input));
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+/// Parse a point in time given as an RFC 3339 timestamp ("2024-05-01T18:00:00+02:00") or
+/// as a duration back from now ("7d", "36h")
+pub fn parse_time_reference(input: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(input.trim()) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let ago = parse_duration(input).map_err(|_| {
+        format!(
+            "invalid time '{}' (use an RFC 3339 timestamp such as 2024-05-01T18:00:00Z, or a duration such as 7d)",
+            input
+        )
+    })?;
+    chrono::Duration::from_std(ago)
+        .ok()
+        .and_then(|ago| Utc::now().checked_sub_signed(ago))
+        .ok_or_else(|| format!("'{}' is too far back", input))
+}
+
+/// Parse a size such as "500M", "2G" or "1.5GB" into bytes (binary units, so "1K" is
+/// 1024 bytes); a bare number is interpreted as bytes
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let upper = input.to_ascii_uppercase();
+    let digits = upper.trim_end_matches(['B', 'I']);
+    let (number, multiplier) = match digits.char_indices().last() {
+        Some((i, 'K')) => (&digits[..i], 1u64 << 10),
+        Some((i, 'M')) => (&digits[..i], 1 << 20),
+        Some((i, 'G')) => (&digits[..i], 1 << 30),
+        Some((i, 'T')) => (&digits[..i], 1 << 40),
+        _ => (digits, 1),
+    };
+
+    let value: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size '{}' (e.g. 500M, 2G)", input))?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(format!("invalid size '{}'", input));
+    }
+    Ok((value * multiplier as f64) as u64)
+}
+
+/// Format a number with thousands separators (e.g. 10584000 -> "10,584,000")
+pub fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+
+    out
+}
+
+/// Format bytes as lowercase hex (e.g. an MD5 digest)
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Format a number of samples at a sample rate as audio time (e.g. "3:07.25")
+pub fn format_audio_time(samples: u64, sample_rate: u32) -> String {
+    let centis = samples * 100 / u64::from(sample_rate.max(1));
+    format!("{}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100)
+}
+
+/// Format a length of audio in seconds as hours, minutes and seconds (e.g. "52:07:19")
+pub fn format_play_time(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Format a sample rate in kHz (e.g. 44100 -> "44.1 kHz", 96000 -> "96 kHz")
+pub fn format_sample_rate(hz: u32) -> String {
+    let khz = format!("{:.1}", f64::from(hz) / 1000.0);
+    format!("{} kHz", khz.trim_end_matches(".0"))
+}
+
+/// Match a path (with `/` separators) against a glob pattern: `*` matches any run of
+/// characters within one path component, `**` any number of components and `?` one
+/// character other than `/`
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    glob_match_chars(&pattern, &path)
+}
+
+fn glob_match_chars(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        // "**/" also matches no directory at all ("**/x" matches "x")
+        ['*', '*', '/', rest @ ..] => (0..=path.len())
+            .filter(|&i| i == 0 || path[i - 1] == '/')
+            .any(|i| glob_match_chars(rest, &path[i..])),
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| glob_match_chars(rest, &path[i..])),
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != '/')
+            .any(|i| glob_match_chars(rest, &path[i..])),
+        ['?', rest @ ..] => {
+            path.first().is_some_and(|&c| c != '/') && glob_match_chars(rest, &path[1..])
+        }
+        [c, rest @ ..] => path.first() == Some(c) && glob_match_chars(rest, &path[1..]),
+    }
+}
+
+/// A path relative to `base`, going up with `..` for paths outside it (e.g. files moved
+/// to a quarantine directory next to the library)
+/// None when there is no such path: they don't share a root or prefix (another drive),
+/// or the base has `..` in the part to climb out of
+pub fn path_relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = path.strip_prefix(base) {
+        return Some(relative.to_path_buf());
+    }
+
+    let path_parts: Vec<Component> = path.components().collect();
+    let base_parts: Vec<Component> = base.components().collect();
+    let shared = path_parts
+        .iter()
+        .zip(&base_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+    // Can't go up from a prefix or root that differs, or through `..` in the base
+    let climbable = path.has_root() == base.has_root()
+        && base_parts[shared..]
+            .iter()
+            .all(|part| matches!(part, Component::Normal(_)));
+    if !climbable || (shared == 0 && path.has_root()) {
+        return None;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in shared..base_parts.len() {
+        relative.push("..");
+    }
+    relative.extend(&path_parts[shared..]);
+    Some(relative)
+}
+
+/// A fresh, empty directory for a test's files, under the system temp directory
+#[cfg(test)]
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("checkflac-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("failed to create the test directory");
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths() {
+        let relative = |path: &str, base: &str| path_relative_to(Path::new(path), Path::new(base));
+        assert_eq!(
+            relative("/music/A/x.flac", "/music"),
+            Some(PathBuf::from("A/x.flac"))
+        );
+        assert_eq!(
+            relative("/quarantine/x.flac", "/music/lib"),
+            Some(PathBuf::from("../../quarantine/x.flac"))
+        );
+        assert_eq!(
+            relative("lib/A/x.flac", "lib/B"),
+            Some(PathBuf::from("../A/x.flac"))
+        );
+        // No way from a relative base to an absolute path, or up through `..`
+        assert_eq!(relative("/music/x.flac", "lib"), None);
+        assert_eq!(relative("x.flac", "../lib"), None);
+    }
+}
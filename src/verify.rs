@@ -0,0 +1,135 @@
+use crate::check::{Verdict, Verification, VerifySettings, verify_file};
+use crate::explore::find_flac_files;
+use crate::types::result_exit_code;
+use anyhow::{Context, Result};
+use colored::*;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+
+/// Options controlling the verify command
+pub struct VerifyOptions {
+    /// Number of parallel threads (defaults to number of CPU cores)
+    pub threads: Option<usize>,
+}
+
+/// Counts of verify results, for the summary and the exit code
+#[derive(Default)]
+struct Counts {
+    ok: usize,
+    warnings: usize,
+    bad: usize,
+    errors: usize,
+}
+
+/// Verify files and directories directly, without a job file
+/// Directories are searched for .flac files; files are verified whatever their extension
+/// Returns the process exit code, with the same meaning as for check
+pub fn verify_paths(paths: Vec<PathBuf>, options: VerifyOptions) -> Result<u8> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut found = find_flac_files(&path, &ProgressBar::hidden())?;
+            found.sort();
+            files.extend(found);
+        } else {
+            // Missing files are reported with the results
+            files.push(path);
+        }
+    }
+
+    if files.is_empty() {
+        println!("{} No FLAC files found", "✓".green().bold());
+        return Ok(0);
+    }
+
+    let thread_count = options.threads.unwrap_or_else(num_cpus::get);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(thread_count)
+        .build()
+        .context("Failed to initialize thread pool")?;
+
+    println!(
+        "{} Verifying {} files with {} threads",
+        "→".blue().bold(),
+        files.len(),
+        thread_count
+    );
+
+    // Same defaults as check; the verify command doesn't take the check-only options
+    let settings = VerifySettings {
+        fallback_flac: None,
+        check_metadata: false,
+        check_seektable: false,
+        pipeline: false,
+        quick: false,
+    };
+    // Ctrl-C simply ends the process, so nothing ever cancels a file here
+    let cancel = AtomicBool::new(false);
+
+    // Results are printed as they come in, so a long run shows progress
+    let counts = Mutex::new(Counts::default());
+    pool.install(|| {
+        files.par_iter().for_each(|path| {
+            let result = if path.exists() {
+                verify_file(path, None, &settings, &cancel)
+            } else {
+                Err(anyhow::anyhow!("File does not exist"))
+            };
+            let mut counts = counts.lock().unwrap();
+            print_result(&path.display().to_string(), &result, &mut counts);
+        })
+    });
+
+    let counts = counts.into_inner().unwrap();
+    println!(
+        "\n{} {} OK, {} with warnings, {} bad, {} errors",
+        if counts.bad + counts.errors == 0 {
+            "✓".green().bold()
+        } else {
+            "⚠".yellow().bold()
+        },
+        counts.ok,
+        counts.warnings,
+        counts.bad,
+        counts.errors
+    );
+
+    Ok(result_exit_code(counts.bad, counts.errors))
+}
+
+/// Print one line per file, followed by the reason when it didn't simply pass
+fn print_result(path: &str, result: &Result<Verification>, counts: &mut Counts) {
+    match result {
+        Ok(verification) => match &verification.verdict {
+            Verdict::Ok if !verification.warnings.is_empty() => {
+                counts.warnings += 1;
+                println!(
+                    "  {} {} {}",
+                    "!".bright_yellow(),
+                    "WARNING".bright_yellow(),
+                    path
+                );
+                for warning in &verification.warnings {
+                    println!("    {}: {}", "Warning".dimmed(), warning.dimmed());
+                }
+            }
+            Verdict::Ok => {
+                counts.ok += 1;
+                println!("  {} {} {}", "✓".green(), "OK".green(), path);
+            }
+            Verdict::Bad(reason) => {
+                counts.bad += 1;
+                println!("  {} {} {}", "✗".red(), "BAD".red(), path);
+                println!("    {}: {}", "Reason".dimmed(), reason.dimmed());
+            }
+        },
+        Err(e) => {
+            counts.errors += 1;
+            println!("  {} {} {}", "⚠".yellow(), "ERROR".yellow(), path);
+            println!("    {}: {}", "Error".dimmed(), e.to_string().dimmed());
+        }
+    }
+}